@@ -0,0 +1,286 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+// Minimal ADIF (ADI text format) handling: just enough to see which fields a
+// record already has and to add more.  Wavelog does the real parsing.
+//
+// A field is `<NAME:LEN>VALUE` or `<NAME:LEN:TYPE>VALUE`, where LEN counts
+// the characters of VALUE.  Bare tags such as `<EOH>` and `<EOR>` have no
+// length.  Anything before `<EOH>` is the file header, not the record.
+
+// One tag found by `tags`: its upper-cased name, the byte offset of its
+// opening `<`, and the byte range of its value (empty for a bare tag).
+struct Tag {
+    name: String,
+    start: usize,
+    value: Range<usize>,
+}
+
+// The tags in `text`, in order.  Values are skipped by their declared length
+// so a `<` inside one is not taken for a tag.
+fn tags(text: &str) -> Vec<Tag> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(open) = text[pos..].find('<').map(|i| pos + i) {
+        let Some(close) = text[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let mut spec = text[open + 1..close].split(':');
+        let name = spec.next().unwrap_or_default().trim().to_ascii_uppercase();
+        let len: usize = spec.next().and_then(|l| l.trim().parse().ok()).unwrap_or(0);
+        pos = text[close + 1..]
+            .char_indices()
+            .nth(len)
+            .map_or(text.len(), |(i, _)| close + 1 + i);
+        found.push(Tag {
+            name,
+            start: open,
+            value: close + 1..pos,
+        });
+    }
+    found
+}
+
+// Byte offset where the record starts: just after `<EOH>`, or 0 with no header.
+fn record_start(text: &str) -> usize {
+    tags(text)
+        .iter()
+        .find(|t| t.name == "EOH")
+        .map_or(0, |t| t.start + "<EOH>".len())
+}
+
+/// The upper-cased names of the fields in the first record of `text`.
+pub fn field_names(text: &str) -> HashSet<String> {
+    let start = record_start(text);
+    tags(&text[start..])
+        .into_iter()
+        .take_while(|t| t.name != "EOR")
+        .map(|t| t.name)
+        .collect()
+}
+
+/// The value of field `name` (case-insensitive) in the first record of
+/// `text`, if it has one.
+pub fn field(text: &str, name: &str) -> Option<String> {
+    let record = &text[record_start(text)..];
+    tags(record)
+        .into_iter()
+        .take_while(|t| t.name != "EOR")
+        .find(|t| t.name.eq_ignore_ascii_case(name))
+        .map(|t| record[t.value].to_string())
+}
+
+/// Add each of `fields` that the record does not already have (names are
+/// compared case-insensitively), just before its `<EOR>`, or at the end if
+/// there is none.  Added fields are in name order.
+pub fn inject(text: &str, fields: &HashMap<String, String>) -> String {
+    let existing = field_names(text);
+    let mut missing: Vec<(&String, &String)> = fields
+        .iter()
+        .filter(|(name, _)| !existing.contains(&name.to_ascii_uppercase()))
+        .collect();
+    if missing.is_empty() {
+        return text.to_string();
+    }
+    missing.sort();
+
+    let added: String = missing
+        .into_iter()
+        .map(|(name, value)| format!("<{}:{}>{} ", name, value.chars().count(), value))
+        .collect();
+    let start = record_start(text);
+    let eor = tags(&text[start..])
+        .into_iter()
+        .find(|t| t.name == "EOR")
+        .map(|t| start + t.start);
+    match eor {
+        Some(at) => format!("{}{}{}", &text[..at], added, &text[at..]),
+        None => format!("{} {}", text.trim_end(), added.trim_end()),
+    }
+}
+
+/// Replace the value of each `names` field (case-insensitive) in the first
+/// record of `text` whose value is a key of `map`, also matched
+/// case-insensitively.  The field keeps its name and any type as written;
+/// only the value and its length change.
+pub fn remap(text: &str, names: &[&str], map: &HashMap<String, String>) -> String {
+    let start = record_start(text);
+    let mut out = text.to_string();
+    // Back to front, so earlier offsets still hold after each replacement.
+    let record_tags = tags(&text[start..]);
+    let fields = record_tags.iter().take_while(|t| t.name != "EOR");
+    for tag in fields.collect::<Vec<_>>().into_iter().rev() {
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(&tag.name)) {
+            continue;
+        }
+        let value = &text[start + tag.value.start..start + tag.value.end];
+        let Some(new) = map
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(value))
+            .map(|(_, to)| to)
+        else {
+            continue;
+        };
+        // The spec between `<` and `>`: NAME:LEN or NAME:LEN:TYPE.
+        let spec = &text[start + tag.start + 1..start + tag.value.start - 1];
+        let mut parts = spec.splitn(3, ':');
+        let name = parts.next().unwrap_or_default();
+        let kind = parts.nth(1).map(|t| format!(":{t}")).unwrap_or_default();
+        let field = format!("<{name}:{}{kind}>{new}", new.chars().count());
+        out.replace_range(start + tag.start..start + tag.value.end, &field);
+    }
+    out
+}
+
+// Header written at the top of a new local log file.
+const LOG_HEADER: &str = "wlrigctl local QSO log\n<ADIF_VER:5>3.1.4 <PROGRAMID:8>wlrigctl <EOH>\n";
+
+/// An ADIF file that QSO records are appended to, one per line.  Shared
+/// between tasks, so writes go through a mutex, on a blocking thread.
+pub struct AdifLog {
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl AdifLog {
+    /// Open `path` for appending, creating it with an ADIF header if it does
+    /// not exist or is empty.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<AdifLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut file = BufWriter::new(file);
+        if file.get_ref().metadata()?.len() == 0 {
+            file.write_all(LOG_HEADER.as_bytes())?;
+            file.flush()?;
+        }
+        Ok(AdifLog {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    /// Append the record in `text`, without any header it came with, adding
+    /// `<EOR>` if it has none.  Flushed straight away so a crash loses
+    /// nothing already logged.
+    pub async fn append(&self, text: &str) -> io::Result<()> {
+        let record = text[record_start(text)..].trim();
+        let line = if tags(record).iter().any(|t| t.name == "EOR") {
+            record.to_string()
+        } else {
+            format!("{record} <EOR>")
+        };
+        let file = self.file.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut file = file.lock().unwrap();
+            writeln!(file, "{line}")?;
+            file.flush()
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn field_names_skip_header_and_values() {
+        let text = "<adif_ver:5>3.1.0 <programid:6>WSJT-X <EOH>\n\
+                    <call:5>G4ABC <comment:7>a<b:1>c <mode:3>FT8 <eor>";
+        let names = field_names(text);
+        assert_eq!(
+            names,
+            ["CALL", "COMMENT", "MODE"].map(str::to_string).into()
+        );
+    }
+
+    #[test]
+    fn field_reads_value_by_length() {
+        let text = "<programid:6>WSJT-X <EOH> <call:5>G4ABC <freq:9>14.075500 <eor>";
+        assert_eq!(field(text, "FREQ").as_deref(), Some("14.075500"));
+        assert_eq!(field(text, "call").as_deref(), Some("G4ABC"));
+        assert_eq!(field(text, "PROGRAMID"), None);
+        assert_eq!(field(text, "MODE"), None);
+    }
+
+    #[test]
+    fn inject_adds_missing_fields_before_eor() {
+        let text = "<call:5>G4ABC <mode:3>FT8 <eor>";
+        let out = inject(
+            text,
+            &fields(&[("MY_GRIDSQUARE", "IO91wm"), ("OPERATOR", "M0XYZ")]),
+        );
+        assert_eq!(
+            out,
+            "<call:5>G4ABC <mode:3>FT8 <MY_GRIDSQUARE:6>IO91wm <OPERATOR:5>M0XYZ <eor>"
+        );
+    }
+
+    #[test]
+    fn inject_keeps_existing_fields() {
+        let text = "<call:5>G4ABC <my_gridsquare:4>JO01 <eor>";
+        let out = inject(text, &fields(&[("MY_GRIDSQUARE", "IO91wm")]));
+        assert_eq!(out, text);
+    }
+
+    #[test]
+    fn inject_without_eor_appends() {
+        let out = inject("<call:5>G4ABC ", &fields(&[("OPERATOR", "M0XYZ")]));
+        assert_eq!(out, "<call:5>G4ABC <OPERATOR:5>M0XYZ");
+    }
+
+    #[test]
+    fn inject_counts_characters_not_bytes() {
+        let out = inject("<call:5>G4ABC <eor>", &fields(&[("COMMENT", "73 de Zoë")]));
+        assert!(out.contains("<COMMENT:9>73 de Zoë "), "{out}");
+    }
+
+    #[test]
+    fn remap_rewrites_listed_fields_only() {
+        let map = fields(&[("JS8", "MFSK"), ("ft4", "MFSK")]);
+        let text = "<programid:3>JS8 <EOH> <call:5>G4ABC <mode:3>JS8 \
+                    <submode:3:S>FT4 <comment:3>JS8 <eor>";
+        assert_eq!(
+            remap(text, &["MODE", "SUBMODE"], &map),
+            "<programid:3>JS8 <EOH> <call:5>G4ABC <mode:4>MFSK \
+             <submode:4:S>MFSK <comment:3>JS8 <eor>"
+        );
+        // Nothing to remap: unchanged.
+        let text = "<call:5>G4ABC <mode:3>FT8 <eor>";
+        assert_eq!(remap(text, &["MODE"], &map), text);
+    }
+
+    #[tokio::test]
+    async fn log_appends_records_after_one_header() {
+        let path = std::env::temp_dir().join("wlrigctl-adif-log.adi");
+        let _ = std::fs::remove_file(&path);
+
+        let log = AdifLog::open(&path).unwrap();
+        log.append("<adif_ver:5>3.1.0 <EOH>\n<call:5>G4ABC <mode:3>FT8 <eor>\n")
+            .await
+            .unwrap();
+        drop(log);
+        AdifLog::open(&path)
+            .unwrap()
+            .append("<call:5>M0XYZ <mode:3>FT4")
+            .await
+            .unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "{LOG_HEADER}<call:5>G4ABC <mode:3>FT8 <eor>\n<call:5>M0XYZ <mode:3>FT4 <EOR>\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// One `[CAT] allow` entry: a single address ("192.168.1.20") or a network in
+// CIDR notation ("192.168.1.0/24", "fd00::/8").
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct IpNet {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IpNet, String> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("'{s}' is not an IP address or CIDR network"))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            None => max,
+            Some(p) => match p.parse::<u32>() {
+                Ok(p) if p <= max => p,
+                _ => return Err(format!("'{s}' has an invalid prefix length")),
+            },
+        };
+        Ok(IpNet { addr, prefix })
+    }
+}
+
+impl IpNet {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                same_prefix(u32::from(net).into(), u32::from(ip).into(), self.prefix, 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                same_prefix(u128::from(net), u128::from(ip), self.prefix, 128)
+            }
+            _ => false,
+        }
+    }
+}
+
+// Whether the top `prefix` bits of two `width`-bit addresses agree.
+fn same_prefix(a: u128, b: u128, prefix: u32, width: u32) -> bool {
+    prefix == 0 || (a ^ b) >> (width - prefix) == 0
+}
+
+/// The clients the CAT server answers, from `[CAT] allow`.  An empty list
+/// lets everyone in.
+#[derive(Debug, Default)]
+pub struct AllowList(Vec<IpNet>);
+
+/// A `[CAT] allow` entry that is neither an address nor a CIDR network.
+#[derive(Debug)]
+pub struct AllowListError(String);
+
+impl fmt::Display for AllowListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[CAT] allow: {}", self.0)
+    }
+}
+
+impl std::error::Error for AllowListError {}
+
+impl AllowList {
+    /// Parse the configured entries.  A bad entry is an error rather than
+    /// skipped, since dropping the only entry would let everyone in.
+    pub fn parse(entries: Option<&[String]>) -> Result<AllowList, AllowListError> {
+        entries
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| entry.parse().map_err(AllowListError))
+            .collect::<Result<_, _>>()
+            .map(AllowList)
+    }
+
+    /// Whether a client at `ip` may use the CAT server.
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        self.0.is_empty() || self.0.iter().any(|net| net.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allow(entries: &[&str]) -> AllowList {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        AllowList::parse(Some(&entries)).unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn listed_address_and_network_are_allowed() {
+        let list = allow(&["127.0.0.1", "192.168.1.0/24", "fd00::/8"]);
+        assert!(list.permits(ip("127.0.0.1")));
+        assert!(list.permits(ip("192.168.1.77")));
+        assert!(list.permits(ip("fd12::1")));
+        // IPv4 clients seen through an IPv6 socket.
+        assert!(list.permits(ip("::ffff:192.168.1.77")));
+    }
+
+    #[test]
+    fn other_addresses_are_denied() {
+        let list = allow(&["127.0.0.1", "192.168.1.0/24"]);
+        assert!(!list.permits(ip("127.0.0.2")));
+        assert!(!list.permits(ip("192.168.2.1")));
+        assert!(!list.permits(ip("::1")));
+    }
+
+    #[test]
+    fn empty_or_absent_list_allows_everyone() {
+        assert!(AllowList::parse(None).unwrap().permits(ip("203.0.113.9")));
+        assert!(allow(&[]).permits(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn zero_prefix_matches_the_whole_family() {
+        let list = allow(&["0.0.0.0/0"]);
+        assert!(list.permits(ip("203.0.113.9")));
+        assert!(!list.permits(ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn bad_entries_are_errors() {
+        for entry in ["192.168.1.0/33", "wavelog.lan", "10.0.0.0/", "::1/129"] {
+            let entries = vec![entry.to_string()];
+            assert!(AllowList::parse(Some(&entries)).is_err(), "{entry}");
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use log::{debug, info, warn};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::cat::band_name;
+use crate::flrig::Mode;
+use crate::statefile::StateFileWriter;
+
+// Remembers the last voice mode used on each band, persisted to a small JSON
+// file ({"40m": "LSB", "20m": "USB"}) so it survives restarts.
+//
+// Only voice modes are remembered: the memory replaces the LSB/USB guess for
+// a "phone" bandmap click, so a CW or data session on a band must not cause
+// the next phone click there to land in CW.
+pub struct BandModeMemory {
+    writer: StateFileWriter<BTreeMap<String, String>>,
+    modes: Mutex<BTreeMap<String, String>>,
+}
+
+fn is_voice_mode(mode: Mode) -> bool {
+    matches!(
+        mode,
+        Mode::LSB | Mode::USB | Mode::AM | Mode::AM_N | Mode::FM | Mode::FM_N
+    )
+}
+
+impl BandModeMemory {
+    /// Load the state file at `path`.  A missing file starts an empty memory;
+    /// an unreadable or corrupt one is logged and also starts empty.  Must be
+    /// called inside the Tokio runtime, which runs the writes.
+    pub fn load(path: impl AsRef<Path>) -> BandModeMemory {
+        let path = path.as_ref().to_path_buf();
+        let modes = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                warn!("Ignoring corrupt band mode file {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!("Could not read band mode file {}: {e}", path.display());
+                BTreeMap::new()
+            }
+        };
+        info!("Band mode memory: {} ({modes:?})", path.display());
+        BandModeMemory {
+            writer: StateFileWriter::spawn(path),
+            modes: Mutex::new(modes),
+        }
+    }
+
+    /// The remembered mode for the band containing `freq_hz`, if any.
+    pub fn remembered(&self, freq_hz: u32) -> Option<Mode> {
+        let band = band_name(freq_hz)?;
+        let modes = self.modes.lock().unwrap();
+        modes.get(band).and_then(|m| m.parse::<Mode>().ok())
+    }
+
+    /// Record that the rig is in `mode` at `freq_hz`.  The state file is
+    /// rewritten in the background, and only when the remembered mode for
+    /// that band actually changes.
+    pub fn observe(&self, freq_hz: u32, mode: Mode) {
+        let Some(band) = band_name(freq_hz) else {
+            return;
+        };
+        if !is_voice_mode(mode) {
+            return;
+        }
+
+        let mode = mode.to_string();
+        let mut modes = self.modes.lock().unwrap();
+        if modes.get(band) == Some(&mode) {
+            return;
+        }
+        debug!("Remembering {mode} on {band}");
+        modes.insert(band.to_string(), mode);
+        self.writer.set(modes.clone());
+    }
+
+    /// Every remembered band and its mode, e.g. {"40m": "LSB"}.
+    pub fn snapshot(&self) -> BTreeMap<String, String> {
+        self.modes.lock().unwrap().clone()
+    }
+
+    /// Write a change still waiting to be saved, e.g. on shutdown.
+    pub async fn flush(&self) {
+        self.writer.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("band-modes.json")
+    }
+
+    #[tokio::test]
+    async fn missing_file_starts_empty() {
+        let mem = BandModeMemory::load(temp_path("wlrigctl-bandmem-missing"));
+        assert_eq!(mem.remembered(7_100_000), None);
+    }
+
+    #[tokio::test]
+    async fn save_load_round_trip() {
+        let path = temp_path("wlrigctl-bandmem-roundtrip");
+        let mem = BandModeMemory::load(&path);
+        mem.observe(7_150_000, Mode::USB);
+        mem.observe(14_200_000, Mode::AM);
+        mem.flush().await;
+        drop(mem);
+
+        let mem = BandModeMemory::load(&path);
+        assert_eq!(mem.remembered(7_050_000), Some(Mode::USB));
+        assert_eq!(mem.remembered(14_300_000), Some(Mode::AM));
+        assert_eq!(mem.remembered(21_200_000), None);
+    }
+
+    #[tokio::test]
+    async fn non_voice_modes_and_out_of_band_are_ignored() {
+        let path = temp_path("wlrigctl-bandmem-ignored");
+        let mem = BandModeMemory::load(&path);
+        mem.observe(7_150_000, Mode::LSB);
+        mem.observe(7_030_000, Mode::CW);
+        mem.observe(11_000_000, Mode::USB);
+        assert_eq!(mem.remembered(7_150_000), Some(Mode::LSB));
+        assert_eq!(mem.remembered(11_000_000), None);
+    }
+
+    #[tokio::test]
+    async fn corrupt_file_starts_empty() {
+        let path = temp_path("wlrigctl-bandmem-corrupt");
+        std::fs::write(&path, "not json").unwrap();
+        let mem = BandModeMemory::load(&path);
+        assert_eq!(mem.remembered(7_150_000), None);
+    }
+}
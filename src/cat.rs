@@ -1,29 +1,72 @@
-use log::{debug, info};
+use log::{debug, error, info, warn};
 use serde_json::json;
+use tokio_rustls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::Role;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
 use tokio_util::sync::CancellationToken;
 
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
-use hyper_util::rt::TokioIo;
-use serde::Deserialize;
-use std::net::Ipv4Addr;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use hyper_util::rt::{TokioIo, TokioTimer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
+use tokio::sync::{broadcast, oneshot, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Duration, Instant};
 
-use hyper::body::Bytes;
-use hyper::header::CONTENT_TYPE;
-use hyper::{Request, Response, StatusCode};
+use futures_util::{SinkExt, StreamExt};
+use hyper::body::{Body, Bytes};
+use hyper::header::{
+    HeaderValue, ALLOW, CONNECTION, CONTENT_TYPE, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE,
+};
+use hyper::{Method, Request, Response, StatusCode};
 use std::convert::Infallible;
 use std::str::FromStr;
 
 pub type HttpResponse = Response<Full<Bytes>>;
 
-use http_body_util::Full;
+use dxr_client::ClientError;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
 
+use crate::allow::{AllowList, AllowListError};
+use crate::latency::SharedLatency;
+use crate::settings::SharedLiveSettings;
+use crate::wavelog::{
+    upload_wsjtx_qso_data, PollRequest, RadioCache, RadioData, RefreshSender, RigUpdates,
+    WavelogError, WavelogSettings,
+};
+use crate::ws::{load_tls_acceptor, radio_status_fields, ws_message};
+use crate::wsjtx::{WsjtxError, WsjtxLink, WsjtxStatusUpdate};
 use crate::{flrig, flrig::Mode, flrig::ModeMap};
 
-const CAT_BIND_HOST: Ipv4Addr = Ipv4Addr::LOCALHOST;
+// Interface the CAT server binds when [CAT] host is absent or unparseable.
+const DEFAULT_CAT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+// Default for [CAT] cache_ms: short enough that a dashboard polling once a
+// second still sees every change, long enough to absorb bursts.
+const DEFAULT_CACHE_MS: u64 = 500;
+
+// Default for [CAT] health_failures.
+const DEFAULT_HEALTH_FAILURES: u32 = 3;
+
+// Default for [CAT] wsjtx_health_secs: four WSJT-X heartbeat intervals.
+const DEFAULT_WSJTX_HEALTH_SECS: u64 = 60;
+
+// Defaults for [CAT] connection_timeout_secs and max_connections.  A bandmap
+// click is answered in well under a second, and a browser keeps only a few
+// connections open.
+const DEFAULT_CONNECTION_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_CONNECTIONS: usize = 32;
+
+// Defaults for [CAT] max_body_bytes and body_timeout_ms.  An ADIF record is
+// a few hundred bytes.
+const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+const DEFAULT_BODY_TIMEOUT_MS: u64 = 10_000;
 
 // UK amateur frequency allocations permitted across all licence classes
 // (Foundation as the common baseline), in Hz.
@@ -32,54 +75,245 @@ const CAT_BIND_HOST: Ipv4Addr = Ipv4Addr::LOCALHOST;
 // (Full licence only, non-contiguous, specialist conditions).
 // Microwave bands above 70cm omitted; add entries here if a supported
 // rig needs them.
-const AMATEUR_BANDS_HZ: &[(u32, u32)] = &[
-    (135_700, 137_800),         // 136 kHz
-    (1_810_000, 2_000_000),     // 160m
-    (3_500_000, 3_800_000),     // 80m
-    (7_000_000, 7_200_000),     // 40m
-    (10_100_000, 10_150_000),   // 30m
-    (14_000_000, 14_350_000),   // 20m
-    (18_068_000, 18_168_000),   // 17m
-    (21_000_000, 21_450_000),   // 15m
-    (24_890_000, 24_990_000),   // 12m
-    (28_000_000, 29_700_000),   // 10m
-    (50_000_000, 52_000_000),   // 6m
-    (70_000_000, 70_500_000),   // 4m
-    (144_000_000, 146_000_000), // 2m
-    (430_000_000, 440_000_000), // 70cm
+//
+// The third column is the ADIF band name, reported by /status.
+const AMATEUR_BANDS_HZ: &[(u32, u32, &str)] = &[
+    (135_700, 137_800, "2190m"),
+    (1_810_000, 2_000_000, "160m"),
+    (3_500_000, 3_800_000, "80m"),
+    (7_000_000, 7_200_000, "40m"),
+    (10_100_000, 10_150_000, "30m"),
+    (14_000_000, 14_350_000, "20m"),
+    (18_068_000, 18_168_000, "17m"),
+    (21_000_000, 21_450_000, "15m"),
+    (24_890_000, 24_990_000, "12m"),
+    (28_000_000, 29_700_000, "10m"),
+    (50_000_000, 52_000_000, "6m"),
+    (70_000_000, 70_500_000, "4m"),
+    (144_000_000, 146_000_000, "2m"),
+    (430_000_000, 440_000_000, "70cm"),
 ];
 
-fn is_amateur_frequency(freq_hz: u32) -> bool {
+// The configured band_homes as (band lower edge, home frequency), in band
+// order.  Unknown band names and homes outside their band are reported and
+// skipped.
+fn parse_band_homes(homes: Option<&HashMap<String, u32>>) -> Vec<(u32, u32)> {
+    let Some(homes) = homes else {
+        return Vec::new();
+    };
+    for name in homes.keys() {
+        if !AMATEUR_BANDS_HZ.iter().any(|&(_, _, band)| band == name) {
+            warn!("Ignoring band_homes entry for unknown band '{name}'");
+        }
+    }
+    AMATEUR_BANDS_HZ
+        .iter()
+        .filter_map(|&(lo, hi, band)| {
+            let &home = homes.get(band)?;
+            if (lo..=hi).contains(&home) {
+                Some((lo, home))
+            } else {
+                warn!("Ignoring band_homes entry {band} = {home}: not within {band}");
+                None
+            }
+        })
+        .collect()
+}
+
+// The home frequency to step to from `freq_hz`: the one on the next listed
+// band up (or down), going round past the ends if `wrap`.  A frequency
+// between bands steps to the nearest listed band in that direction.
+fn step_band(homes: &[(u32, u32)], freq_hz: u32, up: bool, wrap: bool) -> Option<u32> {
+    let here = AMATEUR_BANDS_HZ
+        .iter()
+        .find(|&&(lo, hi, _)| (lo..=hi).contains(&freq_hz))
+        .map_or(freq_hz, |&(lo, _, _)| lo);
+    let next = if up {
+        homes.iter().find(|&&(lo, _)| lo > here)
+    } else {
+        homes.iter().rev().find(|&&(lo, _)| lo < here)
+    };
+    let wrapped = || if up { homes.first() } else { homes.last() };
+    next.or_else(|| wrap.then(wrapped).flatten())
+        .map(|&(_, home)| home)
+}
+
+// Default for [CAT] power_step.
+const DEFAULT_POWER_STEP: PowerStep = PowerStep::Percent(10);
+
+// How far /power/up and /power/down move the power, as [CAT] power_step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PowerStep {
+    Watts(u32),
+    Percent(u32),
+}
+
+impl PowerStep {
+    // "5", "5W" or "10%"; None for anything else, including a zero step.
+    fn parse(s: &str) -> Option<PowerStep> {
+        let s = s.trim();
+        let step = match s.strip_suffix('%') {
+            Some(pct) => PowerStep::Percent(pct.trim().parse().ok()?),
+            None => {
+                let watts = s.strip_suffix(['W', 'w']).unwrap_or(s);
+                PowerStep::Watts(watts.trim().parse().ok()?)
+            }
+        };
+        match step {
+            PowerStep::Watts(0) | PowerStep::Percent(0) => None,
+            step => Some(step),
+        }
+    }
+
+    // The step in watts for a rig of `maxpower` watts; a percentage step
+    // is never less than 1W, so the power always moves.
+    fn watts(self, maxpower: u32) -> u32 {
+        match self {
+            PowerStep::Watts(w) => w,
+            PowerStep::Percent(pct) => {
+                ((maxpower as u64 * pct as u64 + 50) / 100).clamp(1, u32::MAX as u64) as u32
+            }
+        }
+    }
+}
+
+// The configured power_step, or the default if it is absent or does not
+// parse.
+fn parse_power_step(step: Option<&str>) -> PowerStep {
+    let Some(step) = step else {
+        return DEFAULT_POWER_STEP;
+    };
+    PowerStep::parse(step).unwrap_or_else(|| {
+        warn!("Ignoring power_step '{step}': expected watts (\"5\") or a percentage (\"10%\")");
+        DEFAULT_POWER_STEP
+    })
+}
+
+// ADIF band name for a frequency, or None outside AMATEUR_BANDS_HZ.
+pub(crate) fn band_name(freq_hz: u32) -> Option<&'static str> {
     AMATEUR_BANDS_HZ
         .iter()
-        .any(|&(lo, hi)| freq_hz >= lo && freq_hz <= hi)
+        .find(|&&(lo, hi, _)| freq_hz >= lo && freq_hz <= hi)
+        .map(|&(_, _, name)| name)
+}
+
+fn is_amateur_frequency(freq_hz: u32) -> bool {
+    band_name(freq_hz).is_some()
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CatSettings {
+    /// Interface to bind on.  Defaults to `127.0.0.1`, so only this machine
+    /// can reach the CAT server; set "0.0.0.0" (or one address) to serve
+    /// Wavelog on another host, ideally with `tls_cert`/`tls_key` and
+    /// `allow`.
+    pub host: Option<String>,
     pub port: u16,
     /// FLRig mode string to use for CW.  Defaults to "CW" (ICOM/Kenwood/Elecraft).
-    /// Set to "CW-U" for Yaesu rigs that require an explicit sideband suffix.
+    /// Set to "CW-U" for Yaesu rigs that require an explicit sideband suffix,
+    /// or "CW-L" to listen on the lower sideband.
     pub cw_mode: Option<String>,
     /// FLRig mode string to use for RTTY.  Defaults to "RTTY".
-    /// Use "RTTY-U" for Yaesu, "FSK" for Kenwood rigs that name it differently.
+    /// Use "RTTY-U" (or "RTTY-L") for Yaesu, "FSK" for Kenwood rigs that
+    /// name it differently.
     pub rtty_mode: Option<String>,
     /// FLRig mode string to use for digital modes (FT8, PSK31, etc.).
     /// Defaults to "D-USB" (IC-703).  Use "DATA-U" for Yaesu, "USB-D" for
     /// newer ICOM rigs (IC-7300 etc.), "DATA" for Elecraft.
     pub digital_mode: Option<String>,
+    /// FLRig mode for a generic "digi" bandmap click outside every FT8
+    /// window, e.g. "D-USB" for someone who mostly runs PSK.  Defaults to
+    /// the RTTY mode.  An explicit "rtty" click always uses the RTTY mode.
+    pub default_digi_mode: Option<String>,
     /// FT8 dial frequencies in Hz. Overrides the built-in list when present.
     /// Example: ft8_frequencies = [1840000, 3575000, 7074000]
     pub ft8_frequencies: Option<Vec<u64>>,
+    /// Set false to stop the FT8 frequencies influencing the mode: a
+    /// "digi" or "rtty" click on an FT8 frequency then maps as it would
+    /// anywhere else.  Defaults to true.
+    pub ft8_automode: Option<bool>,
     /// Expected value of the HTTP `Origin` header on incoming QSY requests.
     /// When set, requests whose `Origin` does not match are rejected with 403.
     /// Protects against browser-based CSRF from pages not served by Wavelog.
     /// Example: wavelog_origin = "https://wavelog.example.org"
     pub wavelog_origin: Option<String>,
+    /// Frequency in Hz that `/band/next` and `/band/prev` tune to on each
+    /// band, keyed by ADIF band name.  Bands not listed are skipped.
+    /// Example: band_homes = { "40m" = 7074000, "20m" = 14074000 }
+    pub band_homes: Option<HashMap<String, u32>>,
+    /// Whether `/band/next` on the highest listed band goes round to the
+    /// lowest, and `/band/prev` the other way.  Defaults to true; when
+    /// false the request is refused at the ends.
+    pub band_wrap: Option<bool>,
+    /// Answer a QSY as soon as it has been validated, then tune the rig in
+    /// the background, for clients (such as Wavelog) that hang up before a
+    /// slow rig has finished.  The outcome can be read back from
+    /// `/qsy/last`.  Defaults to false.
+    pub respond_early: Option<bool>,
+    /// Extra bandmap mode names, mapped to one of the built-in ones
+    /// ("cw", "phone", "digi", "ft8", ...), for loggers that spell a mode
+    /// differently.  Matched case-insensitively, and checked before the
+    /// built-in names.  Example: mode_aliases = { "SSTV" = "usb" }
+    pub mode_aliases: Option<HashMap<String, String>>,
+    /// How long, in milliseconds, `/status` and `/modes` answers are reused
+    /// before FLRig is asked again.  `/status` also reuses the Wavelog poll
+    /// loop's latest read.  0 disables the cache.  Defaults to 500.
+    pub cache_ms: Option<u64>,
+    /// Consecutive failed FLRig calls before `/health` reports "down", so a
+    /// single hiccup does not trip external monitoring.  Calls made by the
+    /// Wavelog poll loop count too.  Defaults to 3.
+    pub health_failures: Option<u32>,
+    /// Seconds without a WSJT-X datagram before `/health/wsjtx` reports
+    /// "stale".  WSJT-X sends a heartbeat every 15 seconds, so the default
+    /// of 60 allows a few to go missing.
+    pub wsjtx_health_secs: Option<u64>,
+    /// Path to a PEM-encoded TLS certificate.  When set together with
+    /// `tls_key`, the CAT server speaks HTTPS instead of plain HTTP.
+    pub tls_cert: Option<String>,
+    /// Path to the PEM-encoded private key for `tls_cert`.
+    pub tls_key: Option<String>,
+    /// Seconds a client may take over a TLS handshake or a request's
+    /// headers, or leave a keep-alive connection idle, before it is
+    /// dropped.  Stops a client that connects and never sends a request
+    /// from holding resources.  Defaults to 30.
+    pub connection_timeout_secs: Option<u64>,
+    /// Most connections served at once; further ones are closed straight
+    /// away until one finishes.  Defaults to 32.
+    pub max_connections: Option<usize>,
+    /// Largest request body accepted, in bytes; larger ones are refused
+    /// with 413.  Defaults to 65536.
+    pub max_body_bytes: Option<usize>,
+    /// How long, in milliseconds, a client may take to send a request body
+    /// before it is refused with 408.  Defaults to 10000.
+    pub body_timeout_ms: Option<u64>,
+    /// Round every QSY's dial frequency to the nearest multiple of this many
+    /// Hz, e.g. 10 to tune to the nearest 10 Hz or a channel spacing for
+    /// channelised work.  0 (the default) tunes to the exact frequency.
+    pub freq_step_hz: Option<u32>,
+    /// Client addresses or CIDR networks allowed to use the CAT server,
+    /// e.g. `["127.0.0.1", "192.168.1.0/24"]`.  Anyone else gets 403.
+    /// Absent or empty allows every client.  Only remote clients can be
+    /// kept out once `host` binds a network interface.
+    pub allow: Option<Vec<String>>,
+    /// How far `/power/up` and `/power/down` move the output power: a
+    /// number of watts ("5" or "5W") or a percentage of `[flrig] maxpower`
+    /// ("10%").  Defaults to "10%".
+    pub power_step: Option<String>,
+}
+
+impl CatSettings {
+    pub fn bind_addr(&self) -> SocketAddr {
+        let host = self
+            .host
+            .as_deref()
+            .and_then(|host| host.parse().ok())
+            .unwrap_or(DEFAULT_CAT_HOST);
+        SocketAddr::from((host, self.port))
+    }
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 enum WavelogMode {
     Cw,
     Phone,
@@ -89,13 +323,41 @@ enum WavelogMode {
     Rtty,
     Am,
     Fm,
+    // Specific digital modes.  Unlike Digi these name the mode outright, so
+    // they go straight to the rig's data mode without the FT8 heuristic.
+    Ft8,
+    Ft4,
+    Psk,
+    Js8,
+    Olivia,
+}
+
+impl WavelogMode {
+    // The bandmap's own spelling of this mode, as accepted by FromStr.
+    fn as_str(self) -> &'static str {
+        match self {
+            WavelogMode::Cw => "cw",
+            WavelogMode::Phone => "phone",
+            WavelogMode::LSB => "lsb",
+            WavelogMode::USB => "usb",
+            WavelogMode::Digi => "digi",
+            WavelogMode::Rtty => "rtty",
+            WavelogMode::Am => "am",
+            WavelogMode::Fm => "fm",
+            WavelogMode::Ft8 => "ft8",
+            WavelogMode::Ft4 => "ft4",
+            WavelogMode::Psk => "psk",
+            WavelogMode::Js8 => "js8",
+            WavelogMode::Olivia => "olivia",
+        }
+    }
 }
 
 impl FromStr for WavelogMode {
     type Err = ();
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
+        match s.to_ascii_lowercase().as_str() {
             "cw" => Ok(WavelogMode::Cw),
             "phone" => Ok(WavelogMode::Phone),
             "lsb" => Ok(WavelogMode::LSB),
@@ -104,11 +366,73 @@ impl FromStr for WavelogMode {
             "rtty" => Ok(WavelogMode::Rtty),
             "am" => Ok(WavelogMode::Am),
             "fm" => Ok(WavelogMode::Fm),
+            "ft8" => Ok(WavelogMode::Ft8),
+            "ft4" => Ok(WavelogMode::Ft4),
+            "psk" => Ok(WavelogMode::Psk),
+            "js8" => Ok(WavelogMode::Js8),
+            "olivia" => Ok(WavelogMode::Olivia),
             _ => Err(()),
         }
     }
 }
 
+// Other spellings of the bandmap modes seen from Wavelog and Cloudlog
+// versions, on top of WavelogMode's own names.
+const DEFAULT_MODE_ALIASES: &[(&str, WavelogMode)] = &[
+    ("ssb", WavelogMode::Phone),
+    ("voice", WavelogMode::Phone),
+    ("data", WavelogMode::Digi),
+    ("digital", WavelogMode::Digi),
+    ("psk31", WavelogMode::Psk),
+    ("psk63", WavelogMode::Psk),
+    ("js8call", WavelogMode::Js8),
+];
+
+// Mode names accepted in a QSY path beyond WavelogMode's own: the defaults
+// plus [CAT] mode_aliases, keyed in lower case.
+#[derive(Debug)]
+struct ModeAliases(HashMap<String, WavelogMode>);
+
+impl Default for ModeAliases {
+    fn default() -> Self {
+        ModeAliases::new(None)
+    }
+}
+
+impl ModeAliases {
+    // Aliases whose target is not a bandmap mode are reported and skipped.
+    fn new(configured: Option<&HashMap<String, String>>) -> ModeAliases {
+        let mut aliases: HashMap<String, WavelogMode> = DEFAULT_MODE_ALIASES
+            .iter()
+            .map(|&(alias, mode)| (alias.to_string(), mode))
+            .collect();
+        for (alias, target) in configured.into_iter().flatten() {
+            match target.parse::<WavelogMode>() {
+                Ok(mode) => {
+                    aliases.insert(alias.to_ascii_lowercase(), mode);
+                }
+                Err(()) => warn!("Ignoring mode_aliases entry {alias}: unknown mode '{target}'"),
+            }
+        }
+        ModeAliases(aliases)
+    }
+
+    fn resolve(&self, name: &str) -> Option<WavelogMode> {
+        match self.0.get(&name.to_ascii_lowercase()) {
+            Some(&mode) => Some(mode),
+            None => name.parse().ok(),
+        }
+    }
+}
+
+// The FT8 frequency list: the config override if provided, otherwise defaults.
+pub(crate) fn ft8_freqs(configured: Option<&[u64]>) -> Arc<[f64]> {
+    match configured {
+        Some(freqs) => freqs.iter().map(|&f| f as f64).collect::<Vec<f64>>().into(),
+        None => Arc::from(DEFAULT_FT8_FREQS.as_slice()),
+    }
+}
+
 // Default FT8 dial frequencies (Hz).
 // Overridable via ft8_frequencies in the [CAT] config section.
 //
@@ -135,15 +459,110 @@ const DEFAULT_FT8_FREQS: [f64; 10] = [
     50_313_000.0,
 ];
 
-//
-// If dial frequency is within ±2–3 kHz of any entry in `freqs`, the mode should be FT8.
+const FT8_LO_ALLOWANCE: f64 = 2_000.0; // inclusive, below the dial
+const FT8_HI_ALLOWANCE: f64 = 3_000.0; // exclusive, above the dial
+
+// If the dial frequency lies in [centre - 2 kHz, centre + 3 kHz) for any entry
+// in `freqs`, the mode should be FT8.  The window is deliberately lopsided:
+// FT8 signals occupy roughly 0-3 kHz of audio *above* the dial, so a spot up
+// to 3 kHz higher is still FT8 traffic, while below the dial there are no FT8
+// signals and only 2 kHz is allowed for a dial set slightly low.  CW spotted
+// more than 2 kHz below an FT8 dial frequency is therefore left as CW.
 // See unit tests at end of file.
 fn is_ft8(freq_hz: f64, freqs: &[f64]) -> bool {
-    const LO_ALLOWANCE: f64 = 2_000.0;
-    const HI_ALLOWANCE: f64 = 3_000.0;
     freqs
         .iter()
-        .any(|&f| freq_hz >= f - LO_ALLOWANCE && freq_hz < f + HI_ALLOWANCE)
+        .any(|&f| freq_hz >= f - FT8_LO_ALLOWANCE && freq_hz < f + FT8_HI_ALLOWANCE)
+}
+
+// Everything a CAT request handler needs, built once at CAT_thread startup
+// and shared between connections.
+struct CatState {
+    rig: Arc<flrig::FLRig>,
+    allow: AllowList,
+    // Mode names and FT8 frequencies, replaced on a SIGHUP config reload.
+    live: SharedLiveSettings,
+    wavelog_origin: Option<String>,
+    wavelog: WavelogSettings,
+    client: reqwest::Client,
+    // Redacted effective configuration, served by /config.
+    config: serde_json::Value,
+    wsjtx: WsjtxLink,
+    refresh: RefreshSender,
+    // Latest rig state for /status, shared with the poll loop, and the
+    // rig's mode list for /modes; both reused for cache_ttl.
+    radio_cache: RadioCache,
+    // Rig state changes from the poll loop, for /ws subscribers.
+    updates: RigUpdates,
+    latency: SharedLatency,
+    modes_cache: Mutex<Option<(Instant, Vec<String>)>>,
+    cache_ttl: Duration,
+    health_failures: u32,
+    wsjtx_health: Duration,
+    // How long a connection may wait for request headers or sit idle, and
+    // one permit per connection being served.
+    connection_timeout: Duration,
+    connections: Arc<Semaphore>,
+    max_body_bytes: usize,
+    body_timeout: Duration,
+    // band_homes as (band lower edge, home), in band order, and band_wrap.
+    band_homes: Vec<(u32, u32)>,
+    band_wrap: bool,
+    respond_early: bool,
+    mode_aliases: ModeAliases,
+    freq_step_hz: u32,
+    power_step: PowerStep,
+    // Outcome of the most recent respond_early QSY, as served by /qsy/last,
+    // tagged with its number so a slower earlier QSY cannot overwrite it.
+    last_qsy: Mutex<Option<(u64, StatusCode, serde_json::Value)>>,
+    qsy_count: AtomicU64,
+}
+
+/// The parts of the running program the CAT server reaches into, shared
+/// with the tasks that own them.
+pub struct CatHandles {
+    pub rig: Arc<flrig::FLRig>,
+    /// Settings replaced on a SIGHUP config reload.
+    pub live: SharedLiveSettings,
+    /// Route for commands to WSJT-X.
+    pub wsjtx: WsjtxLink,
+    /// Asks the Wavelog poll loop for an immediate read and upload.
+    pub refresh: RefreshSender,
+    /// Latest rig state, kept fresh by the Wavelog poll loop.
+    pub radio_cache: RadioCache,
+    /// Rig state changes announced by the Wavelog poll loop.
+    pub updates: RigUpdates,
+    /// Round-trip stats of FLRig calls and Wavelog uploads, for /stats.
+    pub latency: SharedLatency,
+}
+
+impl CatState {
+    // Map a bandmap mode to an FLRig mode, consulting the rig's per-band
+    // mode memory when one is configured.
+    fn flrig_mode(&self, freq: f64, mode: WavelogMode) -> (Mode, ModeReason) {
+        let remembered = self.rig.remembered_mode(freq as u32);
+        let live = self.live.read().unwrap();
+        // With ft8_automode off, no frequency is an FT8 frequency.
+        let ft8_freqs: &[f64] = if live.ft8_automode {
+            &live.ft8_freqs
+        } else {
+            &[]
+        };
+        explain_flrig_mode(freq, mode, ft8_freqs, &live.mode_map, remembered)
+    }
+
+    // Forget the cached rig state after the rig has been told to change.
+    fn invalidate_radio_cache(&self) {
+        self.radio_cache.invalidate();
+    }
+}
+
+// The value in `slot` if it was stored less than `ttl` ago.
+fn cached<T: Clone>(slot: &Mutex<Option<(Instant, T)>>, ttl: Duration) -> Option<T> {
+    match &*slot.lock().unwrap() {
+        Some((read_at, value)) if read_at.elapsed() < ttl => Some(value.clone()),
+        _ => None,
+    }
 }
 
 #[derive(Debug)]
@@ -152,23 +571,33 @@ struct Qsy {
     mode: WavelogMode,
 }
 
-fn http_err_str(status: StatusCode, msg: impl Into<String>) -> HttpResponse {
-    match Response::builder()
+// JSON response with the CORS headers Wavelog's bandmap (browser JavaScript)
+// needs in order to read it.  Errors carry the same headers as successes, so
+// the browser sees the real 400/500 rather than an opaque CORS failure.
+fn json_response(status: StatusCode, body: serde_json::Value) -> HttpResponse {
+    Response::builder()
         .status(status)
-        .body(Full::new(Bytes::from(msg.into())))
-    {
-        Ok(resp) => resp,
-        Err(_) => {
-            // Satisfy Infallible for caller
-            Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Full::new(Bytes::from_static(b"internal server error")))
-                .unwrap_or_else(|_| {
-                    // minimal response
-                    Response::new(Full::new(Bytes::from_static(b"internal server error")))
-                })
-        }
-    }
+        .header(CONTENT_TYPE, "application/json")
+        .header("Access-Control-Allow-Origin", "*")
+        .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
+        .header("Access-Control-Allow-Headers", "Content-Type")
+        .body(Full::new(Bytes::from(body.to_string())))
+        .unwrap()
+}
+
+fn json_ok(body: serde_json::Value) -> HttpResponse {
+    json_response(StatusCode::OK, body)
+}
+
+// Error response: {"status": "error", "message": msg} with the given status.
+fn http_err_str(status: StatusCode, msg: impl Into<String>) -> HttpResponse {
+    json_response(
+        status,
+        json!({
+            "status": "error",
+            "message": msg.into(),
+        }),
+    )
 }
 
 // Returns true if the request's Origin header matches `expected` exactly.
@@ -180,13 +609,29 @@ fn check_origin<B>(req: &Request<B>, expected: &str) -> bool {
 }
 
 // Parse '/<freq>/<mode>' into a typed struct: Qsy
-fn parse_qsy_path<B>(req: &Request<B>) -> Result<Qsy, Box<HttpResponse>> {
-    let parts: Vec<&str> = req
-        .uri()
-        .path()
-        .trim_start_matches('/')
-        .split('/')
-        .collect();
+fn parse_qsy_path<B>(req: &Request<B>, aliases: &ModeAliases) -> Result<Qsy, Box<HttpResponse>> {
+    parse_freq_mode(req.uri().path(), aliases)
+}
+
+// The optional ?force_mode=true|false on a QSY: re-send the mode even when
+// the rig is already in it.  Defaults to false.
+fn parse_force_mode<B>(req: &Request<B>) -> Result<bool, Box<HttpResponse>> {
+    let query = req.uri().query().unwrap_or("");
+    match url::form_urlencoded::parse(query.as_bytes()).find(|(key, _)| key == "force_mode") {
+        None => Ok(false),
+        Some((_, value)) => value.parse::<bool>().map_err(|_| {
+            Box::new(http_err_str(
+                StatusCode::BAD_REQUEST,
+                "force_mode must be true or false",
+            ))
+        }),
+    }
+}
+
+// Shared by every route that takes a '/<freq>/<mode>' tail, so they all
+// apply the same segment, integer and band-allocation checks.
+fn parse_freq_mode(path: &str, aliases: &ModeAliases) -> Result<Qsy, Box<HttpResponse>> {
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
 
     let &[freq_str, mode_str] = parts.as_slice() else {
         debug!("parse_qsy_path: wrong segment count ({})", parts.len());
@@ -210,7 +655,7 @@ fn parse_qsy_path<B>(req: &Request<B>) -> Result<Qsy, Box<HttpResponse>> {
         )));
     }
 
-    let mode = mode_str.parse::<WavelogMode>().map_err(|_| {
+    let mode = aliases.resolve(mode_str).ok_or_else(|| {
         debug!("parse_qsy_path: unrecognised mode {:?}", mode_str);
         Box::new(http_err_str(StatusCode::BAD_REQUEST, "Invalid mode"))
     })?;
@@ -220,837 +665,4948 @@ fn parse_qsy_path<B>(req: &Request<B>) -> Result<Qsy, Box<HttpResponse>> {
     })
 }
 
+// Receive and transmit halves of a /sat request.
+#[derive(Debug)]
+struct SatQsy {
+    rx: Qsy,
+    tx: Qsy,
+}
+
+// Parse '/<rx_hz>/<rx_mode>/<tx_hz>/<tx_mode>', the tail of a /sat route.
+// Each half gets the same checks as an ordinary QSY.
+fn parse_sat_path(path: &str, aliases: &ModeAliases) -> Result<SatQsy, Box<HttpResponse>> {
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+    let &[rx_freq, rx_mode, tx_freq, tx_mode] = parts.as_slice() else {
+        debug!("parse_sat_path: wrong segment count ({})", parts.len());
+        return Err(Box::new(http_err_str(
+            StatusCode::BAD_REQUEST,
+            "Expected /sat/<rx_hz>/<rx_mode>/<tx_hz>/<tx_mode>",
+        )));
+    };
+
+    Ok(SatQsy {
+        rx: parse_freq_mode(&format!("{rx_freq}/{rx_mode}"), aliases)?,
+        tx: parse_freq_mode(&format!("{tx_freq}/{tx_mode}"), aliases)?,
+    })
+}
+
+// Which branch of the mode heuristic produced a given FLRig mode.  Reported
+// by the /explain route so a "wrong mode on band X" report can say why.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ModeReason {
+    Ft8Window,
+    DigitalOutsideFt8,
+    PhoneBelow10MHz,
+    PhoneAbove10MHz,
+    RememberedBandMode,
+    Explicit,
+}
+
+impl ModeReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            ModeReason::Ft8Window => "matched-ft8",
+            ModeReason::DigitalOutsideFt8 => "digital-outside-ft8",
+            ModeReason::PhoneBelow10MHz => "phone-below-10mhz-lsb",
+            ModeReason::PhoneAbove10MHz => "phone-above-10mhz-usb",
+            ModeReason::RememberedBandMode => "remembered-band-mode",
+            ModeReason::Explicit => "explicit",
+        }
+    }
+}
+
+// Lowest frequency at which "phone" maps to USB rather than LSB.  The split
+// is inclusive: exactly 10.000000 MHz is USB.  It is deliberately not aligned
+// to a band edge: nothing between 7.2 and 14 MHz is a phone allocation (30m
+// is CW and data only), so anything Wavelog labels "phone" there is most
+// likely a data signal, and the sideband for data on 30m is USB.
+const PHONE_USB_FROM_HZ: f64 = 10_000_000.0;
+
 // Map a Wavelog bandmap mode + frequency to the FLRig mode string for the
-// connected rig.  The rig-specific mode names (e.g. "CW-U" vs "CW") come
-// from the ModeMap built at startup from the [CAT] config section.
+// connected rig, and report which heuristic fired.  The rig-specific mode
+// names (e.g. "CW-U" vs "CW") come from the ModeMap built at startup from the
+// [CAT] config section; `remembered` is the band's remembered voice mode.
 //
 // Heuristics applied:
 // * Digi/RTTY at a known FT8 frequency → mode_map.digital (the rig's data mode)
-// * Digi/RTTY elsewhere               → mode_map.rtty
+// * Digi elsewhere                    → mode_map.digi_outside_ft8()
+//                                       (default_digi_mode, else rtty)
+// * RTTY elsewhere                    → mode_map.rtty
+// * Phone on a band with a remembered voice mode → that mode
 // * Phone below 10 MHz                → LSB (convention)
 // * Phone at or above 10 MHz          → USB (convention)
 // * Explicit LSB/USB/AM/FM/CW         → pass straight through via the mode map
-fn wavelog_to_flrig_mode(
+//                                       (never overridden by the FT8 window
+//                                       or band memory: the user knows best)
+// * FT8/FT4/PSK/JS8/Olivia            → mode_map.digital at any frequency
+fn explain_flrig_mode(
     freq: f64,
     mode: WavelogMode,
     ft8_freqs: &[f64],
     mode_map: &ModeMap,
-) -> Mode {
+    remembered: Option<Mode>,
+) -> (Mode, ModeReason) {
     match mode {
-        WavelogMode::Cw => mode_map.cw,
+        WavelogMode::Cw => (mode_map.cw, ModeReason::Explicit),
         WavelogMode::Phone => {
-            if freq < 10_000_000.0 {
-                Mode::LSB
+            if let Some(m) = remembered {
+                (m, ModeReason::RememberedBandMode)
+            } else if freq < PHONE_USB_FROM_HZ {
+                (Mode::LSB, ModeReason::PhoneBelow10MHz)
             } else {
-                Mode::USB
+                (Mode::USB, ModeReason::PhoneAbove10MHz)
             }
         }
-        WavelogMode::LSB => Mode::LSB,
-        WavelogMode::USB => Mode::USB,
-        WavelogMode::Digi | WavelogMode::Rtty => {
-            if is_ft8(freq, ft8_freqs) {
-                mode_map.digital
-            } else {
-                mode_map.rtty
-            }
+        WavelogMode::LSB => (Mode::LSB, ModeReason::Explicit),
+        WavelogMode::USB => (Mode::USB, ModeReason::Explicit),
+        WavelogMode::Digi | WavelogMode::Rtty if is_ft8(freq, ft8_freqs) => {
+            (mode_map.digital, ModeReason::Ft8Window)
         }
-        WavelogMode::Am => Mode::AM,
-        WavelogMode::Fm => Mode::FM,
+        WavelogMode::Digi => (mode_map.digi_outside_ft8(), ModeReason::DigitalOutsideFt8),
+        WavelogMode::Rtty => (mode_map.rtty, ModeReason::DigitalOutsideFt8),
+        WavelogMode::Am => (Mode::AM, ModeReason::Explicit),
+        WavelogMode::Fm => (Mode::FM, ModeReason::Explicit),
+        WavelogMode::Ft8
+        | WavelogMode::Ft4
+        | WavelogMode::Psk
+        | WavelogMode::Js8
+        | WavelogMode::Olivia => (mode_map.digital, ModeReason::Explicit),
     }
 }
 
-async fn qsy(
-    rig: Arc<flrig::FLRig>,
-    req: Request<hyper::body::Incoming>,
-    mode_map: Arc<ModeMap>,
-    ft8_freqs: Arc<[f64]>,
-    wavelog_origin: Option<Arc<String>>,
-) -> Result<Response<Full<Bytes>>, Infallible> {
-    info!("qsy() called");
-
-    if let Some(expected) = &wavelog_origin {
-        if !check_origin(&req, expected) {
-            debug!("qsy: Origin header missing or does not match configured wavelog_origin");
-            return Ok(http_err_str(StatusCode::FORBIDDEN, "Forbidden"));
-        }
-    }
+// The bandmap mode closest to what the rig is doing, the reverse of
+// explain_flrig_mode, so a UI can offer the current mode in the bandmap's own
+// vocabulary.  Every data mode is "digi", and PSK is "psk", which QSY maps
+// back to the rig's data mode.  None for a mode FLRig reports that wlrigctl
+// does not know.
+fn wavelog_mode_for(rig_mode: &str) -> Option<WavelogMode> {
+    let mode = rig_mode.parse::<Mode>().ok()?;
+    Some(match mode {
+        Mode::LSB => WavelogMode::LSB,
+        Mode::USB => WavelogMode::USB,
+        Mode::AM | Mode::AM_N => WavelogMode::Am,
+        Mode::FM | Mode::FM_N => WavelogMode::Fm,
+        Mode::CW | Mode::CW_U | Mode::CW_R | Mode::CW_L => WavelogMode::Cw,
+        Mode::RTTY | Mode::RTTY_U | Mode::RTTY_R | Mode::RTTY_L | Mode::FSK => WavelogMode::Rtty,
+        Mode::PSK => WavelogMode::Psk,
+        Mode::D_LSB
+        | Mode::DATA_L
+        | Mode::D_USB
+        | Mode::DATA_U
+        | Mode::USB_D
+        | Mode::DATA
+        | Mode::DATA_FM
+        | Mode::DATA_FMN => WavelogMode::Digi,
+    })
+}
 
-    let qsyinfo = match parse_qsy_path(&req) {
-        Err(e) => return Ok(*e), // Infallible
+// GET /explain/<freq>/<mode>: run the QSY mode mapping and report the result
+// without touching the rig.  Lets a user share exactly what wlrigctl would
+// have sent to FLRig for a given bandmap click.
+fn explain(state: &CatState, tail: &str) -> HttpResponse {
+    let qsyinfo = match parse_freq_mode(tail, &state.mode_aliases) {
+        Err(e) => return *e,
         Ok(q) => q,
     };
 
-    info!("Got freq:{} mode:{:?}", qsyinfo.freq, qsyinfo.mode);
-    let freq: f64 = qsyinfo.freq;
+    let (mode, reason) = state.flrig_mode(qsyinfo.freq, qsyinfo.mode);
 
-    let mode = wavelog_to_flrig_mode(freq, qsyinfo.mode, &ft8_freqs, &mode_map);
+    json_ok(json!({
+        "frequency": qsyinfo.freq,
+        "requested_mode": qsyinfo.mode.as_str(),
+        "mode": mode.to_string(),
+        "reason": reason.as_str(),
+    }))
+}
 
-    if let Err(e) = rig.set_vfo(freq).await {
-        return Ok(http_err_str(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to set frequency: {e}"),
-        ));
-    };
+// GET /bandplan: everything explain_flrig_mode decides with, as loaded, so a
+// user can check their config parsed as intended before reporting a wrong
+// mode.  Read-only; FLRig is not contacted.
+fn bandplan(state: &CatState) -> HttpResponse {
+    let live = state.live.read().unwrap();
+    json_ok(json!({
+        "ft8_frequencies": &*live.ft8_freqs,
+        "ft8_automode": live.ft8_automode,
+        "ft8_window_hz": {
+            "below": FT8_LO_ALLOWANCE,
+            "above": FT8_HI_ALLOWANCE,
+        },
+        "phone_usb_from_hz": PHONE_USB_FROM_HZ,
+        "modes": {
+            "cw": live.mode_map.cw.to_string(),
+            "rtty": live.mode_map.rtty.to_string(),
+            "digital": live.mode_map.digital.to_string(),
+            "digi_outside_ft8": live.mode_map.digi_outside_ft8().to_string(),
+        },
+        "band_modes": state.rig.remembered_modes(),
+    }))
+}
 
-    if let Err(e) = rig.set_mode(mode).await {
-        return Ok(http_err_str(
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to set mode: {e}"),
-        ));
+// The rig's frequency, mode and power: the cached read if it is fresh enough,
+// otherwise a new one, which is then cached.
+async fn rig_state(state: &CatState) -> Result<RadioData, ClientError> {
+    if let Some(data) = state.radio_cache.fresh(state.cache_ttl) {
+        return Ok(data);
     }
+    let generation = state.radio_cache.generation();
+    state
+        .rig
+        .fetch_radio_data()
+        .await
+        .inspect(|data| state.radio_cache.store(generation, data))
+}
 
-    let body = json!({
+// Body of the /status response.  FLRig reports frequency and power as
+// strings; numbers that fail to parse are reported as 0 rather than failing
+// the whole request, as radio_status_msg does for the WebSocket feed.
+fn status_json(data: &RadioData) -> serde_json::Value {
+    let freq_hz = data.frequency.parse::<f64>().unwrap_or(0.0) as u32;
+    json!({
         "status": "ok",
-        "connected": true,
-        "frequency": freq,
-        "mode": mode.to_string(),
-        "rig": rig.get_identifier(),
+        "rig": data.radio,
+        "frequency": freq_hz,
+        "band": band_name(freq_hz),
+        "mode": data.mode,
+        "rig_mode": data.rig_mode,
+        "wavelog_mode": wavelog_mode_for(&data.rig_mode).map(WavelogMode::as_str),
+        "power": data.power.parse::<u32>().unwrap_or(0),
     })
-    .to_string();
+}
 
-    Ok(Response::builder()
-        .status(200)
-        .header(CONTENT_TYPE, "application/json")
-        .header("Access-Control-Allow-Origin", "*")
-        .header("Access-Control-Allow-Methods", "GET, POST, OPTIONS")
-        .header("Access-Control-Allow-Headers", "Content-Type")
-        .body(Full::new(Bytes::from(body)))
-        .unwrap())
+// Whether the rig is transmitting, and which source said so: the rig's own
+// PTT when FLRig reports it, as it was read just now from the rig itself,
+// else the latest WSJT-X Status, else unknown.
+fn tx_state(ptt: Option<bool>, wsjtx: Option<bool>) -> (Option<bool>, Option<&'static str>) {
+    match (ptt, wsjtx) {
+        (Some(ptt), _) => (Some(ptt), Some("flrig")),
+        (None, Some(transmitting)) => (Some(transmitting), Some("wsjtx")),
+        (None, None) => (None, None),
+    }
 }
 
-#[allow(non_snake_case)]
-pub async fn CAT_thread(
-    settings: CatSettings,
-    rig: &Arc<flrig::FLRig>,
-    token: CancellationToken,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Listen on TCP socket for someone in Cloudlog/Wavelog clicking the bandmap
-    let addr = SocketAddr::from((CAT_BIND_HOST, settings.port));
+// GET /status: report frequency, band, mode, power and TX/RX state, plus DSP
+// feature state, passband and S-meter when [flrig] report_dsp /
+// report_passband / report_smeter are set.  The rig is only read if nothing
+// has read it within cache_ms.  DSP, passband and S-meter are nice-to-haves,
+// so failing to read them omits the field rather than the reply, as does a
+// rig that reports no passband detail at all.
+async fn status(state: &CatState) -> HttpResponse {
+    match rig_state(state).await {
+        Ok(mut data) => {
+            data.radio = state.rig.get_identifier();
+            let mut body = status_json(&data);
+            if state.rig.reports_dsp() {
+                match state.rig.get_dsp_state().await {
+                    Ok(dsp) => body["dsp"] = json!(dsp),
+                    Err(e) => debug!("Could not read DSP state: {e}"),
+                }
+            }
+            if state.rig.reports_passband() {
+                match state.rig.get_passband().await {
+                    Ok(passband) if passband != flrig::PassbandState::default() => {
+                        body["passband"] = json!(passband)
+                    }
+                    Ok(_) => {}
+                    Err(e) => debug!("Could not read passband: {e}"),
+                }
+            }
+            if state.rig.reports_smeter() {
+                match state.rig.read_smeter().await {
+                    Ok(reading) => body["smeter"] = json!(reading),
+                    Err(e) => debug!("Could not read S-meter: {e}"),
+                }
+            }
+            let ptt = state.rig.get_ptt().await.unwrap_or_else(|e| {
+                debug!("Could not read PTT: {e}");
+                None
+            });
+            let (transmitting, source) = tx_state(ptt, state.wsjtx.transmitting());
+            body["transmitting"] = json!(transmitting);
+            body["transmitting_source"] = json!(source);
+            if let Some(warning) = state.rig.maxpower_warning() {
+                body["warning"] = json!(warning);
+            }
+            json_ok(body)
+        }
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read rig state: {e}"),
+        ),
+    }
+}
 
-    // Build the mode map from config; defaults to ICOM/generic names if fields are absent.
-    let mode_map: Arc<ModeMap> = Arc::new(flrig::build_mode_map(
-        settings.cw_mode.as_deref(),
-        settings.rtty_mode.as_deref(),
-        settings.digital_mode.as_deref(),
-    ));
+// Minimal sanity check on a manually submitted ADIF record before it is
+// forwarded to Wavelog: it must name a callsign and be terminated by <EOR>.
+// Wavelog does the real validation; this only catches obviously wrong bodies
+// (empty posts, JSON sent by mistake) with a helpful 400.
+fn is_plausible_adif(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    lower.contains("<call:") && lower.contains("<eor>")
+}
 
-    // Build the FT8 frequency list: use the config override if provided, otherwise defaults.
-    let ft8_freqs: Arc<[f64]> = match settings.ft8_frequencies {
-        Some(freqs) => freqs.iter().map(|&f| f as f64).collect::<Vec<f64>>().into(),
-        None => Arc::from(DEFAULT_FT8_FREQS.as_slice()),
+// Read a whole request body, within [CAT] max_body_bytes and
+// body_timeout_ms.  Every route that takes a body reads it through here.
+async fn read_body<B>(state: &CatState, body: B) -> Result<Bytes, Box<HttpResponse>>
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let limited = Limited::new(body, state.max_body_bytes);
+    match tokio::time::timeout(state.body_timeout, limited.collect()).await {
+        Ok(Ok(collected)) => Ok(collected.to_bytes()),
+        Ok(Err(e)) if e.is::<LengthLimitError>() => Err(Box::new(http_err_str(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body is over {} bytes", state.max_body_bytes),
+        ))),
+        Ok(Err(e)) => {
+            debug!("Failed to read request body: {e}");
+            Err(Box::new(http_err_str(
+                StatusCode::BAD_REQUEST,
+                "Could not read request body",
+            )))
+        }
+        Err(_) => Err(Box::new(http_err_str(
+            StatusCode::REQUEST_TIMEOUT,
+            "Timed out reading request body",
+        ))),
+    }
+}
+
+// POST /qso: forward an ADIF record in the request body to Wavelog through
+// the same upload path used for WSJT-X QSOs.  Wavelog's HTTP status is
+// passed back to the caller unchanged.
+async fn log_qso<B>(state: &CatState, req: Request<B>) -> HttpResponse
+where
+    B: Body<Data = Bytes>,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let body = match read_body(state, req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return *e,
     };
 
-    let wavelog_origin: Option<Arc<String>> = settings.wavelog_origin.map(Arc::new);
+    let Ok(adif_text) = String::from_utf8(body.to_vec()) else {
+        return http_err_str(StatusCode::BAD_REQUEST, "ADIF must be UTF-8 text");
+    };
 
-    info!("Listening for CAT requests from Wavelog on: {:#?}", addr);
+    if !is_plausible_adif(&adif_text) {
+        return http_err_str(
+            StatusCode::BAD_REQUEST,
+            "Expected an ADIF record with a CALL field and <EOR>",
+        );
+    }
 
-    let listener = TcpListener::bind(addr).await?;
+    info!("Forwarding manually submitted QSO to Wavelog");
+    match upload_wsjtx_qso_data(&state.client, &state.wavelog, adif_text, &state.latency).await {
+        Ok(status) => json_response(
+            status,
+            json!({
+                "status": "ok",
+                "upstream_status": status.as_u16(),
+            }),
+        ),
+        Err(WavelogError::HttpStatus { code, body }) => json_response(
+            code,
+            json!({
+                "status": "error",
+                "upstream_status": code.as_u16(),
+                "message": body,
+            }),
+        ),
+        Err(e) => http_err_str(
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to upload QSO to Wavelog: {e}"),
+        ),
+    }
+}
 
-    loop {
-        // accept a series of TCP connections arising from clicks on bandmap in Cloudlog/Wavelog
-        let (stream, _) = tokio::select! {
-            _ = token.cancelled() => {
-                info!("CAT thread shutting down");
-                return Ok(());
-            }
-            result = listener.accept() => result?,
-        };
-        let io = TokioIo::new(stream);
-        let rig_for_qsy = rig.clone();
-        let mode_map_for_qsy = mode_map.clone();
-        let ft8_freqs_for_qsy = ft8_freqs.clone();
-        let wavelog_origin_for_qsy = wavelog_origin.clone();
-        tokio::task::spawn(async move {
-            if let Err(err) = http1::Builder::new()
-                .half_close(true)
-                .serve_connection(
-                    io,
-                    service_fn(move |req| {
-                        qsy(
-                            rig_for_qsy.clone(),
-                            req,
-                            mode_map_for_qsy.clone(),
-                            ft8_freqs_for_qsy.clone(),
-                            wavelog_origin_for_qsy.clone(),
-                        )
-                    }),
-                )
-                .await
-            {
-                // This seems to happen if wavelog doesn't wait for the response to their second
-                // attempt(!) to qsy, and drop the TCP connection early
-                debug!("Error serving connection: {:?}", err);
-            }
-        });
+// Offset from a CW spot's signal frequency to the dial frequency that puts
+// the signal at `pitch_hz`: below the signal on the upper-sideband CW modes,
+// above it on the reverse (lower-sideband) ones, and none on other modes.
+fn cw_dial_offset(mode: Mode, pitch_hz: u32) -> f64 {
+    match mode {
+        Mode::CW | Mode::CW_U => -(pitch_hz as f64),
+        Mode::CW_R | Mode::CW_L => pitch_hz as f64,
+        _ => 0.0,
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// `freq` rounded to the nearest multiple of `step_hz`; unchanged for 0.
+fn snap_to_step(freq: f64, step_hz: u32) -> f64 {
+    if step_hz == 0 {
+        return freq;
+    }
+    let step = step_hz as f64;
+    (freq / step).round() * step
+}
 
-    //////////////////////////////////////////////////////////////
-    // Tests for FT8 frequency identification
-    //////////////////////////////////////////////////////////////
-    // This file assumes the following centres of activity for FT8
-    // Further more, the is_ft8() function checks for:
-    //     * >= centre - 2kHz
-    //     * <  centre + 3kHz
-    //
-    // Therefore we check either side of these boundaries
-    //
-    // Each band gets four boundary checks:
-    //   - centre (must be in-window)
-    //   - lower edge: centre - LO_ALLOWANCE (must be in-window)
-    //   - just below lower edge (must NOT be in-window)
-    //   - upper edge: centre + HI_ALLOWANCE (must NOT be in-window, it's exclusive)
-    //
-    // 160m: 1.840 MHz
-    // 80m:  3.575 MHz
-    // 40m:  7.074 MHz
-    // 30m:  10.136 MHz
-    // 20m:  14.074 MHz
-    // 17m:  18.100 MHz
-    // 15m:  21.074 MHz
-    // 12m:  24.915 MHz
-    // 10m:  28.074 MHz
-    // 6m:   50.313 MHz
+// A validated QSY: where to put the dial, and in which mode.
+struct QsyPlan {
+    freq: f64,
+    mode: Mode,
+    force_mode: bool,
+    // The dial frequency before freq_step_hz rounding, when that changed it.
+    unsnapped: Option<f64>,
+}
 
-    // --- 160m ---
-    #[test]
-    fn ft8_160m() {
-        assert!(is_ft8(1_840_000.0, &DEFAULT_FT8_FREQS));
-    }
-    #[test]
-    fn ft8_160m_lower_edge() {
-        assert!(is_ft8(1_838_000.0, &DEFAULT_FT8_FREQS));
+fn plan_qsy<B>(state: &CatState, req: &Request<B>) -> Result<QsyPlan, Box<HttpResponse>> {
+    let qsyinfo = parse_qsy_path(req, &state.mode_aliases)?;
+    let force_mode = parse_force_mode(req)?;
+
+    info!("Got freq:{} mode:{:?}", qsyinfo.freq, qsyinfo.mode);
+    let (mode, _) = state.flrig_mode(qsyinfo.freq, qsyinfo.mode);
+    let dial = qsyinfo.freq + cw_dial_offset(mode, state.live.read().unwrap().cw_pitch_hz);
+    let freq = snap_to_step(dial, state.freq_step_hz);
+    if freq != dial && !is_amateur_frequency(freq as u32) {
+        return Err(Box::new(http_err_str(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "{freq} Hz (rounded to freq_step_hz) is outside permitted UK amateur allocations"
+            ),
+        )));
     }
-    #[test]
-    fn ft8_160m_below() {
-        assert!(!is_ft8(1_837_999.9999, &DEFAULT_FT8_FREQS));
+    Ok(QsyPlan {
+        freq,
+        mode,
+        force_mode,
+        unsnapped: (freq != dial).then_some(dial),
+    })
+}
+
+// Carry out a planned QSY.  The result is the response's status and body.
+async fn tune(state: &CatState, plan: &QsyPlan) -> (StatusCode, serde_json::Value) {
+    let QsyPlan {
+        freq,
+        mode,
+        force_mode,
+        unsnapped,
+    } = *plan;
+    let error = |msg: String| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "status": "error", "message": msg }),
+        )
+    };
+
+    let _tuning = state.rig.lock_tuning().await;
+    if let Err(e) = state.rig.set_vfo(freq).await {
+        return error(format!("Failed to set frequency: {e}"));
+    };
+
+    if let Err(e) = state.rig.set_mode(mode, force_mode).await {
+        return error(format!("Failed to set mode: {e}"));
     }
-    #[test]
-    fn ft8_160m_above() {
-        assert!(!is_ft8(1_843_000.0, &DEFAULT_FT8_FREQS));
+
+    // The QSY itself has happened, so a failure here is logged, not returned.
+    if let Err(e) = state.rig.enforce_mode_power(mode).await {
+        error!("Could not apply the {mode} power limit: {e}");
+    }
+
+    let mut body = json!({
+        "status": "ok",
+        "connected": true,
+        "frequency": freq,
+        "mode": mode.to_string(),
+        "rig": state.rig.get_identifier(),
+    });
+    if let Some(requested) = unsnapped {
+        body["requested_frequency"] = requested.into();
+    }
+    // With verify_set, say whether the rig really went where it was sent.
+    // A failed read-back is unverified too, with the actual values left out.
+    if state.rig.verifies_set() {
+        match state.rig.verify_set(freq, mode).await {
+            Ok(check) => {
+                if !check.verified {
+                    warn!(
+                        "Rig did not take the QSY: asked for {freq} {mode}, reports {} {}",
+                        check.frequency, check.mode
+                    );
+                }
+                body["verified"] = check.verified.into();
+                body["actual_frequency"] = check.frequency.into();
+                body["actual_mode"] = check.mode.into();
+            }
+            Err(e) => {
+                warn!("Could not read the rig back after a QSY: {e}");
+                body["verified"] = false.into();
+            }
+        }
+    }
+    (StatusCode::OK, body)
+}
+
+async fn qsy<B>(state: &CatState, req: Request<B>) -> HttpResponse {
+    info!("qsy() called");
+
+    let plan = match plan_qsy(state, &req) {
+        Err(e) => return *e,
+        Ok(p) => p,
+    };
+    let (status, body) = tune(state, &plan).await;
+    json_response(status, body)
+}
+
+// respond_early QSY: validate, answer straight away, then tune in the
+// background.  /qsy/last reports "pending" until the rig has been set, then
+// what `qsy` would have returned.
+fn qsy_early<B>(state: &Arc<CatState>, req: Request<B>) -> HttpResponse {
+    info!("qsy() called, responding early");
+
+    let plan = match plan_qsy(state, &req) {
+        Err(e) => return *e,
+        Ok(p) => p,
+    };
+    let mut summary = json!({
+        "frequency": plan.freq,
+        "mode": plan.mode.to_string(),
+        "rig": state.rig.get_identifier(),
+    });
+    if let Some(requested) = plan.unsnapped {
+        summary["requested_frequency"] = requested.into();
+    }
+    let with_status = |status: &str| {
+        let mut body = summary.clone();
+        body["status"] = status.into();
+        body
+    };
+
+    let number = state.qsy_count.fetch_add(1, Ordering::SeqCst);
+    *state.last_qsy.lock().unwrap() = Some((number, StatusCode::ACCEPTED, with_status("pending")));
+    let state = state.clone();
+    tokio::spawn(async move {
+        let (status, body) = tune(&state, &plan).await;
+        if status != StatusCode::OK {
+            error!("Early-answered QSY failed: {}", body["message"]);
+        }
+        state.invalidate_radio_cache();
+        let mut last = state.last_qsy.lock().unwrap();
+        if last.as_ref().is_some_and(|(n, _, _)| *n == number) {
+            *last = Some((number, status, body));
+        }
+    });
+    json_ok(with_status("accepted"))
+}
+
+// GET /qsy/last: how the most recent respond_early QSY went.
+fn last_qsy(state: &CatState) -> HttpResponse {
+    match &*state.last_qsy.lock().unwrap() {
+        Some((_, status, body)) => json_response(*status, body.clone()),
+        None => http_err_str(StatusCode::NOT_FOUND, "No QSY since wlrigctl started"),
+    }
+}
+
+// GET /band/next and /band/prev: tune to the home frequency (band_homes) of
+// the next band up or down.  A sideband voice mode is re-chosen for the new
+// band as for a "phone" click; any other mode is left as it is.
+async fn band_step(state: &CatState, up: bool) -> HttpResponse {
+    if state.band_homes.is_empty() {
+        return http_err_str(
+            StatusCode::NOT_IMPLEMENTED,
+            "No [CAT] band_homes configured",
+        );
+    }
+    // Taken before reading where the rig is, so two quick clicks step two
+    // bands rather than both stepping from the same one.
+    let _tuning = state.rig.lock_tuning().await;
+    state.invalidate_radio_cache();
+    let data = match rig_state(state).await {
+        Ok(data) => data,
+        Err(e) => {
+            return http_err_str(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read rig state: {e}"),
+            )
+        }
+    };
+    let freq = data.frequency.parse::<f64>().unwrap_or(0.0) as u32;
+    let Some(home) = step_band(&state.band_homes, freq, up, state.band_wrap) else {
+        let end = if up { "highest" } else { "lowest" };
+        return http_err_str(
+            StatusCode::CONFLICT,
+            format!("Already on the {end} band in band_homes"),
+        );
+    };
+
+    let mode = match data.rig_mode.parse::<Mode>() {
+        Ok(Mode::LSB | Mode::USB) => Some(state.flrig_mode(home as f64, WavelogMode::Phone).0),
+        _ => None,
+    };
+    if let Err(e) = state.rig.set_vfo(home as f64).await {
+        return http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set frequency: {e}"),
+        );
+    }
+    if let Some(mode) = mode {
+        if let Err(e) = state.rig.set_mode(mode, false).await {
+            return http_err_str(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to set mode: {e}"),
+            );
+        }
+    }
+
+    json_ok(json!({
+        "status": "ok",
+        "band": band_name(home),
+        "frequency": home,
+        "mode": mode.map_or(data.rig_mode, |m| m.to_string()),
+        "rig": state.rig.get_identifier(),
+    }))
+}
+
+// GET /sat/<rx_hz>/<rx_mode>/<tx_hz>/<tx_mode>: set up full-duplex
+// satellite working, receiving on VFO A (the selected VFO) and transmitting
+// on VFO B.  Split is deliberately left alone.  Rigs that cannot give VFO B
+// its own mode are reported as 501, after the frequencies have been set.
+async fn sat(state: &CatState, tail: &str) -> HttpResponse {
+    let sat = match parse_sat_path(tail, &state.mode_aliases) {
+        Err(e) => return *e,
+        Ok(s) => s,
+    };
+    info!(
+        "Satellite rx:{} {:?} tx:{} {:?}",
+        sat.rx.freq, sat.rx.mode, sat.tx.freq, sat.tx.mode
+    );
+
+    let (rx_mode, _) = state.flrig_mode(sat.rx.freq, sat.rx.mode);
+    let (tx_mode, _) = state.flrig_mode(sat.tx.freq, sat.tx.mode);
+
+    let _tuning = state.rig.lock_tuning().await;
+    if let Err(e) = state.rig.set_vfo(sat.rx.freq).await {
+        return http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set VFO A frequency: {e}"),
+        );
+    }
+    if let Err(e) = state.rig.set_mode(rx_mode, false).await {
+        return http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set VFO A mode: {e}"),
+        );
+    }
+    if let Err(e) = state.rig.set_vfo_b(sat.tx.freq).await {
+        return http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set VFO B frequency: {e}"),
+        );
+    }
+    match state.rig.set_mode_b(tx_mode).await {
+        Ok(()) => {}
+        Err(flrig::FlrigError::Unsupported(what)) => {
+            return http_err_str(
+                StatusCode::NOT_IMPLEMENTED,
+                format!("Both frequencies were set, but {what}"),
+            )
+        }
+        Err(flrig::FlrigError::DxrClient(e @ ClientError::Fault { .. })) => {
+            return http_err_str(
+                StatusCode::NOT_IMPLEMENTED,
+                format!("VFO B mode is not available through this FLRig: {e}"),
+            )
+        }
+        Err(e) => {
+            return http_err_str(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to set VFO B mode: {e}"),
+            )
+        }
+    }
+
+    json_ok(json!({
+        "status": "ok",
+        "rig": state.rig.get_identifier(),
+        "rx": { "vfo": "A", "frequency": sat.rx.freq, "mode": rx_mode.to_string() },
+        "tx": { "vfo": "B", "frequency": sat.tx.freq, "mode": tx_mode.to_string() },
+    }))
+}
+
+// GET /refresh: have the Wavelog poll loop read the rig and upload to
+// Wavelog now, whether or not anything changed, and report what it read.
+// Going through the loop, rather than reading here, keeps its change
+// detection and upload throttle consistent with what Wavelog was sent.
+async fn refresh(state: &CatState) -> HttpResponse {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    if state
+        .refresh
+        .send(PollRequest::Refresh(reply_tx))
+        .await
+        .is_err()
+    {
+        return http_err_str(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Wavelog poll loop is not running",
+        );
+    }
+    match reply_rx.await {
+        Ok(Ok(refreshed)) => {
+            let mut body = status_json(&refreshed.data);
+            body["uploaded"] = json!(refreshed.uploaded);
+            json_ok(body)
+        }
+        Ok(Err(e)) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read rig: {e}"),
+        ),
+        Err(_) => http_err_str(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Wavelog poll loop stopped before replying",
+        ),
+    }
+}
+
+// GET /smeter: the current S-meter reading, for a lightweight signal display.
+// Read-only.  FLRig answers with an XML-RPC fault when the connected rig has
+// no S-meter readout, which is reported as 501 rather than a server fault.
+async fn smeter(state: &CatState) -> HttpResponse {
+    match state.rig.read_smeter().await {
+        Ok(reading) => json_ok(json!({
+            "status": "ok",
+            "smeter": reading.raw,
+            "s_units": reading.s_units,
+            "db_over_s9": reading.db_over_s9,
+        })),
+        Err(e @ ClientError::Fault { .. }) => http_err_str(
+            StatusCode::NOT_IMPLEMENTED,
+            format!("S-meter not supported by this rig: {e}"),
+        ),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read S-meter: {e}"),
+        ),
+    }
+}
+
+// GET /modes: the mode names the connected rig supports, so a web UI can
+// offer only those.  An FLRig fault means the back-end cannot list modes.
+async fn modes(state: &CatState) -> HttpResponse {
+    let modes = match cached(&state.modes_cache, state.cache_ttl) {
+        Some(modes) => Ok(modes),
+        None => state.rig.get_modes().await.inspect(|modes| {
+            *state.modes_cache.lock().unwrap() = Some((Instant::now(), modes.clone()));
+        }),
+    };
+    match modes {
+        Ok(modes) => json_ok(json!({
+            "status": "ok",
+            "rig": state.rig.get_identifier(),
+            "modes": modes,
+        })),
+        Err(e @ ClientError::Fault { .. }) => http_err_str(
+            StatusCode::NOT_IMPLEMENTED,
+            format!("Mode list not available for this rig: {e}"),
+        ),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read mode list: {e}"),
+        ),
+    }
+}
+
+// GET /flrig/methods: every FLRig method wlrigctl may call and whether this
+// FLRig lists it, for debugging an unusual rig.  "available" is null for all
+// of them if FLRig does not answer system.listMethods.
+async fn flrig_methods(state: &CatState) -> HttpResponse {
+    let listed = match state.rig.list_methods().await {
+        Ok(listed) => listed,
+        Err(e) => {
+            return http_err_str(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read FLRig's method list: {e}"),
+            )
+        }
+    };
+    let methods: Vec<serde_json::Value> = state
+        .rig
+        .used_methods()
+        .into_iter()
+        .map(|method| {
+            json!({
+                "method": method,
+                "available": listed.as_ref().map(|l| l.iter().any(|m| m == method)),
+            })
+        })
+        .collect();
+    json_ok(json!({
+        "status": "ok",
+        "introspection": listed.is_some(),
+        "methods": methods,
+    }))
+}
+
+// GET /health: whether FLRig is reachable, for external monitoring.  It only
+// reports "down" (503) after health_failures FLRig calls in a row have gone
+// unanswered, counting the poll loop's as well as this probe, and reports
+// "ok" again as soon as one succeeds.
+async fn health(state: &CatState) -> HttpResponse {
+    let probe = state.rig.probe().await;
+    let failures = state.rig.consecutive_failures();
+    if failures < state.health_failures {
+        return json_ok(json!({
+            "status": "ok",
+            "consecutive_failures": failures,
+        }));
+    }
+    let message = match probe {
+        Ok(()) => "FLRig not answering".to_string(),
+        Err(e) => format!("FLRig not answering: {e}"),
+    };
+    json_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        json!({
+            "status": "down",
+            "consecutive_failures": failures,
+            "message": message,
+        }),
+    )
+}
+
+// How recently WSJT-X was heard from: "never" since startup, "stale" if
+// not within `window` of `now`, else "ok".
+fn wsjtx_liveness(last: Option<Instant>, now: Instant, window: Duration) -> &'static str {
+    match last {
+        None => "never",
+        Some(at) if now.saturating_duration_since(at) > window => "stale",
+        Some(_) => "ok",
+    }
+}
+
+// GET /health/wsjtx: whether the WSJT-X listener is still receiving, so
+// monitoring notices WSJT-X crashing or its datagrams going astray.
+// Anything but "ok" is 503.
+fn wsjtx_health(state: &CatState) -> HttpResponse {
+    let last = state.wsjtx.last_received();
+    let now = Instant::now();
+    let liveness = wsjtx_liveness(last, now, state.wsjtx_health);
+    let code = if liveness == "ok" {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    json_response(
+        code,
+        json!({
+            "status": liveness,
+            "last_received_secs": last.map(|at| now.saturating_duration_since(at).as_secs()),
+            "window_secs": state.wsjtx_health.as_secs(),
+        }),
+    )
+}
+
+// GET /stats: rolling round-trip times of FLRig calls and Wavelog uploads,
+// to tell whether lag is in the rig link or the logbook server.  A side with
+// nothing recorded yet is null.
+fn stats(state: &CatState) -> HttpResponse {
+    let latency = &state.latency;
+    json_ok(json!({
+        "flrig": latency.flrig(),
+        "wavelog": latency.wavelog(),
+    }))
+}
+
+// GET /config: the effective configuration with secrets redacted, for
+// pasting into support requests.
+fn config(state: &CatState) -> HttpResponse {
+    json_ok(state.config.clone())
+}
+
+// GET /power/on and /power/off.  Not every rig can be powered remotely, so an
+// unconfigured command is reported as 501 rather than a server fault.
+async fn power(state: &CatState, on: bool) -> HttpResponse {
+    let result = if on {
+        state.rig.power_on().await
+    } else {
+        state.rig.power_off().await
+    };
+
+    match result {
+        Ok(()) => json_ok(json!({
+            "status": "ok",
+            "power": if on { "on" } else { "off" },
+            "rig": state.rig.get_identifier(),
+        })),
+        Err(flrig::FlrigError::Unsupported(what)) => http_err_str(
+            StatusCode::NOT_IMPLEMENTED,
+            format!("Remote power control unsupported: {what}"),
+        ),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to switch rig power: {e}"),
+        ),
+    }
+}
+
+// GET /power/up and /power/down: move the output power by power_step,
+// stopping at 0 and maxpower rather than refusing, so a rotary encoder can
+// be turned past either end.
+async fn power_step(state: &CatState, up: bool) -> HttpResponse {
+    let step = state.power_step.watts(state.rig.maxpower());
+    // Taken so two quick clicks step twice rather than both stepping from
+    // the same reading.
+    let _tuning = state.rig.lock_tuning().await;
+    match state.rig.step_power_watts(step, up).await {
+        Ok(watts) => json_ok(json!({
+            "status": "ok",
+            "power": watts,
+            "rig": state.rig.get_identifier(),
+        })),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to set power: {e}"),
+        ),
+    }
+}
+
+// GET /wsjtx/freetext?text=...&send=true: set WSJT-X's free text message,
+// and optionally transmit it.  `send` defaults to false.
+async fn wsjtx_free_text<B>(state: &CatState, req: &Request<B>) -> HttpResponse {
+    let mut text = None;
+    let mut send = Some(false);
+    for (key, value) in url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes()) {
+        match key.as_ref() {
+            "text" => text = Some(value.into_owned()),
+            "send" => send = value.parse::<bool>().ok(),
+            _ => {}
+        }
+    }
+    let Some(text) = text else {
+        return http_err_str(StatusCode::BAD_REQUEST, "Missing text parameter");
+    };
+    let Some(send) = send else {
+        return http_err_str(StatusCode::BAD_REQUEST, "send must be true or false");
+    };
+
+    match state.wsjtx.send_free_text(&text, send).await {
+        Ok(instances) => json_ok(json!({
+            "status": "ok",
+            "text": text,
+            "send": send,
+            "instances": instances,
+        })),
+        Err(WsjtxError::InvalidCommand(msg)) => http_err_str(StatusCode::BAD_REQUEST, msg),
+        Err(WsjtxError::NotConnected(msg)) => http_err_str(StatusCode::SERVICE_UNAVAILABLE, msg),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send free text to WSJT-X: {e}"),
+        ),
+    }
+}
+
+// GET /wsjtx/replay: ask WSJT-X to send its recent decodes again, e.g. for
+// a dashboard that has just connected.
+async fn wsjtx_replay(state: &CatState) -> HttpResponse {
+    match state.wsjtx.send_replay().await {
+        Ok(instances) => json_ok(json!({
+            "status": "ok",
+            "instances": instances,
+        })),
+        Err(WsjtxError::NotConnected(msg)) => http_err_str(StatusCode::SERVICE_UNAVAILABLE, msg),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send Replay to WSJT-X: {e}"),
+        ),
+    }
+}
+
+// GET /wsjtx/configuration?name=...: make the named configuration (as in
+// WSJT-X's Configurations menu) the current one, e.g. FT8 to FT4.
+async fn wsjtx_configuration<B>(state: &CatState, req: &Request<B>) -> HttpResponse {
+    let name = url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|(key, _)| key == "name")
+        .map(|(_, value)| value.into_owned());
+    let Some(name) = name else {
+        return http_err_str(StatusCode::BAD_REQUEST, "Missing name parameter");
+    };
+
+    match state.wsjtx.send_switch_configuration(&name).await {
+        Ok(instances) => json_ok(json!({
+            "status": "ok",
+            "configuration": name,
+            "instances": instances,
+        })),
+        Err(WsjtxError::InvalidCommand(msg)) => http_err_str(StatusCode::BAD_REQUEST, msg),
+        Err(WsjtxError::NotConnected(msg)) => http_err_str(StatusCode::SERVICE_UNAVAILABLE, msg),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send SwitchConfiguration to WSJT-X: {e}"),
+        ),
+    }
+}
+
+// GET /wsjtx/location?grid=...: set the station's Maidenhead locator in
+// WSJT-X, e.g. when roving into a new grid.
+async fn wsjtx_location<B>(state: &CatState, req: &Request<B>) -> HttpResponse {
+    let grid = url::form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|(key, _)| key == "grid")
+        .map(|(_, value)| value.into_owned());
+    let Some(grid) = grid else {
+        return http_err_str(StatusCode::BAD_REQUEST, "Missing grid parameter");
+    };
+
+    match state.wsjtx.send_location(&grid).await {
+        Ok(instances) => json_ok(json!({
+            "status": "ok",
+            "grid": grid,
+            "instances": instances,
+        })),
+        Err(WsjtxError::InvalidCommand(msg)) => http_err_str(StatusCode::BAD_REQUEST, msg),
+        Err(WsjtxError::NotConnected(msg)) => http_err_str(StatusCode::SERVICE_UNAVAILABLE, msg),
+        Err(e) => http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to send Location to WSJT-X: {e}"),
+        ),
+    }
+}
+
+// The rig state as a /ws stream reports it: the [websocket] server's
+// radio_status fields, with the rig named as /status names it.
+fn ws_state(state: &CatState, data: &RadioData) -> serde_json::Map<String, serde_json::Value> {
+    let mut data = data.clone();
+    data.radio = state.rig.get_identifier();
+    radio_status_fields(&data)
+}
+
+// The fields of `next` whose values differ from `last`.
+fn ws_delta(
+    last: &serde_json::Map<String, serde_json::Value>,
+    next: &serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    next.iter()
+        .filter(|(key, value)| last.get(*key) != Some(value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+// Floor for the /ws ping interval, which a connection_timeout_secs of 0
+// would otherwise make zero.
+const MIN_WS_PING: Duration = Duration::from_millis(100);
+
+// What a connection passes on to a /ws stream upgraded from it: its
+// max_connections permit, which the stream holds until it ends, and the
+// server's cancel token.
+struct ConnectionUpgrade {
+    permit: Mutex<Option<OwnedSemaphorePermit>>,
+    token: CancellationToken,
+}
+
+// GET /ws: upgrade to a WebSocket that streams the rig state in the
+// [websocket] server's message shape: a full "radio_status" first, then a
+// "radio_status_delta" of the fields that changed each time the poll loop
+// sees the rig move, and a "wsjtx_status" for each WSJT-X Status.
+fn ws_upgrade(state: &Arc<CatState>, mut req: Request<hyper::body::Incoming>) -> HttpResponse {
+    let wants_websocket = req
+        .headers()
+        .get(UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let Some(key) = req
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .filter(|_| wants_websocket)
+    else {
+        return http_err_str(StatusCode::BAD_REQUEST, "/ws needs a WebSocket upgrade");
+    };
+    let Some(connection) = req.extensions().get::<Arc<ConnectionUpgrade>>().cloned() else {
+        return http_err_str(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "/ws outside a connection",
+        );
+    };
+    let accept = derive_accept_key(key.as_bytes());
+    // Subscribed before the snapshot is read, so no change falls between.
+    let updates = state.updates.subscribe();
+    let statuses = state.wsjtx.subscribe_status();
+    let upgrade = hyper::upgrade::on(&mut req);
+    let state = state.clone();
+    tokio::spawn(async move {
+        // Still counted against max_connections while the stream runs.
+        let _permit = connection.permit.lock().unwrap().take();
+        match upgrade.await {
+            Ok(upgraded) => {
+                let ws =
+                    WebSocketStream::from_raw_socket(TokioIo::new(upgraded), Role::Server, None)
+                        .await;
+                let feeds = WsFeeds { updates, statuses };
+                stream_rig_state(&state, ws, feeds, &connection.token).await;
+            }
+            Err(e) => debug!("CAT: /ws upgrade failed: {e}"),
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_ACCEPT, accept)
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+// The changes a /ws subscriber is sent.
+struct WsFeeds {
+    updates: broadcast::Receiver<Arc<RadioData>>,
+    statuses: broadcast::Receiver<WsjtxStatusUpdate>,
+}
+
+// Feed one /ws subscriber until it closes the socket, goes quiet for
+// connection_timeout (it is pinged at half that, so a live browser always
+// answers), or the server shuts down.  A rig that cannot be read yet gives
+// an empty snapshot; the first delta then carries every field.
+async fn stream_rig_state<S>(
+    state: &CatState,
+    mut ws: WebSocketStream<S>,
+    mut feeds: WsFeeds,
+    token: &CancellationToken,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let mut last = match rig_state(state).await {
+        Ok(data) => ws_state(state, &data),
+        Err(e) => {
+            debug!("CAT: no rig state for /ws snapshot: {e}");
+            serde_json::Map::new()
+        }
+    };
+    if ws
+        .send(ws_message("radio_status", last.clone()))
+        .await
+        .is_err()
+    {
+        return;
+    }
+    let mut ping = tokio::time::interval((state.connection_timeout / 2).max(MIN_WS_PING));
+    ping.tick().await;
+    let mut last_heard = Instant::now();
+    loop {
+        // Both senders live in CatState, so recv() only ever fails with
+        // Lagged; missed rig changes are covered by diffing against what
+        // this subscriber was last sent, missed Statuses by the next one.
+        let message = tokio::select! {
+            _ = token.cancelled() => break,
+            update = feeds.updates.recv() => match update {
+                Ok(data) => {
+                    let next = ws_state(state, &data);
+                    let delta = ws_delta(&last, &next);
+                    last = next;
+                    if delta.is_empty() {
+                        continue;
+                    }
+                    ws_message("radio_status_delta", delta)
+                }
+                Err(_) => continue,
+            },
+            status = feeds.statuses.recv() => match status.map(serde_json::to_value) {
+                Ok(Ok(serde_json::Value::Object(fields))) => ws_message("wsjtx_status", fields),
+                _ => continue,
+            },
+            incoming = ws.next() => match incoming {
+                // Pings are answered inside the stream; anything else the
+                // client sends only shows it is still there.
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {
+                    last_heard = Instant::now();
+                    continue;
+                }
+            },
+            _ = ping.tick() => {
+                let quiet = last_heard.elapsed();
+                if quiet >= state.connection_timeout {
+                    debug!("CAT: closing /ws after {quiet:?} without a reply");
+                    break;
+                }
+                Message::Ping(Bytes::new())
+            }
+        };
+        if ws.send(message).await.is_err() {
+            return;
+        }
+    }
+    let _ = ws.close(None).await;
+}
+
+// How a route's path is matched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathMatch {
+    Exact(&'static str),
+    // The path starts with this; the handler is given the rest.
+    Prefix(&'static str),
+    // Exactly two segments, '/<freq>/<mode>'.
+    FreqMode,
+}
+
+impl PathMatch {
+    // The part of `path` after the matched prefix, or None if no match.
+    fn tail<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match *self {
+            PathMatch::Exact(exact) => (path == exact).then_some(""),
+            PathMatch::Prefix(prefix) => path.strip_prefix(prefix),
+            PathMatch::FreqMode => {
+                (path.trim_start_matches('/').split('/').count() == 2).then_some(path)
+            }
+        }
+    }
+}
+
+// What a matched request is handed to; see handle().
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handler {
+    Qsy,
+    Sat,
+    Explain,
+    Qso,
+    Status,
+    Smeter,
+    Refresh,
+    BandNext,
+    BandPrev,
+    QsyLast,
+    Modes,
+    Bandplan,
+    PowerOn,
+    PowerOff,
+    PowerUp,
+    PowerDown,
+    WsjtxFreeText,
+    WsjtxReplay,
+    WsjtxConfiguration,
+    WsjtxLocation,
+    Ws,
+    Health,
+    WsjtxHealth,
+    Stats,
+    FlrigMethods,
+    Config,
+    Api,
+}
+
+// One CAT route.
+struct Route {
+    // None answers any method, as the /{freq}/{mode} QSY path always has.
+    method: Option<&'static str>,
+    matches: PathMatch,
+    handler: Handler,
+    // Whether it acts on the rig, the logbook or WSJT-X, and so is logged
+    // with the address of the client that sent it.
+    acts: bool,
+    // The path as shown by /api, and what the route does.
+    path: &'static str,
+    description: &'static str,
+}
+
+// Every CAT route, tried in order; /api lists them.  FreqMode comes last
+// since two-segment routes such as /band/next would otherwise match it.
+const ROUTES: &[Route] = &[
+    Route {
+        method: Some("POST"),
+        matches: PathMatch::Exact("/qso"),
+        handler: Handler::Qso,
+        acts: true,
+        path: "/qso",
+        description: "Log the ADIF record in the body to Wavelog",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/status"),
+        handler: Handler::Status,
+        acts: false,
+        path: "/status",
+        description: "Frequency, band, mode and power",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/smeter"),
+        handler: Handler::Smeter,
+        acts: false,
+        path: "/smeter",
+        description: "The current S-meter reading",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/refresh"),
+        handler: Handler::Refresh,
+        acts: true,
+        path: "/refresh",
+        description: "Read the rig and upload to Wavelog now",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/config"),
+        handler: Handler::Config,
+        acts: false,
+        path: "/config",
+        description: "The effective configuration, secrets redacted",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/bandplan"),
+        handler: Handler::Bandplan,
+        acts: false,
+        path: "/bandplan",
+        description: "The band plan and mode mapping QSYs use",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/health"),
+        handler: Handler::Health,
+        acts: false,
+        path: "/health",
+        description: "Whether FLRig is reachable",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/health/wsjtx"),
+        handler: Handler::WsjtxHealth,
+        acts: false,
+        path: "/health/wsjtx",
+        description: "Whether WSJT-X datagrams are still arriving",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/stats"),
+        handler: Handler::Stats,
+        acts: false,
+        path: "/stats",
+        description: "Round-trip times of FLRig calls and Wavelog uploads",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/modes"),
+        handler: Handler::Modes,
+        acts: false,
+        path: "/modes",
+        description: "Mode names the rig supports",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/flrig/methods"),
+        handler: Handler::FlrigMethods,
+        acts: false,
+        path: "/flrig/methods",
+        description: "FLRig methods wlrigctl may call and whether FLRig has them",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/power/on"),
+        handler: Handler::PowerOn,
+        acts: true,
+        path: "/power/on",
+        description: "Switch the rig on with [flrig] power_on",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/power/off"),
+        handler: Handler::PowerOff,
+        acts: true,
+        path: "/power/off",
+        description: "Switch the rig off with [flrig] power_off",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/power/up"),
+        handler: Handler::PowerUp,
+        acts: true,
+        path: "/power/up",
+        description: "Raise the output power by [CAT] power_step",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/power/down"),
+        handler: Handler::PowerDown,
+        acts: true,
+        path: "/power/down",
+        description: "Lower the output power by [CAT] power_step",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/band/next"),
+        handler: Handler::BandNext,
+        acts: true,
+        path: "/band/next",
+        description: "Tune to the next band's home frequency",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/band/prev"),
+        handler: Handler::BandPrev,
+        acts: true,
+        path: "/band/prev",
+        description: "Tune to the previous band's home frequency",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/wsjtx/freetext"),
+        handler: Handler::WsjtxFreeText,
+        acts: true,
+        path: "/wsjtx/freetext",
+        description: "Set WSJT-X's free text: ?text=...&send=true",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/wsjtx/replay"),
+        handler: Handler::WsjtxReplay,
+        acts: true,
+        path: "/wsjtx/replay",
+        description: "Ask WSJT-X to send its recent decodes again",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/wsjtx/configuration"),
+        handler: Handler::WsjtxConfiguration,
+        acts: true,
+        path: "/wsjtx/configuration",
+        description: "Switch WSJT-X to a named configuration: ?name=...",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/wsjtx/location"),
+        handler: Handler::WsjtxLocation,
+        acts: true,
+        path: "/wsjtx/location",
+        description: "Set the station's grid in WSJT-X: ?grid=IO91wm",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/qsy/last"),
+        handler: Handler::QsyLast,
+        acts: false,
+        path: "/qsy/last",
+        description: "Outcome of the last respond_early QSY",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/ws"),
+        handler: Handler::Ws,
+        acts: false,
+        path: "/ws",
+        description: "WebSocket stream of the rig state and WSJT-X Status",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Exact("/api"),
+        handler: Handler::Api,
+        acts: false,
+        path: "/api",
+        description: "This list",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Prefix("/explain/"),
+        handler: Handler::Explain,
+        acts: false,
+        path: "/explain/{freq}/{mode}",
+        description: "The FLRig mode a QSY would choose, and why, without tuning",
+    },
+    Route {
+        method: Some("GET"),
+        matches: PathMatch::Prefix("/sat/"),
+        handler: Handler::Sat,
+        acts: true,
+        path: "/sat/{rx_hz}/{rx_mode}/{tx_hz}/{tx_mode}",
+        description: "Satellite: RX/downlink on VFO A, TX/uplink on VFO B; split is left alone",
+    },
+    Route {
+        method: None,
+        matches: PathMatch::FreqMode,
+        handler: Handler::Qsy,
+        acts: true,
+        path: "/{freq}/{mode}",
+        description: "QSY: tune to freq (Hz) in a Wavelog mode; ?force_mode=true re-sends the mode",
+    },
+];
+
+// Why find_route has no route to call.
+#[derive(Debug, PartialEq)]
+enum NoRoute {
+    // No route has the path: 404.
+    NotFound,
+    // The path's route only takes this method: 405.
+    WrongMethod(&'static str),
+}
+
+// The route for a request and the path tail its handler is given.
+fn find_route<'a>(method: &Method, path: &'a str) -> Result<(&'static Route, &'a str), NoRoute> {
+    // The first route with the path owns it, so a PUT to /power/on is not
+    // taken for a QSY to frequency "power".
+    let (route, tail) = ROUTES
+        .iter()
+        .find_map(|route| route.matches.tail(path).map(|tail| (route, tail)))
+        .ok_or(NoRoute::NotFound)?;
+    match route.method {
+        Some(allowed) if allowed != method.as_str() => Err(NoRoute::WrongMethod(allowed)),
+        _ => Ok((route, tail)),
+    }
+}
+
+// Whether a request acts on the rig, the logbook or WSJT-X, and so is
+// logged with the address of the client that sent it.  A path no route
+// knows acts on nothing; it gets a 404.
+fn is_action(method: &Method, path: &str) -> bool {
+    find_route(method, path).is_ok_and(|(route, _)| route.acts)
+}
+
+// GET /api: the route table, for web UI developers.
+fn api() -> HttpResponse {
+    let routes: Vec<serde_json::Value> = ROUTES
+        .iter()
+        .map(|route| {
+            json!({
+                "method": route.method.unwrap_or("ANY"),
+                "path": route.path,
+                "description": route.description,
+            })
+        })
+        .collect();
+    json_ok(json!({ "status": "ok", "routes": routes }))
+}
+
+// Run the handler a request was routed to.  `tail` is what is left of the
+// path after a Prefix match.
+async fn handle(
+    state: &Arc<CatState>,
+    handler: Handler,
+    tail: &str,
+    req: Request<hyper::body::Incoming>,
+) -> HttpResponse {
+    match handler {
+        Handler::Qso => log_qso(state, req).await,
+        Handler::Status => status(state).await,
+        Handler::Smeter => smeter(state).await,
+        Handler::Refresh => refresh(state).await,
+        Handler::Config => config(state),
+        Handler::Bandplan => bandplan(state),
+        Handler::Health => health(state).await,
+        Handler::WsjtxHealth => wsjtx_health(state),
+        Handler::Stats => stats(state),
+        Handler::Modes => modes(state).await,
+        Handler::FlrigMethods => flrig_methods(state).await,
+        Handler::PowerOn | Handler::PowerOff => {
+            let response = power(state, handler == Handler::PowerOn).await;
+            state.invalidate_radio_cache();
+            response
+        }
+        Handler::PowerUp | Handler::PowerDown => {
+            let response = power_step(state, handler == Handler::PowerUp).await;
+            state.invalidate_radio_cache();
+            response
+        }
+        Handler::BandNext | Handler::BandPrev => {
+            let response = band_step(state, handler == Handler::BandNext).await;
+            state.invalidate_radio_cache();
+            response
+        }
+        Handler::WsjtxFreeText => wsjtx_free_text(state, &req).await,
+        Handler::WsjtxReplay => wsjtx_replay(state).await,
+        Handler::WsjtxConfiguration => wsjtx_configuration(state, &req).await,
+        Handler::WsjtxLocation => wsjtx_location(state, &req).await,
+        Handler::QsyLast => last_qsy(state),
+        Handler::Ws => ws_upgrade(state, req),
+        Handler::Api => api(),
+        Handler::Explain => explain(state, tail),
+        Handler::Sat => {
+            let response = sat(state, tail).await;
+            state.invalidate_radio_cache();
+            response
+        }
+        Handler::Qsy if state.respond_early => qsy_early(state, req),
+        Handler::Qsy => {
+            let response = qsy(state, req).await;
+            state.invalidate_radio_cache();
+            response
+        }
+    }
+}
+
+// Dispatch an incoming CAT request from `peer`.  The [CAT] allow list, and
+// the Origin check when wavelog_origin is set, apply to every route, read-only
+// ones included; only routes that act are logged with the peer's address.
+async fn route(
+    state: Arc<CatState>,
+    req: Request<hyper::body::Incoming>,
+    peer: SocketAddr,
+) -> Result<HttpResponse, Infallible> {
+    if !state.allow.permits(peer.ip()) {
+        warn!(
+            "CAT: refusing {} from {peer}, not in [CAT] allow",
+            req.uri()
+        );
+        return Ok(http_err_str(StatusCode::FORBIDDEN, "Forbidden"));
+    }
+
+    if let Some(expected) = &state.wavelog_origin {
+        if !check_origin(&req, expected) {
+            debug!(
+                "CAT: Origin header from {peer} missing or does not match configured wavelog_origin"
+            );
+            return Ok(http_err_str(StatusCode::FORBIDDEN, "Forbidden"));
+        }
+    }
+
+    if is_action(req.method(), req.uri().path()) {
+        info!("CAT: {} {} from {peer}", req.method(), req.uri());
+    }
+
+    let path = req.uri().path().to_string();
+    let (route, tail) = match find_route(req.method(), &path) {
+        Ok(found) => found,
+        Err(NoRoute::NotFound) => {
+            debug!("CAT: no route for {} {path}", req.method());
+            return Ok(http_err_str(StatusCode::NOT_FOUND, "Not found"));
+        }
+        Err(NoRoute::WrongMethod(allowed)) => {
+            debug!("CAT: {path} does not take {}", req.method());
+            let mut response = http_err_str(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+            response
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static(allowed));
+            return Ok(response);
+        }
+    };
+    Ok(handle(&state, route.handler, tail, req).await)
+}
+
+fn build_state(
+    settings: CatSettings,
+    wavelog_settings: WavelogSettings,
+    handles: CatHandles,
+    config: serde_json::Value,
+) -> Result<CatState, AllowListError> {
+    Ok(CatState {
+        rig: handles.rig,
+        allow: AllowList::parse(settings.allow.as_deref())?,
+        live: handles.live,
+        wavelog_origin: settings.wavelog_origin,
+        wavelog: wavelog_settings,
+        client: reqwest::Client::new(),
+        config,
+        wsjtx: handles.wsjtx,
+        refresh: handles.refresh,
+        radio_cache: handles.radio_cache,
+        updates: handles.updates,
+        latency: handles.latency,
+        modes_cache: Mutex::new(None),
+        cache_ttl: Duration::from_millis(settings.cache_ms.unwrap_or(DEFAULT_CACHE_MS)),
+        health_failures: settings
+            .health_failures
+            .unwrap_or(DEFAULT_HEALTH_FAILURES)
+            .max(1),
+        wsjtx_health: Duration::from_secs(
+            settings
+                .wsjtx_health_secs
+                .unwrap_or(DEFAULT_WSJTX_HEALTH_SECS),
+        ),
+        connection_timeout: Duration::from_secs(
+            settings
+                .connection_timeout_secs
+                .unwrap_or(DEFAULT_CONNECTION_TIMEOUT_SECS),
+        ),
+        connections: Arc::new(Semaphore::new(
+            settings
+                .max_connections
+                .unwrap_or(DEFAULT_MAX_CONNECTIONS)
+                .max(1),
+        )),
+        max_body_bytes: settings.max_body_bytes.unwrap_or(DEFAULT_MAX_BODY_BYTES),
+        body_timeout: Duration::from_millis(
+            settings.body_timeout_ms.unwrap_or(DEFAULT_BODY_TIMEOUT_MS),
+        ),
+        band_homes: parse_band_homes(settings.band_homes.as_ref()),
+        band_wrap: settings.band_wrap.unwrap_or(true),
+        respond_early: settings.respond_early.unwrap_or(false),
+        freq_step_hz: settings.freq_step_hz.unwrap_or(0),
+        power_step: parse_power_step(settings.power_step.as_deref()),
+        mode_aliases: ModeAliases::new(settings.mode_aliases.as_ref()),
+        last_qsy: Mutex::new(None),
+        qsy_count: AtomicU64::new(0),
+    })
+}
+
+#[allow(non_snake_case)]
+pub async fn CAT_thread(
+    settings: CatSettings,
+    wavelog_settings: WavelogSettings,
+    handles: CatHandles,
+    config: serde_json::Value,
+    token: CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Listen on TCP socket for someone in Cloudlog/Wavelog clicking the bandmap
+    let addr = settings.bind_addr();
+    if let Some(warning) = exposure_warning(&settings) {
+        warn!("CAT: {warning}");
+    }
+    let tls = cat_tls_acceptor(&settings)?;
+    let state = Arc::new(build_state(settings, wavelog_settings, handles, config)?);
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    info!("Listening for CAT requests from Wavelog on: {scheme}://{addr}");
+
+    let listener = TcpListener::bind(addr).await?;
+    serve(listener, state, tls, token).await
+}
+
+// What is off about [CAT] allow for the address the server binds: on a
+// network interface with no list anyone who can reach it may QSY the rig, and
+// on loopback a list can only tell local processes apart.
+fn exposure_warning(settings: &CatSettings) -> Option<&'static str> {
+    let listed = settings
+        .allow
+        .as_ref()
+        .is_some_and(|allow| !allow.is_empty());
+    match (settings.bind_addr().ip().is_loopback(), listed) {
+        (false, false) => {
+            Some("no [CAT] allow list, so any client that can reach [CAT] host may QSY")
+        }
+        (true, true) => {
+            Some("[CAT] allow is set but [CAT] host is loopback, so no remote client can connect")
+        }
+        _ => None,
+    }
+}
+
+// The TLS acceptor for [CAT] tls_cert/tls_key, or None for plain HTTP.  A
+// half-configured or unreadable certificate is an error rather than a quiet
+// fall back to plain HTTP.
+fn cat_tls_acceptor(
+    settings: &CatSettings,
+) -> Result<Option<TlsAcceptor>, Box<dyn std::error::Error + Send + Sync>> {
+    match (settings.tls_cert.as_deref(), settings.tls_key.as_deref()) {
+        (None, None) => Ok(None),
+        (Some(cert), Some(key)) => {
+            let acceptor = load_tls_acceptor(cert, key)
+                .map_err(|e| format!("CAT TLS: failed to load {cert} / {key}: {e}"))?;
+            info!("CAT TLS: loaded cert from {cert}");
+            Ok(Some(acceptor))
+        }
+        _ => Err("[CAT] tls_cert and tls_key must both be set or both absent".into()),
+    }
+}
+
+// Accept loop, split from CAT_thread so tests can serve on an ephemeral port.
+async fn serve(
+    listener: TcpListener,
+    state: Arc<CatState>,
+    tls: Option<TlsAcceptor>,
+    token: CancellationToken,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    loop {
+        // accept a series of TCP connections arising from clicks on bandmap in Cloudlog/Wavelog
+        let (stream, peer) = tokio::select! {
+            _ = token.cancelled() => {
+                info!("CAT thread shutting down");
+                return Ok(());
+            }
+            result = listener.accept() => result?,
+        };
+        let Ok(permit) = state.connections.clone().try_acquire_owned() else {
+            warn!("CAT: too many open connections; dropping one from {peer}");
+            continue;
+        };
+        let state_for_conn = state.clone();
+        let tls = tls.clone();
+        let timeout = state.connection_timeout;
+        // Dropped with the connection, unless a /ws stream takes the permit.
+        let connection = Arc::new(ConnectionUpgrade {
+            permit: Mutex::new(Some(permit)),
+            token: token.clone(),
+        });
+        tokio::task::spawn(async move {
+            // The handshake happens here, not in the accept loop, so a slow
+            // or broken client cannot hold up other connections.
+            match tls {
+                None => {
+                    serve_connection(TokioIo::new(stream), state_for_conn, peer, connection).await
+                }
+                Some(acceptor) => {
+                    match tokio::time::timeout(timeout, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let io = TokioIo::new(stream);
+                            serve_connection(io, state_for_conn, peer, connection).await
+                        }
+                        Ok(Err(e)) => debug!("CAT TLS handshake failed: {e}"),
+                        Err(_) => debug!("CAT: no TLS handshake from {peer} within {timeout:?}"),
+                    }
+                }
+            }
+        });
+    }
+}
+
+// The requests a connection is answering, and when the last one started or
+// finished, for its idle timer.
+struct Activity {
+    in_flight: usize,
+    since: Instant,
+}
+
+impl Activity {
+    fn begin(&mut self) {
+        self.in_flight += 1;
+        self.since = Instant::now();
+    }
+
+    fn end(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.since = Instant::now();
+    }
+
+    // When to look again: once `idle` has passed with nothing in flight.
+    fn check_at(&self, idle: Duration) -> Instant {
+        if self.in_flight > 0 {
+            Instant::now() + idle
+        } else {
+            self.since + idle
+        }
+    }
+
+    fn idle_for(&self, idle: Duration) -> bool {
+        self.in_flight == 0 && self.since.elapsed() >= idle
+    }
+}
+
+// Serve one connection until the client closes it.  Headers must arrive
+// within connection_timeout of the connection opening or the previous
+// response, and a keep-alive connection with no request in progress for
+// that long is closed; a busy one is never cut off mid-request.
+async fn serve_connection<I>(
+    io: I,
+    state: Arc<CatState>,
+    peer: SocketAddr,
+    connection: Arc<ConnectionUpgrade>,
+) where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let timeout = state.connection_timeout;
+    let activity = Arc::new(Mutex::new(Activity {
+        in_flight: 0,
+        since: Instant::now(),
+    }));
+    let busy = activity.clone();
+    let service = service_fn(move |mut req: Request<hyper::body::Incoming>| {
+        req.extensions_mut().insert(connection.clone());
+        let (state, busy) = (state.clone(), busy.clone());
+        async move {
+            busy.lock().unwrap().begin();
+            let response = route(state, req, peer).await;
+            busy.lock().unwrap().end();
+            response
+        }
+    });
+    let conn = http1::Builder::new()
+        .timer(TokioTimer::new())
+        .header_read_timeout(timeout)
+        .half_close(true)
+        .serve_connection(io, service)
+        .with_upgrades();
+    tokio::pin!(conn);
+    let mut closing = false;
+    let result = loop {
+        let check_at = activity.lock().unwrap().check_at(timeout);
+        tokio::select! {
+            result = conn.as_mut() => break result,
+            _ = tokio::time::sleep_until(check_at), if !closing => {
+                if activity.lock().unwrap().idle_for(timeout) {
+                    debug!("CAT: closing connection from {peer} after {timeout:?} idle");
+                    conn.as_mut().graceful_shutdown();
+                    closing = true;
+                }
+            }
+        }
+    };
+    if let Err(err) = result {
+        // This seems to happen if wavelog doesn't wait for the response to their second
+        // attempt(!) to qsy, and drop the TCP connection early
+        debug!("Error serving connection: {:?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_wavelog::mock_wavelog;
+    use crate::settings::LiveSettings;
+    use crate::wavelog::RigPublishers;
+    use std::sync::RwLock;
+
+    // The mode mapping without band memory, which is what most tests need.
+    fn wavelog_to_flrig_mode(
+        freq: f64,
+        mode: WavelogMode,
+        ft8_freqs: &[f64],
+        mode_map: &ModeMap,
+    ) -> Mode {
+        explain_flrig_mode(freq, mode, ft8_freqs, mode_map, None).0
+    }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for FT8 frequency identification
+    //////////////////////////////////////////////////////////////
+    // This file assumes the following centres of activity for FT8
+    // Furthermore, the is_ft8() function checks for:
+    //     * >= centre - 2kHz   (inclusive, below the dial)
+    //     * <  centre + 3kHz   (exclusive, above the dial)
+    //
+    // Therefore we check either side of these boundaries
+    //
+    // Each band gets four boundary checks:
+    //   - centre (must be in-window)
+    //   - lower edge: centre - LO_ALLOWANCE (must be in-window)
+    //   - just below lower edge (must NOT be in-window)
+    //   - upper edge: centre + HI_ALLOWANCE (must NOT be in-window, it's exclusive)
+    //
+    // 160m: 1.840 MHz
+    // 80m:  3.575 MHz
+    // 40m:  7.074 MHz
+    // 30m:  10.136 MHz
+    // 20m:  14.074 MHz
+    // 17m:  18.100 MHz
+    // 15m:  21.074 MHz
+    // 12m:  24.915 MHz
+    // 10m:  28.074 MHz
+    // 6m:   50.313 MHz
+
+    // --- 160m ---
+    #[test]
+    fn ft8_160m() {
+        assert!(is_ft8(1_840_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_160m_lower_edge() {
+        assert!(is_ft8(1_838_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_160m_below() {
+        assert!(!is_ft8(1_837_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_160m_above() {
+        assert!(!is_ft8(1_843_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 80m ---
+    #[test]
+    fn ft8_80m() {
+        assert!(is_ft8(3_575_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_80m_lower_edge() {
+        assert!(is_ft8(3_573_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_80m_below() {
+        assert!(!is_ft8(3_572_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_80m_above() {
+        assert!(!is_ft8(3_578_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 40m ---
+    #[test]
+    fn ft8_40m() {
+        assert!(is_ft8(7_074_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_40m_below() {
+        assert!(!is_ft8(7_071_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_40m_lower() {
+        assert!(is_ft8(7_072_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_40m_upper() {
+        assert!(is_ft8(7_076_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_40m_above() {
+        assert!(!is_ft8(7_077_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 30m ---
+    #[test]
+    fn ft8_30m() {
+        assert!(is_ft8(10_136_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_30m_lower_edge() {
+        assert!(is_ft8(10_134_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_30m_below() {
+        assert!(!is_ft8(10_133_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_30m_above() {
+        assert!(!is_ft8(10_139_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 20m ---
+    #[test]
+    fn ft8_20m() {
+        assert!(is_ft8(14_074_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_20m_lower_edge() {
+        assert!(is_ft8(14_072_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_20m_below() {
+        assert!(!is_ft8(14_071_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_20m_above() {
+        assert!(!is_ft8(14_077_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 17m ---
+    #[test]
+    fn ft8_17m() {
+        assert!(is_ft8(18_100_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_17m_lower_edge() {
+        assert!(is_ft8(18_098_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_17m_below() {
+        assert!(!is_ft8(18_097_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_17m_above() {
+        assert!(!is_ft8(18_103_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 15m ---
+    #[test]
+    fn ft8_15m() {
+        assert!(is_ft8(21_074_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_15m_lower_edge() {
+        assert!(is_ft8(21_072_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_15m_below() {
+        assert!(!is_ft8(21_071_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_15m_above() {
+        assert!(!is_ft8(21_077_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 12m ---
+    #[test]
+    fn ft8_12m() {
+        assert!(is_ft8(24_915_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_12m_lower_edge() {
+        assert!(is_ft8(24_913_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_12m_below() {
+        assert!(!is_ft8(24_912_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_12m_above() {
+        assert!(!is_ft8(24_918_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 10m ---
+    #[test]
+    fn ft8_10m() {
+        assert!(is_ft8(28_074_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_10m_lower_edge() {
+        assert!(is_ft8(28_072_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_10m_below() {
+        assert!(!is_ft8(28_071_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_10m_above() {
+        assert!(!is_ft8(28_077_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // --- 6m ---
+    #[test]
+    fn ft8_6m() {
+        assert!(is_ft8(50_313_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_6m_lower_edge() {
+        assert!(is_ft8(50_311_000.0, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_6m_below() {
+        assert!(!is_ft8(50_310_999.999_9, &DEFAULT_FT8_FREQS));
+    }
+    #[test]
+    fn ft8_6m_above() {
+        assert!(!is_ft8(50_316_000.0, &DEFAULT_FT8_FREQS));
+    }
+
+    // Helpers: construct a ModeMap for testing without config or a live FLRig.
+    fn icom_mode_map() -> ModeMap {
+        flrig::build_mode_map(None, None, None)
+    }
+    fn yaesu_mode_map() -> ModeMap {
+        flrig::build_mode_map(Some("CW-U"), Some("RTTY-U"), Some("DATA-U"))
+    }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for Bandlist/Cluster mode/frequency conversions (ICOM/generic map)
+    // FT8 detection only overrides Digi and Rtty; other modes pass through.
+    //////////////////////////////////////////////////////////////
+
+    #[test]
+    fn flrig_40m_ft8_digi_rtty_become_d_usb() {
+        const FT8_40M: f64 = 7_074_000.0;
+        let m = icom_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+            Mode::D_USB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+            Mode::D_USB
+        );
+    }
+
+    #[test]
+    fn flrig_40m_ft8_other_modes_unaffected() {
+        const FT8_40M: f64 = 7_074_000.0;
+        let m = icom_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
+            Mode::CW
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
+            Mode::USB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
+            Mode::AM
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
+            Mode::FM
+        );
+    }
+
+    #[test]
+    fn flrig_40m_cw() {
+        let m = icom_mode_map();
+        const BAND_40M: [f64; 5] = [
+            7_000_000.0,
+            7_030_000.0,
+            7_100_000.0,
+            7_185_000.0,
+            7_200_000.0,
+        ];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
+                Mode::CW
+            );
+        }
+    }
+
+    #[test]
+    fn flrig_40m_phone() {
+        let m = icom_mode_map();
+        const BAND_40M: [f64; 5] = [
+            7_000_000.0,
+            7_030_000.0,
+            7_100_000.0,
+            7_185_000.0,
+            7_200_000.0,
+        ];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+                Mode::LSB
+            );
+        }
+    }
+
+    #[test]
+    fn explicit_sideband_is_authoritative_on_every_ft8_frequency() {
+        let m = icom_mode_map();
+        for freq in DEFAULT_FT8_FREQS {
+            for (requested, expected) in
+                [(WavelogMode::LSB, Mode::LSB), (WavelogMode::USB, Mode::USB)]
+            {
+                assert_eq!(
+                    explain_flrig_mode(freq, requested, &DEFAULT_FT8_FREQS, &m, Some(Mode::AM)),
+                    (expected, ModeReason::Explicit),
+                    "{requested:?} at {freq}"
+                );
+            }
+            // The generic digital mode there still gets the data mode.
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+                Mode::D_USB
+            );
+        }
+    }
+
+    #[test]
+    fn flrig_40m_lsb() {
+        let m = icom_mode_map();
+        const BAND_40M: [f64; 5] = [
+            7_000_000.0,
+            7_030_000.0,
+            7_100_000.0,
+            7_185_000.0,
+            7_200_000.0,
+        ];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
+                Mode::LSB
+            );
+        }
+    }
+
+    #[test]
+    fn flrig_40m_usb() {
+        let m = icom_mode_map();
+        const BAND_40M: [f64; 5] = [
+            7_000_000.0,
+            7_030_000.0,
+            7_100_000.0,
+            7_185_000.0,
+            7_200_000.0,
+        ];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
+                Mode::USB
+            );
+        }
+    }
+
+    #[test]
+    fn flrig_40m_digi_rtty() {
+        let m = icom_mode_map();
+        const BAND_40M: [f64; 5] = [
+            7_000_000.0,
+            7_030_000.0,
+            7_100_000.0,
+            7_185_000.0,
+            7_200_000.0,
+        ];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+                Mode::RTTY
+            );
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+                Mode::RTTY
+            );
+        }
+    }
+
+    #[test]
+    fn digi_outside_ft8_uses_default_digi_mode() {
+        let m = icom_mode_map().with_digi_fallback(Some("D-USB"));
+        assert_eq!(
+            wavelog_to_flrig_mode(7_040_000.0, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+            Mode::D_USB
+        );
+        // An explicit RTTY click is still RTTY.
+        assert_eq!(
+            wavelog_to_flrig_mode(7_040_000.0, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+            Mode::RTTY
+        );
+    }
+
+    #[test]
+    fn flrig_am_fm() {
+        let m = icom_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(7_200_000.0, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
+            Mode::AM
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(29_600_000.0, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
+            Mode::FM
+        );
+    }
+
+    #[test]
+    fn flrig_20m_phone_becomes_usb() {
+        let m = icom_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+            Mode::USB
+        );
+    }
+
+    // Exact whole-Hz edges of the window, as a bandmap would send them:
+    // 2000 Hz below is in, 2001 Hz below is out, 2999 Hz above is in and
+    // 3000 Hz above is out.
+    #[test]
+    fn ft8_window_whole_hz_boundaries() {
+        for centre in [7_074_000.0, 14_074_000.0, 50_313_000.0] {
+            assert!(
+                is_ft8(centre - 2_000.0, &DEFAULT_FT8_FREQS),
+                "{centre} - 2000"
+            );
+            assert!(
+                !is_ft8(centre - 2_001.0, &DEFAULT_FT8_FREQS),
+                "{centre} - 2001"
+            );
+            assert!(
+                is_ft8(centre + 2_999.0, &DEFAULT_FT8_FREQS),
+                "{centre} + 2999"
+            );
+            assert!(
+                !is_ft8(centre + 3_000.0, &DEFAULT_FT8_FREQS),
+                "{centre} + 3000"
+            );
+        }
+    }
+
+    #[test]
+    fn is_ft8_custom_freqs() {
+        let custom: [f64; 1] = [14_074_000.0];
+        assert!(is_ft8(14_074_000.0, &custom));
+        assert!(!is_ft8(7_074_000.0, &custom));
+    }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for Yaesu mode map (CW→CW_U, Digi/Rtty→DATA_U or RTTY_U)
+    //////////////////////////////////////////////////////////////
+
+    #[test]
+    fn yaesu_ft8_digi_rtty_become_data_u() {
+        const FT8_40M: f64 = 7_074_000.0;
+        let m = yaesu_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+            Mode::DATA_U
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+            Mode::DATA_U
+        );
+    }
+
+    #[test]
+    fn yaesu_ft8_other_modes_unaffected() {
+        const FT8_40M: f64 = 7_074_000.0;
+        let m = yaesu_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
+            Mode::CW_U
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
+            Mode::USB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
+            Mode::AM
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
+            Mode::FM
+        );
     }
 
-    // --- 80m ---
     #[test]
-    fn ft8_80m() {
-        assert!(is_ft8(3_575_000.0, &DEFAULT_FT8_FREQS));
+    fn yaesu_40m_cw_becomes_cw_u() {
+        let m = yaesu_mode_map();
+        const BAND_40M: [f64; 3] = [7_000_000.0, 7_030_000.0, 7_200_000.0];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
+                Mode::CW_U
+            );
+        }
+    }
+
+    #[test]
+    fn yaesu_lower_sideband_preference_for_cw_and_rtty() {
+        let m = flrig::build_mode_map(Some("CW-L"), Some("RTTY-L"), Some("DATA-U"));
+        assert_eq!(
+            wavelog_to_flrig_mode(7_030_000.0, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
+            Mode::CW_L
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(7_040_000.0, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+            Mode::RTTY_L
+        );
+        // FT8 windows still use the data mode whatever the RTTY preference.
+        assert_eq!(
+            wavelog_to_flrig_mode(7_074_000.0, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+            Mode::DATA_U
+        );
+    }
+
+    #[test]
+    fn phone_sideband_split_at_10mhz() {
+        let m = icom_mode_map();
+        let cases = [
+            (9_999_999.0, Mode::LSB, ModeReason::PhoneBelow10MHz),
+            (10_000_000.0, Mode::USB, ModeReason::PhoneAbove10MHz),
+            (10_000_001.0, Mode::USB, ModeReason::PhoneAbove10MHz),
+        ];
+        for (freq, mode, reason) in cases {
+            let (got_mode, got_reason) =
+                explain_flrig_mode(freq, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m, None);
+            assert_eq!(got_mode, mode, "mode at {freq} Hz");
+            assert_eq!(got_reason.as_str(), reason.as_str(), "reason at {freq} Hz");
+        }
+    }
+
+    #[test]
+    fn remembered_band_mode_overrides_phone_guess_only() {
+        let m = icom_mode_map();
+        let (mode, reason) = explain_flrig_mode(
+            7_150_000.0,
+            WavelogMode::Phone,
+            &DEFAULT_FT8_FREQS,
+            &m,
+            Some(Mode::USB),
+        );
+        assert_eq!(mode, Mode::USB);
+        assert_eq!(reason.as_str(), "remembered-band-mode");
+
+        // Explicit and digital requests ignore the memory.
+        let (mode, _) = explain_flrig_mode(
+            7_150_000.0,
+            WavelogMode::LSB,
+            &DEFAULT_FT8_FREQS,
+            &m,
+            Some(Mode::USB),
+        );
+        assert_eq!(mode, Mode::LSB);
+        let (mode, _) = explain_flrig_mode(
+            7_074_000.0,
+            WavelogMode::Digi,
+            &DEFAULT_FT8_FREQS,
+            &m,
+            Some(Mode::USB),
+        );
+        assert_eq!(mode, Mode::D_USB);
+    }
+
+    #[test]
+    fn phone_on_30m_is_usb() {
+        let m = icom_mode_map();
+        for freq in [10_100_000.0, 10_125_000.0, 10_150_000.0] {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+                Mode::USB
+            );
+        }
+    }
+
+    #[test]
+    fn yaesu_phone_lsb_usb_boundary() {
+        let m = yaesu_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(7_150_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
+            Mode::USB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(7_150_000.0, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
+            Mode::LSB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
+            Mode::USB
+        );
+    }
+
+    #[test]
+    fn yaesu_40m_digi_rtty_become_rtty_u() {
+        let m = yaesu_mode_map();
+        const BAND_40M: [f64; 3] = [7_000_000.0, 7_030_000.0, 7_200_000.0];
+        for freq in BAND_40M {
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+                Mode::RTTY_U
+            );
+            assert_eq!(
+                wavelog_to_flrig_mode(freq, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
+                Mode::RTTY_U
+            );
+        }
+    }
+
+    #[test]
+    fn yaesu_am_fm() {
+        let m = yaesu_mode_map();
+        assert_eq!(
+            wavelog_to_flrig_mode(7_200_000.0, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
+            Mode::AM
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(29_600_000.0, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
+            Mode::FM
+        );
+    }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for parse_qsy_path input validation
+    //////////////////////////////////////////////////////////////
+
+    fn make_get(path: &str) -> Request<()> {
+        Request::builder().uri(path).body(()).unwrap()
+    }
+
+    // --- Baseline: exact wire format Wavelog sends ---
+
+    #[test]
+    fn qsy_path_wavelog_wire_format_accepted() {
+        // Wavelog constructs the QSY URL as <cat_url>/<freq>/<mode>.
+        // This test documents and locks that format.  If parse_qsy_path ever
+        // stops accepting it, something has broken the Wavelog integration.
+        assert!(parse_qsy_path(&make_get("/14074000/usb"), &ModeAliases::default()).is_ok());
+        assert!(parse_qsy_path(&make_get("/7074000/digi"), &ModeAliases::default()).is_ok());
+        assert!(parse_qsy_path(&make_get("/3573000/cw"), &ModeAliases::default()).is_ok());
+    }
+
+    #[test]
+    fn wavelog_mode_specific_digital_modes_parse() {
+        for s in ["ft8", "ft4", "psk", "js8", "olivia"] {
+            let mode = s
+                .parse::<WavelogMode>()
+                .unwrap_or_else(|_| panic!("{s} rejected"));
+            assert_eq!(mode.as_str(), s);
+        }
+        assert!(parse_qsy_path(&make_get("/14080000/ft4"), &ModeAliases::default()).is_ok());
+    }
+
+    #[test]
+    fn qsy_mode_is_case_insensitive() {
+        let aliases = ModeAliases::default();
+        for path in ["/14250000/PHONE", "/14250000/Phone", "/14074000/FT8"] {
+            assert!(parse_qsy_path(&make_get(path), &aliases).is_ok(), "{path}");
+        }
+        assert_eq!("USB".parse::<WavelogMode>(), Ok(WavelogMode::USB));
+    }
+
+    #[test]
+    fn default_mode_aliases() {
+        let aliases = ModeAliases::default();
+        assert_eq!(aliases.resolve("SSB"), Some(WavelogMode::Phone));
+        assert_eq!(aliases.resolve("data"), Some(WavelogMode::Digi));
+        assert_eq!(aliases.resolve("PSK31"), Some(WavelogMode::Psk));
+        assert_eq!(aliases.resolve("sstv"), None);
+    }
+
+    #[test]
+    fn configured_mode_aliases() {
+        let aliases = ModeAliases::new(Some(&HashMap::from([
+            ("SSTV".to_string(), "usb".to_string()),
+            ("rtty".to_string(), "Digi".to_string()),
+            ("hell".to_string(), "feld".to_string()),
+        ])));
+        assert_eq!(aliases.resolve("sstv"), Some(WavelogMode::USB));
+        // A configured alias wins over the built-in name...
+        assert_eq!(aliases.resolve("RTTY"), Some(WavelogMode::Digi));
+        // ...and one with an unknown target is dropped.
+        assert_eq!(aliases.resolve("hell"), None);
+        assert_eq!(aliases.resolve("ssb"), Some(WavelogMode::Phone));
+        let qsy = parse_qsy_path(&make_get("/14230000/SSTV"), &aliases).unwrap();
+        assert_eq!(qsy.mode, WavelogMode::USB);
+    }
+
+    #[test]
+    fn specific_digital_modes_map_to_data_mode_off_ft8_frequencies() {
+        // 14.080 MHz is not an FT8 dial frequency, so Digi would give RTTY.
+        for mode in [
+            WavelogMode::Ft8,
+            WavelogMode::Ft4,
+            WavelogMode::Psk,
+            WavelogMode::Js8,
+            WavelogMode::Olivia,
+        ] {
+            assert_eq!(
+                wavelog_to_flrig_mode(14_080_000.0, mode, &DEFAULT_FT8_FREQS, &icom_mode_map()),
+                Mode::D_USB
+            );
+            assert_eq!(
+                wavelog_to_flrig_mode(14_080_000.0, mode, &DEFAULT_FT8_FREQS, &yaesu_mode_map()),
+                Mode::DATA_U
+            );
+        }
+    }
+
+    #[test]
+    fn without_data_mode_sends_usb_for_digital() {
+        for m in [icom_mode_map(), yaesu_mode_map()] {
+            let m = m.without_data_mode();
+            assert_eq!(
+                wavelog_to_flrig_mode(14_074_000.0, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
+                Mode::USB
+            );
+            assert_eq!(
+                wavelog_to_flrig_mode(14_080_000.0, WavelogMode::Ft4, &DEFAULT_FT8_FREQS, &m),
+                Mode::USB
+            );
+        }
+    }
+
+    #[test]
+    fn data_mode_used_by_default() {
+        assert_eq!(
+            wavelog_to_flrig_mode(
+                14_074_000.0,
+                WavelogMode::Digi,
+                &DEFAULT_FT8_FREQS,
+                &icom_mode_map()
+            ),
+            Mode::D_USB
+        );
+        assert_eq!(
+            wavelog_to_flrig_mode(
+                14_074_000.0,
+                WavelogMode::Digi,
+                &DEFAULT_FT8_FREQS,
+                &yaesu_mode_map()
+            ),
+            Mode::DATA_U
+        );
+    }
+
+    // --- Malformed paths ---
+
+    #[test]
+    fn qsy_path_single_segment_rejected() {
+        let req = Request::builder().uri("/14030000").body(()).unwrap();
+        assert!(parse_qsy_path(&req, &ModeAliases::default()).is_err());
+    }
+
+    #[test]
+    fn qsy_path_empty_rejected() {
+        let req = Request::builder().uri("/").body(()).unwrap();
+        assert!(parse_qsy_path(&req, &ModeAliases::default()).is_err());
+    }
+
+    #[test]
+    fn qsy_path_three_segments_rejected() {
+        assert!(parse_qsy_path(&make_get("/14030000/cw/extra"), &ModeAliases::default()).is_err());
+    }
+
+    // --- Frequency allowlist: out-of-band inputs rejected ---
+
+    #[test]
+    fn qsy_rejects_zero_frequency() {
+        assert!(
+            parse_qsy_path(&make_get("/0/usb"), &ModeAliases::default()).is_err(),
+            "frequency 0 Hz must be rejected"
+        );
+    }
+
+    #[test]
+    fn qsy_rejects_broadcast_band_frequency() {
+        // 909 kHz is an AM broadcast frequency, not an amateur allocation.
+        assert!(
+            parse_qsy_path(&make_get("/909000/usb"), &ModeAliases::default()).is_err(),
+            "broadcast-band frequency 909 kHz must be rejected"
+        );
+    }
+
+    #[test]
+    fn qsy_rejects_max_u32_frequency() {
+        // 4,294,967,295 Hz (~4.3 GHz) is not an amateur allocation.
+        assert!(
+            parse_qsy_path(&make_get("/4294967295/usb"), &ModeAliases::default()).is_err(),
+            "out-of-range frequency 4294967295 Hz must be rejected"
+        );
+    }
+
+    #[test]
+    fn qsy_rejects_between_bands() {
+        // 11 MHz falls between 30m (10.15 MHz) and 20m (14.0 MHz).
+        assert!(
+            parse_qsy_path(&make_get("/11000000/usb"), &ModeAliases::default()).is_err(),
+            "inter-band frequency 11 MHz must be rejected"
+        );
+    }
+
+    // --- Frequency allowlist: valid in-band inputs accepted ---
+
+    #[test]
+    fn qsy_accepts_valid_hf_frequencies() {
+        let valid = [
+            "/1840000/usb",  // 160m
+            "/3573000/usb",  // 80m FT8
+            "/7074000/usb",  // 40m FT8
+            "/10136000/usb", // 30m FT8
+            "/14074000/usb", // 20m FT8
+            "/18100000/usb", // 17m FT8
+            "/21074000/usb", // 15m FT8
+            "/24915000/usb", // 12m FT8
+            "/28074000/usb", // 10m FT8
+            "/50313000/usb", // 6m FT8
+        ];
+        for path in valid {
+            assert!(
+                parse_qsy_path(&make_get(path), &ModeAliases::default()).is_ok(),
+                "expected Ok for {path}"
+            );
+        }
+    }
+
+    // --- is_amateur_frequency: band edge boundary checks ---
+
+    #[test]
+    fn amateur_frequency_band_edges() {
+        // Lower and upper edges of each band must be accepted (inclusive).
+        for &(lo, hi, _) in AMATEUR_BANDS_HZ {
+            assert!(
+                is_amateur_frequency(lo),
+                "{lo} Hz (band lower edge) should be accepted"
+            );
+            assert!(
+                is_amateur_frequency(hi),
+                "{hi} Hz (band upper edge) should be accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn amateur_frequency_outside_all_bands_rejected() {
+        let out_of_band = [
+            0,             // zero
+            100_000,       // below 136 kHz band
+            500_000,       // 500 kHz (between 136 kHz and 160m)
+            472_000,       // 472 kHz (Full-only, excluded by policy)
+            5_000_000,     // 5 MHz (Full-only specialist channels, excluded by policy)
+            11_000_000,    // between 30m and 20m
+            200_000_000,   // between 70cm and anything above
+            4_294_967_295, // max u32
+        ];
+        for freq in out_of_band {
+            assert!(!is_amateur_frequency(freq), "{freq} Hz should be rejected");
+        }
     }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for band_name and the /status body
+    //////////////////////////////////////////////////////////////
+
     #[test]
-    fn ft8_80m_lower_edge() {
-        assert!(is_ft8(3_573_000.0, &DEFAULT_FT8_FREQS));
+    fn band_name_for_known_frequencies() {
+        assert_eq!(band_name(1_840_000), Some("160m"));
+        assert_eq!(band_name(7_074_000), Some("40m"));
+        assert_eq!(band_name(14_000_000), Some("20m")); // lower edge
+        assert_eq!(band_name(29_700_000), Some("10m")); // upper edge
+        assert_eq!(band_name(145_500_000), Some("2m"));
+        assert_eq!(band_name(432_200_000), Some("70cm"));
     }
+
     #[test]
-    fn ft8_80m_below() {
-        assert!(!is_ft8(3_572_999.9999, &DEFAULT_FT8_FREQS));
+    fn band_name_out_of_band_is_none() {
+        assert_eq!(band_name(11_000_000), None);
+        assert_eq!(band_name(909_000), None);
     }
-    #[test]
-    fn ft8_80m_above() {
-        assert!(!is_ft8(3_578_000.0, &DEFAULT_FT8_FREQS));
+
+    //////////////////////////////////////////////////////////////
+    // Tests for band_homes and /band/next, /band/prev
+    //////////////////////////////////////////////////////////////
+
+    fn homes() -> Vec<(u32, u32)> {
+        parse_band_homes(Some(&HashMap::from([
+            ("20m".to_string(), 14_074_000),
+            ("80m".to_string(), 3_573_000),
+            ("40m".to_string(), 7_074_000),
+        ])))
     }
 
-    // --- 40m ---
     #[test]
-    fn ft8_40m() {
-        assert!(is_ft8(7_074_000.0, &DEFAULT_FT8_FREQS));
+    fn band_homes_in_band_order() {
+        assert_eq!(
+            homes(),
+            vec![
+                (3_500_000, 3_573_000),
+                (7_000_000, 7_074_000),
+                (14_000_000, 14_074_000)
+            ]
+        );
     }
+
     #[test]
-    fn ft8_40m_below() {
-        assert!(!is_ft8(7_071_999.9999, &DEFAULT_FT8_FREQS));
+    fn band_homes_skip_unknown_and_out_of_band() {
+        let homes = parse_band_homes(Some(&HashMap::from([
+            ("11m".to_string(), 27_555_000),
+            ("40m".to_string(), 14_074_000),
+            ("20m".to_string(), 14_074_000),
+        ])));
+        assert_eq!(homes, vec![(14_000_000, 14_074_000)]);
+        assert!(parse_band_homes(None).is_empty());
     }
+
     #[test]
-    fn ft8_40m_lower() {
-        assert!(is_ft8(7_072_000.0, &DEFAULT_FT8_FREQS));
+    fn step_band_moves_between_listed_bands() {
+        let homes = homes();
+        assert_eq!(step_band(&homes, 7_030_000, true, true), Some(14_074_000));
+        assert_eq!(step_band(&homes, 7_030_000, false, true), Some(3_573_000));
+        // 30m is not listed, so it is stepped over.
+        assert_eq!(step_band(&homes, 10_136_000, true, true), Some(14_074_000));
+        assert_eq!(step_band(&homes, 10_136_000, false, true), Some(7_074_000));
     }
+
     #[test]
-    fn ft8_40m_upper() {
-        assert!(is_ft8(7_076_999.9999, &DEFAULT_FT8_FREQS));
+    fn step_band_wraps_only_when_asked() {
+        let homes = homes();
+        assert_eq!(step_band(&homes, 14_200_000, true, true), Some(3_573_000));
+        assert_eq!(step_band(&homes, 3_700_000, false, true), Some(14_074_000));
+        assert_eq!(step_band(&homes, 14_200_000, true, false), None);
+        assert_eq!(step_band(&homes, 3_700_000, false, false), None);
     }
+
     #[test]
-    fn ft8_40m_above() {
-        assert!(!is_ft8(7_077_000.0, &DEFAULT_FT8_FREQS));
+    fn step_band_from_between_bands() {
+        let homes = homes();
+        assert_eq!(step_band(&homes, 11_000_000, true, false), Some(14_074_000));
+        assert_eq!(step_band(&homes, 11_000_000, false, false), Some(7_074_000));
     }
 
-    // --- 30m ---
     #[test]
-    fn ft8_30m() {
-        assert!(is_ft8(10_136_000.0, &DEFAULT_FT8_FREQS));
+    fn step_band_single_band_returns_home() {
+        let homes = vec![(7_000_000, 7_074_000)];
+        assert_eq!(step_band(&homes, 7_150_000, true, true), Some(7_074_000));
+        assert_eq!(step_band(&homes, 7_150_000, true, false), None);
     }
+
     #[test]
-    fn ft8_30m_lower_edge() {
-        assert!(is_ft8(10_134_000.0, &DEFAULT_FT8_FREQS));
+    fn power_step_parses_watts_and_percent() {
+        assert_eq!(PowerStep::parse("5"), Some(PowerStep::Watts(5)));
+        assert_eq!(PowerStep::parse("5W"), Some(PowerStep::Watts(5)));
+        assert_eq!(PowerStep::parse(" 10 % "), Some(PowerStep::Percent(10)));
+        assert_eq!(PowerStep::parse("0"), None);
+        assert_eq!(PowerStep::parse("0%"), None);
+        assert_eq!(PowerStep::parse("lots"), None);
+        assert_eq!(parse_power_step(Some("lots")), DEFAULT_POWER_STEP);
     }
+
     #[test]
-    fn ft8_30m_below() {
-        assert!(!is_ft8(10_133_999.9999, &DEFAULT_FT8_FREQS));
+    fn power_step_percent_is_at_least_one_watt() {
+        assert_eq!(PowerStep::Percent(10).watts(100), 10);
+        assert_eq!(PowerStep::Percent(10).watts(5), 1);
+        assert_eq!(PowerStep::Watts(5).watts(10), 5);
     }
+
+    fn sample_radio_data(frequency: &str) -> RadioData {
+        RadioData {
+            key: "k".to_string(),
+            radio: "IC-703".to_string(),
+            frequency: frequency.to_string(),
+            mode: "USB".to_string(),
+            rig_mode: "USB".to_string(),
+            power: "10".to_string(),
+            cat_url: None,
+            my_grid: None,
+        }
+    }
+
     #[test]
-    fn ft8_30m_above() {
-        assert!(!is_ft8(10_139_000.0, &DEFAULT_FT8_FREQS));
+    fn status_json_reports_band_and_power() {
+        let json = status_json(&sample_radio_data("14074000"));
+        assert_eq!(json["frequency"], 14_074_000);
+        assert_eq!(json["band"], "20m");
+        assert_eq!(json["mode"], "USB");
+        assert_eq!(json["power"], 10);
+        assert_eq!(json["rig"], "IC-703");
     }
 
-    // --- 20m ---
     #[test]
-    fn ft8_20m() {
-        assert!(is_ft8(14_074_000.0, &DEFAULT_FT8_FREQS));
+    fn wavelog_mode_for_common_rig_modes() {
+        for (rig_mode, expected) in [
+            ("LSB", "lsb"),
+            ("USB", "usb"),
+            ("CW", "cw"),
+            ("CW-U", "cw"),
+            ("CW-R", "cw"),
+            ("RTTY", "rtty"),
+            ("FSK", "rtty"),
+            ("AM", "am"),
+            ("FM", "fm"),
+            ("FM-N", "fm"),
+            ("D-USB", "digi"),
+            ("DATA-U", "digi"),
+            ("USB-D", "digi"),
+            ("DATA-FM", "digi"),
+            ("PSK", "psk"),
+        ] {
+            assert_eq!(
+                wavelog_mode_for(rig_mode).map(WavelogMode::as_str),
+                Some(expected),
+                "{rig_mode}"
+            );
+        }
+        assert_eq!(wavelog_mode_for("WIBBLE"), None);
+        assert_eq!(wavelog_mode_for(""), None);
     }
+
     #[test]
-    fn ft8_20m_lower_edge() {
-        assert!(is_ft8(14_072_000.0, &DEFAULT_FT8_FREQS));
+    fn wavelog_mode_round_trips_through_qsy_mapping() {
+        let m = icom_mode_map();
+        for rig_mode in ["LSB", "USB", "CW", "AM", "FM"] {
+            let back = wavelog_mode_for(rig_mode).unwrap();
+            assert_eq!(
+                wavelog_to_flrig_mode(14_074_000.0, back, &DEFAULT_FT8_FREQS, &m).to_string(),
+                rig_mode
+            );
+        }
+        // "digi" comes back as the data mode on an FT8 frequency.
+        let back = wavelog_mode_for("D-USB").unwrap();
+        assert_eq!(
+            wavelog_to_flrig_mode(14_074_000.0, back, &DEFAULT_FT8_FREQS, &m),
+            Mode::D_USB
+        );
     }
+
     #[test]
-    fn ft8_20m_below() {
-        assert!(!is_ft8(14_071_999.9999, &DEFAULT_FT8_FREQS));
+    fn status_json_out_of_band_reports_null_band() {
+        let json = status_json(&sample_radio_data("11000000"));
+        assert!(json["band"].is_null());
     }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for check_origin
+    //////////////////////////////////////////////////////////////
+
     #[test]
-    fn ft8_20m_above() {
-        assert!(!is_ft8(14_077_000.0, &DEFAULT_FT8_FREQS));
+    fn origin_correct_accepted() {
+        let req = Request::builder()
+            .header("origin", "https://wavelog.example.org")
+            .uri("/14074000/usb")
+            .body(())
+            .unwrap();
+        assert!(check_origin(&req, "https://wavelog.example.org"));
     }
 
-    // --- 17m ---
     #[test]
-    fn ft8_17m() {
-        assert!(is_ft8(18_100_000.0, &DEFAULT_FT8_FREQS));
+    fn origin_wrong_rejected() {
+        let req = Request::builder()
+            .header("origin", "https://evil.example.com")
+            .uri("/14074000/usb")
+            .body(())
+            .unwrap();
+        assert!(!check_origin(&req, "https://wavelog.example.org"));
     }
+
     #[test]
-    fn ft8_17m_lower_edge() {
-        assert!(is_ft8(18_098_000.0, &DEFAULT_FT8_FREQS));
+    fn origin_missing_rejected() {
+        let req = Request::builder().uri("/14074000/usb").body(()).unwrap();
+        assert!(!check_origin(&req, "https://wavelog.example.org"));
     }
+
     #[test]
-    fn ft8_17m_below() {
-        assert!(!is_ft8(18_097_999.9999, &DEFAULT_FT8_FREQS));
+    fn actions_are_logged_with_the_peer_but_reads_are_not() {
+        for path in [
+            "/14074000/usb",
+            "/power/on",
+            "/band/next",
+            "/sat/1/usb/2/usb",
+        ] {
+            assert!(is_action(&Method::GET, path), "{path}");
+        }
+        assert!(is_action(&Method::POST, "/qso"));
+        for path in ["/status", "/health", "/qsy/last", "/explain/14074000/usb"] {
+            assert!(!is_action(&Method::GET, path), "{path}");
+        }
     }
+
     #[test]
-    fn ft8_17m_above() {
-        assert!(!is_ft8(18_103_000.0, &DEFAULT_FT8_FREQS));
+    fn dispatcher_routes_paths_to_their_handlers() {
+        let handler = |method: &Method, path: &str| {
+            find_route(method, path).map(|(route, tail)| (route.handler, tail.to_string()))
+        };
+        assert_eq!(
+            handler(&Method::GET, "/status"),
+            Ok((Handler::Status, String::new()))
+        );
+        assert_eq!(
+            handler(&Method::GET, "/band/next"),
+            Ok((Handler::BandNext, String::new()))
+        );
+        assert_eq!(
+            handler(&Method::GET, "/14074000/usb"),
+            Ok((Handler::Qsy, "/14074000/usb".to_string()))
+        );
+        assert_eq!(
+            handler(&Method::GET, "/sat/145900000/fm/435300000/fm"),
+            Ok((Handler::Sat, "145900000/fm/435300000/fm".to_string()))
+        );
+        assert_eq!(
+            handler(&Method::GET, "/explain/7074000/usb"),
+            Ok((Handler::Explain, "7074000/usb".to_string()))
+        );
+        assert_eq!(
+            handler(&Method::POST, "/qso"),
+            Ok((Handler::Qso, String::new()))
+        );
     }
 
-    // --- 15m ---
     #[test]
-    fn ft8_15m() {
-        assert!(is_ft8(21_074_000.0, &DEFAULT_FT8_FREQS));
+    fn dispatcher_matches_nothing_for_unknown_paths() {
+        for path in ["/nope", "/status/extra/bit", "/", "/explainfoo/7074000/usb"] {
+            assert!(
+                matches!(find_route(&Method::GET, path), Err(NoRoute::NotFound)),
+                "{path}"
+            );
+        }
     }
+
     #[test]
-    fn ft8_15m_lower_edge() {
-        assert!(is_ft8(21_072_000.0, &DEFAULT_FT8_FREQS));
+    fn dispatcher_names_the_method_a_known_path_takes() {
+        let method = |method: &Method, path: &str| find_route(method, path).err();
+        // /qso only takes POST, and the other named routes only GET.
+        assert_eq!(
+            method(&Method::GET, "/qso"),
+            Some(NoRoute::WrongMethod("POST"))
+        );
+        assert_eq!(
+            method(&Method::POST, "/status"),
+            Some(NoRoute::WrongMethod("GET"))
+        );
+        assert_eq!(
+            method(&Method::PUT, "/power/on"),
+            Some(NoRoute::WrongMethod("GET"))
+        );
+        // The QSY path answers any method.
+        assert_eq!(method(&Method::POST, "/14074000/usb"), None);
     }
+
     #[test]
-    fn ft8_15m_below() {
-        assert!(!is_ft8(21_071_999.9999, &DEFAULT_FT8_FREQS));
+    fn every_handler_has_one_route() {
+        for route in ROUTES {
+            let same = ROUTES.iter().filter(|r| r.handler == route.handler).count();
+            assert_eq!(same, 1, "{:?}", route.handler);
+        }
+    }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for the POST /qso ADIF ingest route
+    //////////////////////////////////////////////////////////////
+
+    const SAMPLE_ADIF: &str = "<call:5>G4ABC <qso_date:8>20260101 <time_on:4>1200 \
+                               <band:3>20m <mode:3>FT8 <eor>";
+
+    fn test_state(qso_url: String) -> CatState {
+        let rig = flrig::FLRig::new(
+            flrig::FlrigSettings {
+                host: "http://127.0.0.1".to_string(),
+                port: 19999,
+                maxpower: 100,
+                cw_bw_index: None,
+                mode_bw_index: None,
+                narrow_delay_ms: None,
+                power_on: None,
+                power_off: None,
+                poll_interval_ms: None,
+                report_dsp: None,
+                report_passband: None,
+                report_smeter: None,
+                report_ptt: None,
+                smeter_calibration: None,
+                use_data_mode: None,
+                mode_power_limits: None,
+                enforce_mode_power: None,
+                cw_pitch_hz: None,
+                use_get_info: None,
+                backup_host: None,
+                backup_port: None,
+                failover_after: None,
+                verify_set: None,
+                log_vfob: None,
+                restore_on_start: None,
+                state_file: None,
+                power_is_watts: None,
+                band_mode_file: None,
+            },
+            "test-rig".to_string(),
+        );
+        CatState {
+            rig: Arc::new(rig),
+            allow: AllowList::default(),
+            live: Arc::new(RwLock::new(LiveSettings {
+                poll_interval_ms: 1000,
+                jitter: 0.0,
+                min_upload_interval_ms: 1000,
+                keepalive_secs: 0,
+                mode_map: icom_mode_map(),
+                ft8_freqs: ft8_freqs(None),
+                ft8_automode: true,
+                cw_pitch_hz: 0,
+            })),
+            wavelog_origin: None,
+            wavelog: WavelogSettings {
+                url: "http://127.0.0.1:19999/index.php/api/radio".to_string(),
+                qso_url,
+                key: "test".to_string(),
+                identifier: "test-rig".to_string(),
+                station_profile_id: 1,
+                interval: 1000,
+                cat_url: None,
+                my_grid: None,
+                jitter: None,
+                min_upload_interval_ms: None,
+                keepalive_secs: None,
+                compress: None,
+                adif_inject: None,
+                mode_remap: None,
+                local_adif: None,
+                qso_spool: None,
+                radio_method: None,
+                qso_method: None,
+                base_url: None,
+            },
+            client: reqwest::Client::new(),
+            config: serde_json::json!({ "wavelog": { "key": "***" } }),
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+            modes_cache: Mutex::new(None),
+            cache_ttl: Duration::from_millis(DEFAULT_CACHE_MS),
+            health_failures: DEFAULT_HEALTH_FAILURES,
+            wsjtx_health: Duration::from_secs(DEFAULT_WSJTX_HEALTH_SECS),
+            connection_timeout: Duration::from_secs(DEFAULT_CONNECTION_TIMEOUT_SECS),
+            connections: Arc::new(Semaphore::new(DEFAULT_MAX_CONNECTIONS)),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            body_timeout: Duration::from_millis(DEFAULT_BODY_TIMEOUT_MS),
+            band_homes: Vec::new(),
+            band_wrap: true,
+            respond_early: false,
+            mode_aliases: ModeAliases::default(),
+            freq_step_hz: 0,
+            power_step: DEFAULT_POWER_STEP,
+            last_qsy: Mutex::new(None),
+            qsy_count: AtomicU64::new(0),
+        }
     }
+
+    fn make_post(body: &str) -> Request<Full<Bytes>> {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/qso")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .unwrap()
+    }
+
     #[test]
-    fn ft8_15m_above() {
-        assert!(!is_ft8(21_077_000.0, &DEFAULT_FT8_FREQS));
+    fn adif_plausibility_check() {
+        assert!(is_plausible_adif(SAMPLE_ADIF));
+        assert!(is_plausible_adif("<CALL:5>G4ABC <EOR>"));
+        assert!(!is_plausible_adif(""));
+        assert!(!is_plausible_adif("<call:5>G4ABC")); // no <eor>
+        assert!(!is_plausible_adif("{\"call\": \"G4ABC\"}"));
+    }
+
+    #[tokio::test]
+    async fn log_qso_forwards_valid_adif() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = test_state(mock_wavelog(StatusCode::OK, tx).await);
+
+        let resp = log_qso(&state, make_post(SAMPLE_ADIF)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let uploaded: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(uploaded["type"], "adif");
+        // Forwarded as posted, plus the rig identifier.
+        assert_eq!(
+            uploaded["string"],
+            SAMPLE_ADIF.replace("<eor>", "<APP_WLRIGCTL_RIG:8>test-rig <eor>")
+        );
+        assert_eq!(uploaded["station_profile_id"], 1);
+    }
+
+    #[tokio::test]
+    async fn log_qso_injects_configured_fields() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut state = test_state(mock_wavelog(StatusCode::OK, tx).await);
+        state.wavelog.adif_inject = Some(HashMap::from([
+            ("MY_GRIDSQUARE".to_string(), "IO91wm".to_string()),
+            ("BAND".to_string(), "40m".to_string()),
+        ]));
+
+        let resp = log_qso(&state, make_post(SAMPLE_ADIF)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let uploaded: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        let adif = uploaded["string"].as_str().unwrap();
+        assert!(adif.contains("<MY_GRIDSQUARE:6>IO91wm "), "{adif}");
+        assert!(adif.contains("<band:3>20m"), "{adif}");
+        assert!(!adif.contains("40m"), "{adif}");
+        assert!(adif.ends_with("<eor>"), "{adif}");
+    }
+
+    #[tokio::test]
+    async fn log_qso_passes_upstream_status_through() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let state = test_state(mock_wavelog(StatusCode::UNAUTHORIZED, tx).await);
+
+        let resp = log_qso(&state, make_post(SAMPLE_ADIF)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
     }
 
-    // --- 12m ---
     #[test]
-    fn ft8_12m() {
-        assert!(is_ft8(24_915_000.0, &DEFAULT_FT8_FREQS));
+    fn http_err_str_is_json_envelope_with_cors() {
+        let resp = http_err_str(StatusCode::BAD_REQUEST, "bad frequency");
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(resp.headers()["Access-Control-Allow-Origin"], "*");
+
+        let body = futures_util::FutureExt::now_or_never(resp.into_body().collect())
+            .unwrap()
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["message"], "bad frequency");
+    }
+
+    #[tokio::test]
+    async fn smeter_without_flrig_is_server_error() {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        assert_eq!(
+            smeter(&state).await.status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[tokio::test]
+    async fn power_unconfigured_is_not_implemented() {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        assert_eq!(
+            power(&state, true).await.status(),
+            StatusCode::NOT_IMPLEMENTED
+        );
+        assert_eq!(
+            power(&state, false).await.status(),
+            StatusCode::NOT_IMPLEMENTED
+        );
     }
+
+    //////////////////////////////////////////////////////////////
+    // Tests for /explain
+    //////////////////////////////////////////////////////////////
+
+    fn explain_json(tail: &str) -> serde_json::Value {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        let resp = explain(&state, tail);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = futures_util::FutureExt::now_or_never(resp.into_body().collect())
+            .unwrap()
+            .unwrap()
+            .to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
     #[test]
-    fn ft8_12m_lower_edge() {
-        assert!(is_ft8(24_913_000.0, &DEFAULT_FT8_FREQS));
+    fn explain_ft8_window() {
+        let json = explain_json("/7074000/digi");
+        assert_eq!(json["mode"], "D-USB");
+        assert_eq!(json["reason"], "matched-ft8");
+        assert_eq!(json["requested_mode"], "digi");
     }
+
     #[test]
-    fn ft8_12m_below() {
-        assert!(!is_ft8(24_912_999.9999, &DEFAULT_FT8_FREQS));
+    fn explain_phone_below_10mhz() {
+        let json = explain_json("/7150000/phone");
+        assert_eq!(json["mode"], "LSB");
+        assert_eq!(json["reason"], "phone-below-10mhz-lsb");
     }
+
     #[test]
-    fn ft8_12m_above() {
-        assert!(!is_ft8(24_918_000.0, &DEFAULT_FT8_FREQS));
+    fn explain_explicit_usb() {
+        let json = explain_json("/7074000/usb");
+        assert_eq!(json["mode"], "USB");
+        assert_eq!(json["reason"], "explicit");
     }
 
-    // --- 10m ---
     #[test]
-    fn ft8_10m() {
-        assert!(is_ft8(28_074_000.0, &DEFAULT_FT8_FREQS));
+    fn explain_ft8_automode_off_ignores_ft8_window() {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        state.live.write().unwrap().ft8_automode = false;
+        let resp = explain(&state, "/7074000/digi");
+        let body = futures_util::FutureExt::now_or_never(resp.into_body().collect())
+            .unwrap()
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["mode"], "RTTY");
+        assert_eq!(json["reason"], "digital-outside-ft8");
     }
+
     #[test]
-    fn ft8_10m_lower_edge() {
-        assert!(is_ft8(28_072_000.0, &DEFAULT_FT8_FREQS));
+    fn explain_rejects_out_of_band() {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        assert_eq!(
+            explain(&state, "/11000000/usb").status(),
+            StatusCode::BAD_REQUEST
+        );
     }
+
     #[test]
-    fn ft8_10m_below() {
-        assert!(!is_ft8(28_071_999.9999, &DEFAULT_FT8_FREQS));
+    fn bandplan_reports_default_plan() {
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        let resp = bandplan(&state);
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = futures_util::FutureExt::now_or_never(resp.into_body().collect())
+            .unwrap()
+            .unwrap()
+            .to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        let ft8 = json["ft8_frequencies"].as_array().unwrap();
+        assert_eq!(ft8.len(), DEFAULT_FT8_FREQS.len());
+        assert_eq!(ft8[2], 7_074_000.0);
+        assert_eq!(json["ft8_window_hz"]["below"], 2_000.0);
+        assert_eq!(json["ft8_window_hz"]["above"], 3_000.0);
+        assert_eq!(json["phone_usb_from_hz"], 10_000_000.0);
+        assert_eq!(json["modes"]["digital"], "D-USB");
+        assert_eq!(json["band_modes"], json!({}));
+    }
+
+    #[tokio::test]
+    async fn log_qso_rejects_oversized_body() {
+        let mut state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        state.max_body_bytes = 16;
+        let resp = log_qso(&state, make_post(SAMPLE_ADIF)).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn read_body_times_out_on_a_stalled_body() {
+        let mut state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        state.body_timeout = Duration::from_millis(50);
+        // A body that never delivers any data.
+        let stalled = http_body_util::StreamBody::new(futures_util::stream::pending::<
+            Result<hyper::body::Frame<Bytes>, Infallible>,
+        >());
+        let err = read_body(&state, stalled).await.unwrap_err();
+        assert_eq!(err.status(), StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn log_qso_rejects_non_adif_body() {
+        // Nothing is listening on the QSO URL: the request must be rejected
+        // before any upload is attempted.
+        let state = test_state("http://127.0.0.1:19999/index.php/api/qso".to_string());
+        let resp = log_qso(&state, make_post("hello")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    //////////////////////////////////////////////////////////////
+    // End-to-end tests: real HTTP against serve(), FLRig mocked
+    //////////////////////////////////////////////////////////////
+
+    use crate::mock_flrig::MockFlrig;
+
+    fn e2e_cat_settings() -> CatSettings {
+        CatSettings {
+            host: None,
+            port: 0,
+            cw_mode: None,
+            rtty_mode: None,
+            digital_mode: None,
+            default_digi_mode: None,
+            ft8_frequencies: None,
+            ft8_automode: None,
+            wavelog_origin: None,
+            band_homes: None,
+            band_wrap: None,
+            respond_early: None,
+            mode_aliases: None,
+            cache_ms: None,
+            health_failures: None,
+            wsjtx_health_secs: None,
+            tls_cert: None,
+            tls_key: None,
+            connection_timeout_secs: None,
+            max_connections: None,
+            max_body_bytes: None,
+            body_timeout_ms: None,
+            freq_step_hz: None,
+            allow: None,
+            power_step: None,
+        }
+    }
+
+    // Serve the CAT routes on an ephemeral port against `flrig`; returns the
+    // base URL.  The server stops when `token` is cancelled.
+    async fn start_cat(flrig: flrig::FlrigSettings, token: &CancellationToken) -> String {
+        let wavelog = test_state("http://127.0.0.1:19999/api/qso".to_string()).wavelog;
+        let live = LiveSettings::new(&wavelog, &flrig, &e2e_cat_settings());
+        let handles = CatHandles {
+            rig: Arc::new(flrig::FLRig::new(flrig, "test-rig".to_string())),
+            live: Arc::new(RwLock::new(live)),
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        serve_handles(handles, token).await
+    }
+
+    // As start_cat, with the caller's handles.
+    async fn serve_handles(handles: CatHandles, token: &CancellationToken) -> String {
+        serve_settings_handles(e2e_cat_settings(), handles, token).await
+    }
+
+    // As serve_handles, with the caller's CAT settings too.
+    async fn serve_settings_handles(
+        settings: CatSettings,
+        handles: CatHandles,
+        token: &CancellationToken,
+    ) -> String {
+        let wavelog = test_state("http://127.0.0.1:19999/api/qso".to_string()).wavelog;
+        let state =
+            Arc::new(build_state(settings, wavelog, handles, serde_json::Value::Null).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state, None, token.clone()));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn e2e_qsy_over_tls() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let dir = std::env::temp_dir().join("wlrigctl-cat-tls");
+        std::fs::create_dir_all(&dir).unwrap();
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::write(&cert_path, certified.cert.pem()).unwrap();
+        std::fs::write(&key_path, certified.signing_key.serialize_pem()).unwrap();
+
+        let settings = CatSettings {
+            tls_cert: Some(cert_path.to_string_lossy().into_owned()),
+            tls_key: Some(key_path.to_string_lossy().into_owned()),
+            ..e2e_cat_settings()
+        };
+        let tls = cat_tls_acceptor(&settings).unwrap();
+        assert!(tls.is_some());
+        let wavelog = test_state("http://127.0.0.1:19999/api/qso".to_string()).wavelog;
+        let handles = CatHandles {
+            rig: Arc::new(flrig::FLRig::new(flrig.settings(), "test-rig".to_string())),
+            live: test_state(String::new()).live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        let state =
+            Arc::new(build_state(settings, wavelog, handles, serde_json::Value::Null).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let token = CancellationToken::new();
+        tokio::spawn(serve(listener, state, tls, token.clone()));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(
+                reqwest::Certificate::from_pem(certified.cert.pem().as_bytes()).unwrap(),
+            )
+            .resolve("localhost", SocketAddr::from(([127, 0, 0, 1], port)))
+            .build()
+            .unwrap();
+        let resp = client
+            .get(format!("https://localhost:{port}/14074000/usb"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(flrig.last_call("rig.set_vfo").unwrap().contains("14074000"));
+
+        // Plain HTTP is not served on a TLS listener.
+        assert!(
+            reqwest::get(format!("http://127.0.0.1:{port}/14074000/usb"))
+                .await
+                .is_err()
+        );
+        token.cancel();
+    }
+
+    // Serve with `settings` and no working FLRig; returns the address.
+    async fn serve_with(settings: CatSettings, token: &CancellationToken) -> SocketAddr {
+        let test = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let handles = CatHandles {
+            rig: test.rig,
+            live: test.live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        let state = Arc::new(
+            build_state(settings, test.wavelog, handles, serde_json::Value::Null).unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state, None, token.clone()));
+        addr
+    }
+
+    #[tokio::test]
+    async fn allow_list_refuses_unlisted_clients() {
+        let token = CancellationToken::new();
+        let get = |addr: SocketAddr| async move {
+            reqwest::get(format!("http://{addr}/qsy/last"))
+                .await
+                .unwrap()
+                .status()
+        };
+
+        let denied = serve_with(
+            CatSettings {
+                allow: Some(vec!["192.168.1.0/24".to_string()]),
+                ..e2e_cat_settings()
+            },
+            &token,
+        )
+        .await;
+        assert_eq!(get(denied).await, StatusCode::FORBIDDEN);
+
+        // Let through, /qsy/last answers that there has been no QSY yet.
+        let allowed = serve_with(
+            CatSettings {
+                allow: Some(vec!["192.168.1.0/24".to_string(), "127.0.0.1".to_string()]),
+                ..e2e_cat_settings()
+            },
+            &token,
+        )
+        .await;
+        assert_eq!(get(allowed).await, StatusCode::NOT_FOUND);
+
+        let open = serve_with(e2e_cat_settings(), &token).await;
+        assert_eq!(get(open).await, StatusCode::NOT_FOUND);
+        token.cancel();
     }
+
     #[test]
-    fn ft8_10m_above() {
-        assert!(!is_ft8(28_077_000.0, &DEFAULT_FT8_FREQS));
+    fn bad_allow_entry_stops_startup() {
+        let test = test_state(String::new());
+        let handles = CatHandles {
+            rig: test.rig,
+            live: test.live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        let settings = CatSettings {
+            allow: Some(vec!["192.168.1.0/40".to_string()]),
+            ..e2e_cat_settings()
+        };
+        assert!(build_state(settings, test.wavelog, handles, serde_json::Value::Null).is_err());
+    }
+
+    // Read until the server closes the connection, or fail after `limit`.
+    async fn expect_closed(stream: &mut tokio::net::TcpStream, limit: Duration) {
+        use tokio::io::AsyncReadExt;
+        let mut buf = [0u8; 64];
+        let read = tokio::time::timeout(limit, stream.read(&mut buf))
+            .await
+            .expect("connection was not closed");
+        assert!(matches!(read, Ok(0) | Err(_)), "{read:?}");
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_closed_after_timeout() {
+        let token = CancellationToken::new();
+        let settings = CatSettings {
+            connection_timeout_secs: Some(1),
+            ..e2e_cat_settings()
+        };
+        let addr = serve_with(settings, &token).await;
+
+        let mut idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let started = Instant::now();
+        expect_closed(&mut idle, Duration::from_secs(5)).await;
+        assert!(started.elapsed() >= Duration::from_millis(900));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn busy_connection_outlives_the_timeout() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let token = CancellationToken::new();
+        let settings = CatSettings {
+            connection_timeout_secs: Some(1),
+            ..e2e_cat_settings()
+        };
+        let addr = serve_with(settings, &token).await;
+
+        // Keep-alive requests spread over well past the timeout all get an
+        // answer on the one connection.
+        let mut conn = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let started = Instant::now();
+        for _ in 0..4 {
+            conn.write_all(b"GET /qsy/last HTTP/1.1\r\nHost: cat\r\n\r\n")
+                .await
+                .unwrap();
+            let mut buf = [0u8; 1024];
+            let n = conn.read(&mut buf).await.unwrap();
+            assert!(buf[..n].starts_with(b"HTTP/1.1 404"));
+            tokio::time::sleep(Duration::from_millis(400)).await;
+        }
+        assert!(started.elapsed() > Duration::from_secs(1));
+
+        // Once it falls idle it is closed.
+        expect_closed(&mut conn, Duration::from_secs(5)).await;
+        token.cancel();
     }
 
-    // --- 6m ---
-    #[test]
-    fn ft8_6m() {
-        assert!(is_ft8(50_313_000.0, &DEFAULT_FT8_FREQS));
+    #[tokio::test]
+    async fn connections_over_the_limit_are_dropped() {
+        let token = CancellationToken::new();
+        let settings = CatSettings {
+            max_connections: Some(1),
+            ..e2e_cat_settings()
+        };
+        let addr = serve_with(settings, &token).await;
+
+        let _first = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+        expect_closed(&mut second, Duration::from_secs(5)).await;
+        token.cancel();
     }
+
     #[test]
-    fn ft8_6m_lower_edge() {
-        assert!(is_ft8(50_311_000.0, &DEFAULT_FT8_FREQS));
+    fn cat_tls_needs_both_cert_and_key() {
+        assert!(cat_tls_acceptor(&e2e_cat_settings()).unwrap().is_none());
+        let settings = CatSettings {
+            tls_cert: Some("/nonexistent/cert.pem".to_string()),
+            ..e2e_cat_settings()
+        };
+        assert!(cat_tls_acceptor(&settings).is_err());
     }
+
     #[test]
-    fn ft8_6m_below() {
-        assert!(!is_ft8(50_310_999.9999, &DEFAULT_FT8_FREQS));
+    fn allow_list_is_checked_against_bind_address() {
+        let listed = Some(vec!["192.168.1.0/24".to_string()]);
+        let exposed = CatSettings {
+            host: Some("0.0.0.0".to_string()),
+            ..e2e_cat_settings()
+        };
+        assert!(exposure_warning(&exposed).is_some());
+        let guarded = CatSettings {
+            allow: listed.clone(),
+            ..exposed
+        };
+        assert!(exposure_warning(&guarded).is_none());
+
+        assert!(exposure_warning(&e2e_cat_settings()).is_none());
+        let loopback_list = CatSettings {
+            allow: listed,
+            ..e2e_cat_settings()
+        };
+        assert!(exposure_warning(&loopback_list).is_some());
     }
+
     #[test]
-    fn ft8_6m_above() {
-        assert!(!is_ft8(50_316_000.0, &DEFAULT_FT8_FREQS));
-    }
+    fn cat_binds_loopback_unless_host_is_set() {
+        let settings = CatSettings {
+            port: 54321,
+            ..e2e_cat_settings()
+        };
+        assert_eq!(settings.bind_addr().to_string(), "127.0.0.1:54321");
 
-    // Helpers: construct a ModeMap for testing without config or a live FLRig.
-    fn icom_mode_map() -> ModeMap {
-        flrig::build_mode_map(None, None, None)
-    }
-    fn yaesu_mode_map() -> ModeMap {
-        flrig::build_mode_map(Some("CW-U"), Some("RTTY-U"), Some("DATA-U"))
+        let settings = CatSettings {
+            host: Some("0.0.0.0".to_string()),
+            ..settings
+        };
+        assert_eq!(settings.bind_addr().to_string(), "0.0.0.0:54321");
+
+        let settings = CatSettings {
+            host: Some("not-an-ip".to_string()),
+            ..settings
+        };
+        assert_eq!(settings.bind_addr().to_string(), "127.0.0.1:54321");
     }
 
-    //////////////////////////////////////////////////////////////
-    // Tests for Bandlist/Cluster mode/frequency conversions (ICOM/generic map)
-    // FT8 detection only overrides Digi and Rtty; other modes pass through.
-    //////////////////////////////////////////////////////////////
+    #[tokio::test]
+    async fn e2e_qsy_sets_vfo_and_mode() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
 
-    #[test]
-    fn flrig_40m_ft8_digi_rtty_become_d_usb() {
-        const FT8_40M: f64 = 7_074_000.0;
-        let m = icom_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
-            Mode::D_USB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
-            Mode::D_USB
-        );
+        let resp = reqwest::get(format!("{base}/14074000/cw")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()["access-control-allow-origin"], "*");
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["frequency"], 14_074_000.0);
+        assert_eq!(json["mode"], "CW");
+        assert_eq!(json["rig"], "test-rig");
+
+        let methods = flrig.methods();
+        assert!(methods.contains(&"rig.set_vfo".to_string()), "{methods:?}");
+        assert!(methods.contains(&"rig.set_mode".to_string()), "{methods:?}");
+        assert!(flrig.last_call("rig.set_vfo").unwrap().contains("14074000"));
+        assert!(flrig.last_call("rig.set_mode").unwrap().contains("CW"));
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_ft8_other_modes_unaffected() {
-        const FT8_40M: f64 = 7_074_000.0;
-        let m = icom_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
-            Mode::CW
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
+    #[tokio::test]
+    async fn e2e_concurrent_qsys_do_not_interleave() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        flrig.delay_replies(50);
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let (a, b) = tokio::join!(
+            reqwest::get(format!("{base}/14074000/cw")),
+            reqwest::get(format!("{base}/7150000/lsb")),
         );
+        assert_eq!(a.unwrap().status(), StatusCode::OK);
+        assert_eq!(b.unwrap().status(), StatusCode::OK);
+
+        // Each QSY's frequency is followed by its own mode, never the other's.
+        let calls: Vec<String> = flrig
+            .methods()
+            .into_iter()
+            .filter(|m| m == "rig.set_vfo" || m == "rig.set_mode")
+            .collect();
         assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
+            calls,
+            ["rig.set_vfo", "rig.set_mode", "rig.set_vfo", "rig.set_mode"],
         );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
-            Mode::USB
+        let (vfo, mode) = (
+            flrig.last_call("rig.set_vfo").unwrap(),
+            flrig.last_call("rig.set_mode").unwrap(),
         );
         assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
-            Mode::AM
+            vfo.contains("14074000"),
+            mode.contains("CW"),
+            "{vfo} / {mode}"
         );
         assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
-            Mode::FM
+            vfo.contains("7150000"),
+            mode.contains("LSB"),
+            "{vfo} / {mode}"
         );
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_cw() {
-        let m = icom_mode_map();
-        const BAND_40M: [f64; 5] = [
-            7_000_000.0,
-            7_030_000.0,
-            7_100_000.0,
-            7_185_000.0,
-            7_200_000.0,
-        ];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
-                Mode::CW
-            );
-        }
+    #[tokio::test]
+    async fn e2e_respond_early_answers_before_tuning() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        flrig.delay_replies(300);
+        let token = CancellationToken::new();
+        let test = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let handles = CatHandles {
+            rig: Arc::new(flrig::FLRig::new(flrig.settings(), "test-rig".to_string())),
+            live: test.live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        let settings = CatSettings {
+            respond_early: Some(true),
+            ..e2e_cat_settings()
+        };
+        let base = serve_settings_handles(settings, handles, &token).await;
+
+        let resp = reqwest::get(format!("{base}/qsy/last")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // Answered before FLRig has replied to anything.
+        let started = Instant::now();
+        let resp = reqwest::get(format!("{base}/14074000/cw")).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(300));
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["status"], "accepted");
+        assert_eq!(json["mode"], "CW");
+
+        let resp = reqwest::get(format!("{base}/qsy/last")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["status"], "pending");
+
+        // Bad requests are still refused up front.
+        let resp = reqwest::get(format!("{base}/14074000/nonsense"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let json = loop {
+            let resp = reqwest::get(format!("{base}/qsy/last")).await.unwrap();
+            if resp.status() == StatusCode::OK {
+                break resp.json::<serde_json::Value>().await.unwrap();
+            }
+            assert!(Instant::now() < deadline, "QSY never completed");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        };
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["frequency"], 14_074_000.0);
+        assert!(flrig.last_call("rig.set_mode").unwrap().contains("CW"));
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_phone() {
-        let m = icom_mode_map();
-        const BAND_40M: [f64; 5] = [
-            7_000_000.0,
-            7_030_000.0,
-            7_100_000.0,
-            7_185_000.0,
-            7_200_000.0,
-        ];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-                Mode::LSB
-            );
-        }
+    #[tokio::test]
+    async fn e2e_force_mode_resends_unchanged_mode() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        // Already in USB: skipped by default...
+        let resp = reqwest::get(format!("{base}/14250000/usb")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(flrig.last_call("rig.set_mode").is_none());
+
+        // ...but sent when forced.
+        let resp = reqwest::get(format!("{base}/14250000/usb?force_mode=true"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(flrig.last_call("rig.set_mode").unwrap().contains("USB"));
+
+        let resp = reqwest::get(format!("{base}/14250000/usb?force_mode=yes"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_lsb() {
-        let m = icom_mode_map();
-        const BAND_40M: [f64; 5] = [
-            7_000_000.0,
-            7_030_000.0,
-            7_100_000.0,
-            7_185_000.0,
-            7_200_000.0,
-        ];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
-                Mode::LSB
-            );
-        }
+    #[tokio::test]
+    async fn e2e_path_without_mode_is_not_found() {
+        let flrig = MockFlrig::start(&[]).await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/14074000")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers()["access-control-allow-origin"], "*");
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["status"], "error");
+        assert!(flrig.methods().is_empty(), "rig must not be touched");
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_usb() {
-        let m = icom_mode_map();
-        const BAND_40M: [f64; 5] = [
-            7_000_000.0,
-            7_030_000.0,
-            7_100_000.0,
-            7_185_000.0,
-            7_200_000.0,
-        ];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
-                Mode::USB
-            );
-        }
+    #[tokio::test]
+    async fn e2e_status_reads_rig_via_multicall() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>7074000.000000</string>"),
+            ("rig.get_mode", "<string>D-USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>25</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/status")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["frequency"], 7_074_000);
+        assert_eq!(json["band"], "40m");
+        assert_eq!(json["mode"], "USB");
+        assert_eq!(json["rig_mode"], "D-USB");
+        assert_eq!(json["wavelog_mode"], "digi");
+        assert_eq!(json["power"], 25);
+        token.cancel();
     }
 
-    #[test]
-    fn flrig_40m_digi_rtty() {
-        let m = icom_mode_map();
-        const BAND_40M: [f64; 5] = [
-            7_000_000.0,
-            7_030_000.0,
-            7_100_000.0,
-            7_185_000.0,
-            7_200_000.0,
-        ];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
-                Mode::RTTY
-            );
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
-                Mode::RTTY
-            );
+    #[tokio::test]
+    async fn e2e_status_within_ttl_reads_rig_once() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+            ("rig.set_vfo", "<string></string>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+        let fetches = || {
+            flrig
+                .methods()
+                .iter()
+                .filter(|m| *m == "rig.get_vfo")
+                .count()
+        };
+
+        for _ in 0..2 {
+            let resp = reqwest::get(format!("{base}/status")).await.unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
         }
+        assert_eq!(fetches(), 1);
+
+        // A QSY invalidates the cached state.
+        reqwest::get(format!("{base}/14074000/usb")).await.unwrap();
+        reqwest::get(format!("{base}/status")).await.unwrap();
+        assert_eq!(fetches(), 2);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn health_reports_down_only_after_repeated_failures() {
+        let flrig = MockFlrig::start(&[("main.get_version", "<string>2.0.04</string>")]).await;
+        let handles = CatHandles {
+            rig: Arc::new(flrig::FLRig::new(flrig.settings(), "test-rig".to_string())),
+            live: test_state(String::new()).live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache: RadioCache::default(),
+            updates: broadcast::channel(16).0,
+            latency: SharedLatency::default(),
+        };
+        let settings = CatSettings {
+            health_failures: Some(2),
+            ..e2e_cat_settings()
+        };
+        let state = build_state(
+            settings,
+            test_state(String::new()).wavelog,
+            handles,
+            serde_json::Value::Null,
+        )
+        .unwrap();
+
+        // Each failed probe is one call, retried once: two dropped connections.
+        flrig.drop_connections(2);
+        assert_eq!(health(&state).await.status(), StatusCode::OK);
+        flrig.drop_connections(2);
+        assert_eq!(
+            health(&state).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(health(&state).await.status(), StatusCode::OK);
     }
 
     #[test]
-    fn flrig_am_fm() {
-        let m = icom_mode_map();
+    fn wsjtx_liveness_reports_old_datagram_as_stale() {
+        let now = Instant::now();
+        let window = Duration::from_secs(60);
+        assert_eq!(wsjtx_liveness(None, now, window), "never");
         assert_eq!(
-            wavelog_to_flrig_mode(7_200_000.0, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
-            Mode::AM
+            wsjtx_liveness(Some(now - Duration::from_secs(10)), now, window),
+            "ok"
         );
         assert_eq!(
-            wavelog_to_flrig_mode(29_600_000.0, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
-            Mode::FM
+            wsjtx_liveness(Some(now - Duration::from_secs(61)), now, window),
+            "stale"
         );
     }
 
     #[test]
-    fn flrig_20m_phone_becomes_usb() {
-        let m = icom_mode_map();
+    fn wsjtx_health_before_any_datagram_is_unavailable() {
+        let state = test_state(String::new());
         assert_eq!(
-            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-            Mode::USB
+            wsjtx_health(&state).status(),
+            StatusCode::SERVICE_UNAVAILABLE
         );
     }
 
-    #[test]
-    fn is_ft8_custom_freqs() {
-        let custom: [f64; 1] = [14_074_000.0];
-        assert!(is_ft8(14_074_000.0, &custom));
-        assert!(!is_ft8(7_074_000.0, &custom));
+    #[tokio::test]
+    async fn stats_reports_flrig_round_trips() {
+        let flrig = MockFlrig::start(&[("main.get_version", "<string>2.0.04</string>")]).await;
+        let mut state = test_state(String::new());
+        state.rig = Arc::new(
+            flrig::FLRig::new(flrig.settings(), "test-rig".to_string())
+                .with_latency(state.latency.clone()),
+        );
+
+        let body: serde_json::Value = serde_json::from_slice(
+            &stats(&state)
+                .into_body()
+                .collect()
+                .await
+                .unwrap()
+                .to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(body["flrig"], serde_json::Value::Null);
+
+        state.rig.probe().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(
+            &stats(&state)
+                .into_body()
+                .collect()
+                .await
+                .unwrap()
+                .to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(body["flrig"]["samples"], 1);
+        assert_eq!(body["wavelog"], serde_json::Value::Null);
     }
 
-    //////////////////////////////////////////////////////////////
-    // Tests for Yaesu mode map (CW→CW_U, Digi/Rtty→DATA_U or RTTY_U)
-    //////////////////////////////////////////////////////////////
+    #[tokio::test]
+    async fn status_reuses_poll_loop_read() {
+        let mut state = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        state.radio_cache = RadioCache::default();
+        let generation = state.radio_cache.generation();
+        state
+            .radio_cache
+            .store(generation, &rig_at("7074000", "USB"));
 
-    #[test]
-    fn yaesu_ft8_digi_rtty_become_data_u() {
-        const FT8_40M: f64 = 7_074_000.0;
-        let m = yaesu_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
-            Mode::DATA_U
-        );
+        // Nothing listens on the test rig's port, so this only passes
+        // without an FLRig call.
+        let resp = status(&state).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value =
+            serde_json::from_slice(&resp.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(json["frequency"], 7_074_000);
+
+        state.cache_ttl = Duration::ZERO;
         assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
-            Mode::DATA_U
+            status(&state).await.status(),
+            StatusCode::INTERNAL_SERVER_ERROR
         );
     }
 
-    #[test]
-    fn yaesu_ft8_other_modes_unaffected() {
-        const FT8_40M: f64 = 7_074_000.0;
-        let m = yaesu_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
-            Mode::CW_U
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
-            Mode::USB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
-            Mode::AM
-        );
+    #[tokio::test]
+    async fn e2e_status_reports_dsp_state_when_enabled() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+            ("rig.get_noise", "<i4>1</i4>"),
+            ("rig.get_nr", "<i4>0</i4>"),
+            // no rig.get_notch: the mock faults, as an unsupported rig would
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                report_dsp: Some(true),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["dsp"], json!({ "nb": true, "nr": false }));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_status_reports_passband_when_enabled() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+            (
+                "rig.get_bw",
+                "<array><data><value><string>2400</string></value>\
+                 <value><string></string></value></data></array>",
+            ),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                report_passband: Some(true),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["passband"], json!({ "width": "2400" }));
+        assert!(json.get("dsp").is_none());
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_status_reports_calibrated_smeter_when_enabled() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+            ("rig.get_smeter", "<i4>160</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                report_smeter: Some(true),
+                smeter_calibration: Some(vec![[0.0, -54.0], [120.0, 0.0], [200.0, 40.0]]),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
         assert_eq!(
-            wavelog_to_flrig_mode(FT8_40M, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
-            Mode::FM
+            json["smeter"],
+            json!({ "raw": 160, "s_units": "S9+20", "db_over_s9": 20.0 })
         );
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/smeter"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["smeter"], 160);
+        assert_eq!(json["s_units"], "S9+20");
+        assert_eq!(json["db_over_s9"], 20.0);
+        token.cancel();
     }
 
-    #[test]
-    fn yaesu_40m_cw_becomes_cw_u() {
-        let m = yaesu_mode_map();
-        const BAND_40M: [f64; 3] = [7_000_000.0, 7_030_000.0, 7_200_000.0];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Cw, &DEFAULT_FT8_FREQS, &m),
-                Mode::CW_U
-            );
-        }
+    #[tokio::test]
+    async fn e2e_status_reports_rig_ptt_when_enabled() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+            ("rig.get_ptt", "<i4>1</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                report_ptt: Some(true),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["transmitting"], true);
+        assert_eq!(json["transmitting_source"], "flrig");
+        token.cancel();
     }
 
     #[test]
-    fn yaesu_phone_lsb_usb_boundary() {
-        let m = yaesu_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(7_150_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::Phone, &DEFAULT_FT8_FREQS, &m),
-            Mode::USB
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(7_150_000.0, WavelogMode::LSB, &DEFAULT_FT8_FREQS, &m),
-            Mode::LSB
-        );
+    fn tx_state_prefers_rig_ptt_then_wsjtx() {
         assert_eq!(
-            wavelog_to_flrig_mode(14_225_000.0, WavelogMode::USB, &DEFAULT_FT8_FREQS, &m),
-            Mode::USB
+            tx_state(Some(false), Some(true)),
+            (Some(false), Some("flrig"))
         );
+        assert_eq!(tx_state(None, Some(true)), (Some(true), Some("wsjtx")));
+        assert_eq!(tx_state(None, None), (None, None));
     }
 
-    #[test]
-    fn yaesu_40m_digi_rtty_become_rtty_u() {
-        let m = yaesu_mode_map();
-        const BAND_40M: [f64; 3] = [7_000_000.0, 7_030_000.0, 7_200_000.0];
-        for freq in BAND_40M {
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Digi, &DEFAULT_FT8_FREQS, &m),
-                Mode::RTTY_U
-            );
-            assert_eq!(
-                wavelog_to_flrig_mode(freq, WavelogMode::Rtty, &DEFAULT_FT8_FREQS, &m),
-                Mode::RTTY_U
-            );
+    #[tokio::test]
+    async fn e2e_status_omits_dsp_by_default() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>10</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/status"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(json.get("dsp").is_none());
+        assert!(!flrig.methods().contains(&"rig.get_noise".to_string()));
+        assert_eq!(json["transmitting"], serde_json::Value::Null);
+        assert!(!flrig.methods().contains(&"rig.get_ptt".to_string()));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_modes_lists_rig_modes() {
+        let flrig = MockFlrig::start(&[(
+            "rig.get_modes",
+            "<array><data><value><string>LSB</string></value>\
+             <value><string>USB</string></value><value><string>CW</string></value>\
+             </data></array>",
+        )])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/modes")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["modes"], json!(["LSB", "USB", "CW"]));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_modes_unsupported_is_not_implemented() {
+        let flrig = MockFlrig::start(&[]).await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/modes")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_IMPLEMENTED);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_flrig_methods_reports_availability() {
+        let flrig = MockFlrig::start(&[(
+            "system.listMethods",
+            "<array><data><value><string>rig.get_vfo</string></value>\
+             <value><string>rig.set_vfo</string></value></data></array>",
+        )])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/flrig/methods"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["introspection"], true);
+        let methods = json["methods"].as_array().unwrap();
+        let available = |name: &str| {
+            methods
+                .iter()
+                .find(|m| m["method"] == name)
+                .unwrap_or_else(|| panic!("{name} not listed"))["available"]
+                .clone()
+        };
+        for name in [
+            "rig.get_vfo",
+            "rig.set_vfo",
+            "rig.get_mode",
+            "rig.set_mode",
+            "rig.get_power",
+            "rig.get_maxpwr",
+            "rig.set_bw",
+        ] {
+            assert_eq!(available(name), name.ends_with("_vfo"), "{name}");
         }
+        token.cancel();
     }
 
-    #[test]
-    fn yaesu_am_fm() {
-        let m = yaesu_mode_map();
-        assert_eq!(
-            wavelog_to_flrig_mode(7_200_000.0, WavelogMode::Am, &DEFAULT_FT8_FREQS, &m),
-            Mode::AM
-        );
-        assert_eq!(
-            wavelog_to_flrig_mode(29_600_000.0, WavelogMode::Fm, &DEFAULT_FT8_FREQS, &m),
-            Mode::FM
-        );
+    #[tokio::test]
+    async fn e2e_flrig_methods_without_introspection() {
+        let flrig = MockFlrig::start(&[]).await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/flrig/methods"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["introspection"], false);
+        assert_eq!(json["methods"][0]["available"], serde_json::Value::Null);
+        token.cancel();
     }
 
-    //////////////////////////////////////////////////////////////
-    // Tests for parse_qsy_path input validation
-    //////////////////////////////////////////////////////////////
+    #[tokio::test]
+    async fn e2e_power_up_and_down_clamp_at_the_ends() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_power", "<i4>100</i4>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_power", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
 
-    fn make_get(path: &str) -> Request<()> {
-        Request::builder().uri(path).body(()).unwrap()
+        // Already at maxpower: up stays there rather than failing.
+        let resp = reqwest::get(format!("{base}/power/up")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["power"], 100);
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/power/down"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["power"], 90);
+        let set_power = flrig.last_call("rig.set_power").unwrap();
+        assert!(set_power.contains("<i4>90</i4>"), "{set_power}");
+
+        // From 0, down stays at 0.
+        let flrig = MockFlrig::start(&[
+            ("rig.get_power", "<i4>0</i4>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_power", "<i4>0</i4>"),
+        ])
+        .await;
+        let base = start_cat(flrig.settings(), &token).await;
+        let json: serde_json::Value = reqwest::get(format!("{base}/power/down"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["power"], 0);
+        token.cancel();
     }
 
-    // --- Baseline: exact wire format Wavelog sends ---
+    #[tokio::test]
+    async fn e2e_qsy_reduces_power_to_mode_limit() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+            ("rig.get_power", "<i4>100</i4>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.set_power", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let limits = [("D-USB".to_string(), 30)].into_iter().collect();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                mode_power_limits: Some(limits),
+                enforce_mode_power: Some(true),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
 
-    #[test]
-    fn qsy_path_wavelog_wire_format_accepted() {
-        // Wavelog constructs the QSY URL as <cat_url>/<freq>/<mode>.
-        // This test documents and locks that format.  If parse_qsy_path ever
-        // stops accepting it, something has broken the Wavelog integration.
-        assert!(parse_qsy_path(&make_get("/14074000/usb")).is_ok());
-        assert!(parse_qsy_path(&make_get("/7074000/digi")).is_ok());
-        assert!(parse_qsy_path(&make_get("/3573000/cw")).is_ok());
+        let resp = reqwest::get(format!("{base}/14074000/digi")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let set_power = flrig.last_call("rig.set_power").expect("power not reduced");
+        assert!(set_power.contains("<i4>30</i4>"), "{set_power}");
+
+        // Phone has no limit, so the power is left alone.
+        let calls_before = flrig.methods().len();
+        reqwest::get(format!("{base}/14200000/phone"))
+            .await
+            .unwrap();
+        let later: Vec<String> = flrig.methods().split_off(calls_before);
+        assert!(!later.contains(&"rig.set_power".to_string()), "{later:?}");
+        token.cancel();
     }
 
-    // --- Malformed paths ---
+    #[tokio::test]
+    async fn e2e_qsy_reports_verification() {
+        // This rig stays on 14.2 MHz whatever it is told.
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14200000.000000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                verify_set: Some(true),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/14200000/phone"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["verified"], true);
+
+        let resp = reqwest::get(format!("{base}/14250000/phone"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["verified"], false);
+        assert_eq!(json["actual_frequency"], "14200000");
+        assert_eq!(json["actual_mode"], "USB");
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_qsy_omits_verification_by_default() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let json: serde_json::Value = reqwest::get(format!("{base}/14200000/phone"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(json["status"], "ok");
+        assert!(json.get("verified").is_none());
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn wsjtx_free_text_validates_query() {
+        let state = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let get = |uri: &str| Request::builder().uri(uri).body(()).unwrap();
+
+        let resp = wsjtx_free_text(&state, &get("/wsjtx/freetext")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = wsjtx_free_text(&state, &get("/wsjtx/freetext?text=CQ&send=maybe")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let long = "X".repeat(40);
+        let resp = wsjtx_free_text(&state, &get(&format!("/wsjtx/freetext?text={long}"))).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Valid, but no WSJT-X has been heard from.
+        let resp = wsjtx_free_text(&state, &get("/wsjtx/freetext?text=CQ%20TEST&send=true")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn wsjtx_configuration_validates_query() {
+        let state = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let get = |uri: &str| Request::builder().uri(uri).body(()).unwrap();
+
+        let resp = wsjtx_configuration(&state, &get("/wsjtx/configuration")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = wsjtx_configuration(&state, &get("/wsjtx/configuration?name=")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Valid, but no WSJT-X has been heard from.
+        let resp = wsjtx_configuration(&state, &get("/wsjtx/configuration?name=FT4")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn wsjtx_location_validates_grid() {
+        let state = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let get = |uri: &str| Request::builder().uri(uri).body(()).unwrap();
+
+        let resp = wsjtx_location(&state, &get("/wsjtx/location")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let resp = wsjtx_location(&state, &get("/wsjtx/location?grid=ZZ99")).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        // Valid, but no WSJT-X has been heard from.
+        let resp = wsjtx_location(&state, &get("/wsjtx/location?grid=IO91wm")).await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
 
     #[test]
-    fn qsy_path_single_segment_rejected() {
-        let req = Request::builder().uri("/14030000").body(()).unwrap();
-        assert!(parse_qsy_path(&req).is_err());
+    fn freq_step_snaps_to_nearest_multiple() {
+        assert_eq!(snap_to_step(14_074_013.0, 10), 14_074_010.0);
+        assert_eq!(snap_to_step(14_074_013.0, 1000), 14_074_000.0);
+        assert_eq!(snap_to_step(14_074_500.0, 1000), 14_075_000.0);
+        assert_eq!(snap_to_step(14_074_013.0, 0), 14_074_013.0);
     }
 
     #[test]
-    fn qsy_path_empty_rejected() {
-        let req = Request::builder().uri("/").body(()).unwrap();
-        assert!(parse_qsy_path(&req).is_err());
+    fn qsy_plan_reports_snapped_frequency() {
+        let mut state = test_state(String::new());
+        state.freq_step_hz = 1000;
+        let req = Request::builder().uri("/14074013/usb").body(()).unwrap();
+        let plan = plan_qsy(&state, &req).map_err(|_| "rejected").unwrap();
+        assert_eq!(plan.freq, 14_074_000.0);
+        assert_eq!(plan.unsnapped, Some(14_074_013.0));
+
+        state.freq_step_hz = 10;
+        let plan = plan_qsy(&state, &req).map_err(|_| "rejected").unwrap();
+        assert_eq!(plan.freq, 14_074_010.0);
     }
 
     #[test]
-    fn qsy_path_three_segments_rejected() {
-        assert!(parse_qsy_path(&make_get("/14030000/cw/extra")).is_err());
+    fn cw_dial_offset_only_on_cw_modes() {
+        assert_eq!(cw_dial_offset(Mode::CW, 700), -700.0);
+        assert_eq!(cw_dial_offset(Mode::CW_U, 700), -700.0);
+        assert_eq!(cw_dial_offset(Mode::CW_R, 700), 700.0);
+        assert_eq!(cw_dial_offset(Mode::CW_L, 700), 700.0);
+        for mode in [Mode::USB, Mode::LSB, Mode::RTTY, Mode::D_USB, Mode::FM] {
+            assert_eq!(cw_dial_offset(mode, 700), 0.0, "{mode}");
+        }
+        assert_eq!(cw_dial_offset(Mode::CW, 0), 0.0);
     }
 
-    // --- Frequency allowlist: out-of-band inputs rejected ---
+    #[tokio::test]
+    async fn e2e_qsy_applies_cw_pitch_to_cw_only() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>CW</string>"),
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(
+            flrig::FlrigSettings {
+                cw_pitch_hz: Some(700),
+                ..flrig.settings()
+            },
+            &token,
+        )
+        .await;
 
-    #[test]
-    fn qsy_rejects_zero_frequency() {
-        assert!(
-            parse_qsy_path(&make_get("/0/usb")).is_err(),
-            "frequency 0 Hz must be rejected"
-        );
+        reqwest::get(format!("{base}/14025000/cw")).await.unwrap();
+        let set_vfo = flrig.last_call("rig.set_vfo").unwrap();
+        assert!(set_vfo.contains("14024300"), "{set_vfo}");
+
+        reqwest::get(format!("{base}/14200000/phone"))
+            .await
+            .unwrap();
+        let set_vfo = flrig.last_call("rig.set_vfo").unwrap();
+        assert!(set_vfo.contains("14200000"), "{set_vfo}");
+        token.cancel();
     }
 
     #[test]
-    fn qsy_rejects_broadcast_band_frequency() {
-        // 909 kHz is an AM broadcast frequency, not an amateur allocation.
-        assert!(
-            parse_qsy_path(&make_get("/909000/usb")).is_err(),
-            "broadcast-band frequency 909 kHz must be rejected"
-        );
+    fn parse_sat_path_splits_rx_and_tx() {
+        let sat = parse_sat_path("/145900000/fm/435800000/fm", &ModeAliases::default()).unwrap();
+        assert_eq!(sat.rx.freq, 145_900_000.0);
+        assert_eq!(sat.rx.mode, WavelogMode::Fm);
+        assert_eq!(sat.tx.freq, 435_800_000.0);
+        assert_eq!(sat.tx.mode, WavelogMode::Fm);
+
+        let sat = parse_sat_path("435600000/usb/145900000/lsb", &ModeAliases::default()).unwrap();
+        assert_eq!(sat.rx.mode, WavelogMode::USB);
+        assert_eq!(sat.tx.mode, WavelogMode::LSB);
     }
 
     #[test]
-    fn qsy_rejects_max_u32_frequency() {
-        // 4,294,967,295 Hz (~4.3 GHz) is not an amateur allocation.
-        assert!(
-            parse_qsy_path(&make_get("/4294967295/usb")).is_err(),
-            "out-of-range frequency 4294967295 Hz must be rejected"
-        );
+    fn parse_sat_path_rejects_bad_segments() {
+        for path in [
+            "/145900000/fm/435800000",
+            "/145900000/fm/435800000/fm/extra",
+            "/145900000/fm/435800000/wibble",
+            "/145900000/fm/abc/fm",
+            "/145900000/fm/1300000000/fm",
+        ] {
+            let err = parse_sat_path(path, &ModeAliases::default()).expect_err(path);
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST, "{path}");
+        }
     }
 
-    #[test]
-    fn qsy_rejects_between_bands() {
-        // 11 MHz falls between 30m (10.15 MHz) and 20m (14.0 MHz).
-        assert!(
-            parse_qsy_path(&make_get("/11000000/usb")).is_err(),
-            "inter-band frequency 11 MHz must be rejected"
+    #[tokio::test]
+    async fn e2e_sat_sets_both_vfos() {
+        let flrig = MockFlrig::start(&[
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.get_mode", "<string>FM</string>"),
+            ("rig.set_vfoB", "<i4>0</i4>"),
+            ("rig.set_modeB", "<i4>0</i4>"),
+            ("rig.get_modeB", "<string>FM</string>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/sat/145900000/fm/435800000/fm"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["rx"]["frequency"], 145_900_000.0);
+        assert_eq!(json["tx"]["vfo"], "B");
+        assert_eq!(json["tx"]["mode"], "FM");
+        assert!(!flrig.methods().iter().any(|m| m.contains("split")));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_sat_shared_mode_is_not_implemented() {
+        let flrig = MockFlrig::start(&[
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_vfoB", "<i4>0</i4>"),
+            ("rig.set_modeB", "<i4>0</i4>"),
+            ("rig.get_modeB", "<string>USB</string>"),
+        ])
+        .await;
+        let token = CancellationToken::new();
+        let base = start_cat(flrig.settings(), &token).await;
+
+        let resp = reqwest::get(format!("{base}/sat/435600000/usb/145900000/lsb"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 501);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn e2e_refresh_reads_rig_and_uploads_now() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>7074000</string>"),
+            ("rig.get_mode", "<string>D-USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>25</i4>"),
+        ])
+        .await;
+        let (tx, mut uploads) = tokio::sync::mpsc::unbounded_channel();
+        let token = CancellationToken::new();
+
+        // A poll interval long enough that only /refresh can cause an upload.
+        let mut wavelog = test_state("http://127.0.0.1:19999/api/qso".to_string()).wavelog;
+        wavelog.url = mock_wavelog(StatusCode::OK, tx).await;
+        wavelog.interval = 3_600_000;
+        let live = Arc::new(RwLock::new(LiveSettings::new(
+            &wavelog,
+            &flrig.settings(),
+            &e2e_cat_settings(),
+        )));
+        let rig = Arc::new(flrig::FLRig::new(flrig.settings(), "test-rig".to_string()));
+        let (refresh_tx, refresh_rx) = tokio::sync::mpsc::channel(1);
+        crate::wavelog::wavelog_thread(
+            wavelog,
+            rig.clone(),
+            live.clone(),
+            token.clone(),
+            RigPublishers {
+                ws_tx: tokio::sync::watch::channel(None).0,
+                updates: broadcast::channel(16).0,
+            },
+            refresh_rx,
+            RadioCache::default(),
+            SharedLatency::default(),
         );
+        let base = serve_handles(
+            CatHandles {
+                rig,
+                live,
+                wsjtx: WsjtxLink::default(),
+                refresh: refresh_tx,
+                radio_cache: RadioCache::default(),
+                updates: broadcast::channel(16).0,
+                latency: SharedLatency::default(),
+            },
+            &token,
+        )
+        .await;
+
+        let resp = reqwest::get(format!("{base}/refresh")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(json["frequency"], 7_074_000);
+        assert_eq!(json["mode"], "USB");
+        assert_eq!(json["uploaded"], true);
+
+        let uploaded: serde_json::Value =
+            serde_json::from_str(&uploads.recv().await.unwrap()).unwrap();
+        assert_eq!(uploaded["frequency"], "7074000");
+        assert_eq!(uploaded["power"], "25");
+        token.cancel();
     }
 
-    // --- Frequency allowlist: valid in-band inputs accepted ---
+    #[tokio::test]
+    async fn refresh_without_poll_loop_is_unavailable() {
+        let state = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        assert_eq!(
+            refresh(&state).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
 
-    #[test]
-    fn qsy_accepts_valid_hf_frequencies() {
-        let valid = [
-            "/1840000/usb",  // 160m
-            "/3573000/usb",  // 80m FT8
-            "/7074000/usb",  // 40m FT8
-            "/10136000/usb", // 30m FT8
-            "/14074000/usb", // 20m FT8
-            "/18100000/usb", // 17m FT8
-            "/21074000/usb", // 15m FT8
-            "/24915000/usb", // 12m FT8
-            "/28074000/usb", // 10m FT8
-            "/50313000/usb", // 6m FT8
-        ];
-        for path in valid {
-            assert!(
-                parse_qsy_path(&make_get(path)).is_ok(),
-                "expected Ok for {path}"
-            );
+    fn rig_at(frequency: &str, mode: &str) -> RadioData {
+        RadioData {
+            key: "secret".to_string(),
+            radio: String::new(),
+            frequency: frequency.to_string(),
+            mode: mode.to_string(),
+            rig_mode: mode.to_string(),
+            power: "25".to_string(),
+            cat_url: None,
+            my_grid: None,
         }
     }
 
-    // --- is_amateur_frequency: band edge boundary checks ---
+    // The next /ws message, as JSON.
+    async fn next_json<S>(ws: &mut WebSocketStream<S>) -> serde_json::Value
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let msg = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("no message")
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(msg.to_text().unwrap()).unwrap()
+    }
 
-    #[test]
-    fn amateur_frequency_band_edges() {
-        // Lower and upper edges of each band must be accepted (inclusive).
-        for &(lo, hi) in AMATEUR_BANDS_HZ {
-            assert!(
-                is_amateur_frequency(lo),
-                "{lo} Hz (band lower edge) should be accepted"
-            );
-            assert!(
-                is_amateur_frequency(hi),
-                "{hi} Hz (band upper edge) should be accepted"
+    // The next /ws message as JSON, less its timestamp.
+    async fn next_untimed<S>(ws: &mut WebSocketStream<S>) -> serde_json::Value
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        let mut msg = next_json(ws).await;
+        assert!(msg["timestamp"].as_u64().is_some(), "{msg}");
+        msg.as_object_mut().unwrap().remove("timestamp");
+        msg
+    }
+
+    // CAT handles with a fresh cached read at 7.074 MHz USB, so a /ws
+    // snapshot needs no FLRig, and the sender for rig changes.
+    fn ws_handles() -> (CatHandles, RigUpdates) {
+        let test = test_state("http://127.0.0.1:19999/api/qso".to_string());
+        let (updates, _) = broadcast::channel(16);
+        let radio_cache = RadioCache::default();
+        radio_cache.store(radio_cache.generation(), &rig_at("7074000", "USB"));
+        let handles = CatHandles {
+            rig: test.rig,
+            live: test.live,
+            wsjtx: WsjtxLink::default(),
+            refresh: tokio::sync::mpsc::channel(1).0,
+            radio_cache,
+            updates: updates.clone(),
+            latency: SharedLatency::default(),
+        };
+        (handles, updates)
+    }
+
+    #[tokio::test]
+    async fn ws_subscribers_get_a_snapshot_then_deltas() {
+        use tokio_tungstenite::connect_async;
+
+        let token = CancellationToken::new();
+        let (handles, updates) = ws_handles();
+        let base = serve_handles(handles, &token).await;
+        let url = base.replacen("http", "ws", 1) + "/ws";
+
+        let (mut first, _) = connect_async(&url).await.unwrap();
+        let (mut second, _) = connect_async(&url).await.unwrap();
+        for ws in [&mut first, &mut second] {
+            assert_eq!(
+                next_untimed(ws).await,
+                json!({
+                    "type": "radio_status",
+                    "frequency": 7_074_000,
+                    "mode": "USB",
+                    "power": 25.0,
+                    "radio": "test-rig",
+                })
             );
         }
-    }
 
-    #[test]
-    fn amateur_frequency_outside_all_bands_rejected() {
-        let out_of_band = [
-            0,             // zero
-            100_000,       // below 136 kHz band
-            500_000,       // 500 kHz (between 136 kHz and 160m)
-            472_000,       // 472 kHz (Full-only, excluded by policy)
-            5_000_000,     // 5 MHz (Full-only specialist channels, excluded by policy)
-            11_000_000,    // between 30m and 20m
-            200_000_000,   // between 70cm and anything above
-            4_294_967_295, // max u32
-        ];
-        for freq in out_of_band {
-            assert!(!is_amateur_frequency(freq), "{freq} Hz should be rejected");
+        // The poll loop sees the rig move to 20m; mode and power stay.
+        assert_eq!(
+            updates.send(Arc::new(rig_at("14074000", "USB"))).ok(),
+            Some(2)
+        );
+        for ws in [&mut first, &mut second] {
+            assert_eq!(
+                next_untimed(ws).await,
+                json!({ "type": "radio_status_delta", "frequency": 14_074_000 })
+            );
         }
+
+        // One subscriber leaving does not disturb the other.
+        first.close(None).await.unwrap();
+        assert!(updates.send(Arc::new(rig_at("14074000", "CW"))).is_ok());
+        let delta = next_untimed(&mut second).await;
+        assert_eq!(delta, json!({ "type": "radio_status_delta", "mode": "CW" }));
+        token.cancel();
     }
 
-    //////////////////////////////////////////////////////////////
-    // Tests for check_origin
-    //////////////////////////////////////////////////////////////
+    #[tokio::test]
+    async fn ws_streams_wsjtx_status() {
+        use tokio_tungstenite::connect_async;
 
-    #[test]
-    fn origin_correct_accepted() {
-        let req = Request::builder()
-            .header("origin", "https://wavelog.example.org")
-            .uri("/14074000/usb")
-            .body(())
+        let token = CancellationToken::new();
+        let (handles, _updates) = ws_handles();
+        let wsjtx = handles.wsjtx.clone();
+        let base = serve_handles(handles, &token).await;
+        let (mut ws, _) = connect_async(base.replacen("http", "ws", 1) + "/ws")
+            .await
             .unwrap();
-        assert!(check_origin(&req, "https://wavelog.example.org"));
+        assert_eq!(next_untimed(&mut ws).await["type"], "radio_status");
+
+        wsjtx.publish_status(WsjtxStatusUpdate {
+            id: "WSJT-X".to_string(),
+            dial_frequency: 14_074_000,
+            mode: "FT8".to_string(),
+            tx_mode: "FT8".to_string(),
+            dx_call: "G4ABC".to_string(),
+            report: "-10".to_string(),
+            tx_enabled: true,
+            transmitting: false,
+            decoding: true,
+        });
+        let status = next_untimed(&mut ws).await;
+        assert_eq!(status["type"], "wsjtx_status");
+        assert_eq!(status["dx_call"], "G4ABC");
+        assert_eq!(status["tx_enabled"], true);
+        token.cancel();
     }
 
-    #[test]
-    fn origin_wrong_rejected() {
-        let req = Request::builder()
-            .header("origin", "https://evil.example.com")
-            .uri("/14074000/usb")
-            .body(())
+    #[tokio::test]
+    async fn ws_stream_holds_a_connection_until_shutdown() {
+        use tokio_tungstenite::connect_async;
+
+        let token = CancellationToken::new();
+        let settings = CatSettings {
+            max_connections: Some(1),
+            ..e2e_cat_settings()
+        };
+        let (handles, _updates) = ws_handles();
+        let base = serve_settings_handles(settings, handles, &token).await;
+        let (mut ws, _) = connect_async(base.replacen("http", "ws", 1) + "/ws")
+            .await
             .unwrap();
-        assert!(!check_origin(&req, "https://wavelog.example.org"));
+        assert_eq!(next_untimed(&mut ws).await["type"], "radio_status");
+
+        // The stream still counts against max_connections after the upgrade.
+        let addr = base.trim_start_matches("http://");
+        let mut other = tokio::net::TcpStream::connect(addr).await.unwrap();
+        expect_closed(&mut other, Duration::from_secs(5)).await;
+
+        token.cancel();
+        let closed = tokio::time::timeout(Duration::from_secs(5), ws.next())
+            .await
+            .expect("stream not closed on shutdown");
+        assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
     }
 
-    #[test]
-    fn origin_missing_rejected() {
-        let req = Request::builder().uri("/14074000/usb").body(()).unwrap();
-        assert!(!check_origin(&req, "https://wavelog.example.org"));
+    #[tokio::test]
+    async fn ws_without_upgrade_is_bad_request() {
+        let token = CancellationToken::new();
+        let addr = serve_with(e2e_cat_settings(), &token).await;
+        let resp = reqwest::get(format!("http://{addr}/ws")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn api_lists_the_routes() {
+        let token = CancellationToken::new();
+        let addr = serve_with(e2e_cat_settings(), &token).await;
+        let resp = reqwest::get(format!("http://{addr}/api")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let json: serde_json::Value = resp.json().await.unwrap();
+        let routes = json["routes"].as_array().unwrap();
+        assert!(routes.contains(&json!({
+            "method": "ANY",
+            "path": "/{freq}/{mode}",
+            "description": find_route(&Method::GET, "/1/usb").unwrap().0.description,
+        })));
+        assert!(routes
+            .iter()
+            .any(|r| r["method"] == "POST" && r["path"] == "/qso"));
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn unknown_path_is_404_but_bad_qsy_is_400() {
+        let token = CancellationToken::new();
+        let addr = serve_with(e2e_cat_settings(), &token).await;
+        let get = |path: &'static str| async move {
+            let resp = reqwest::get(format!("http://{addr}{path}")).await.unwrap();
+            let status = resp.status();
+            let json: serde_json::Value = resp.json().await.unwrap();
+            (status, json["message"].as_str().unwrap().to_string())
+        };
+
+        assert_eq!(
+            get("/no/such/route").await,
+            (StatusCode::NOT_FOUND, "Not found".to_string())
+        );
+        assert_eq!(get("/nope").await.0, StatusCode::NOT_FOUND);
+        assert_eq!(
+            get("/abc/usb").await,
+            (
+                StatusCode::BAD_REQUEST,
+                "Frequency must be a positive integer".to_string()
+            )
+        );
+        assert_eq!(get("/14074000/xyz").await.0, StatusCode::BAD_REQUEST);
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn wrong_method_on_a_known_path_is_405() {
+        let token = CancellationToken::new();
+        let addr = serve_with(e2e_cat_settings(), &token).await;
+        let resp = reqwest::get(format!("http://{addr}/qso")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(resp.headers()["allow"], "POST");
+        token.cancel();
     }
 }
@@ -1,17 +1,27 @@
+use crate::bandmem::BandModeMemory;
+use crate::latency::SharedLatency;
+use crate::rigstate::{RigStateFile, SavedRigState};
 use crate::wavelog::RadioData;
 use log::{debug, info, warn};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::result::Result;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-use dxr::TryFromValue;
+use dxr::{TryFromValue, TryToParams};
 use dxr_client::{Client, ClientBuilder, ClientError};
 use url::Url;
 
 // Settings from .toml file
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FlrigSettings {
+    /// FLRig's host, normally with its "http://".  A host given without a
+    /// scheme gets "http://", and a port written into it is replaced by
+    /// `port`.
     pub host: String,
     pub port: u16,
     pub maxpower: u32,
@@ -19,15 +29,359 @@ pub struct FlrigSettings {
     /// This is NOT a value in Hz.  See CLAUDE.md for the IC-703 FLRig bug that
     /// makes index 1 (labelled "MED") the correct choice for the narrow filter.
     pub cw_bw_index: Option<u32>,
+    /// Bandwidth table index to apply after switching into a mode, keyed by
+    /// FLRig mode name, e.g. `{ "D-USB" = 3 }` for the separate data-mode
+    /// filter on Icom rigs.  Applied with `rig.set_bw` exactly like
+    /// `cw_bw_index`, which is shorthand for a `CW` entry here.
+    pub mode_bw_index: Option<HashMap<String, u32>>,
+    /// Milliseconds to wait after `rig.set_mode` before applying the
+    /// bandwidth index, so the rig has settled into the new mode and does
+    /// not ignore it.  Defaults to 100, enough for the IC-703.
+    pub narrow_delay_ms: Option<u64>,
+    /// FLRig XML-RPC call that switches the rig on, for rigs that support
+    /// remote power.  FLRig has no portable power method, so this is left to
+    /// the user; absent means remote power-on is unsupported.
+    pub power_on: Option<FlrigCommand>,
+    /// As `power_on`, but switches the rig off.
+    pub power_off: Option<FlrigCommand>,
+    /// How often to poll FLRig, in milliseconds.  Falls back to
+    /// `[wavelog] interval` when absent.
+    pub poll_interval_ms: Option<u64>,
+    /// Include noise blanker / noise reduction / notch state in the CAT
+    /// /status response.  Off by default: it costs an extra FLRig round-trip
+    /// per request and not every rig reports these.
+    pub report_dsp: Option<bool>,
+    /// Include the filter width and passband tuning (PBT) settings in the
+    /// CAT /status response.  Off by default, for the same reasons as
+    /// `report_dsp`.
+    pub report_passband: Option<bool>,
+    /// Include the S-meter reading in the CAT /status response.  Off by
+    /// default: one more FLRig round-trip per request.
+    pub report_smeter: Option<bool>,
+    /// Read the rig's PTT for the `transmitting` field of the CAT /status
+    /// response, rather than relying on WSJT-X alone.  Off by default: one
+    /// more FLRig round-trip per request.
+    pub report_ptt: Option<bool>,
+    /// Map from FLRig's raw S-meter reading to dB relative to S9, as
+    /// `[raw, dB]` breakpoints, e.g. `[[0, -54], [50, 0], [100, 60]]` (the
+    /// default, FLRig's nominal scale).  Readings between breakpoints are
+    /// interpolated; outside them, the end value is used.
+    pub smeter_calibration: Option<Vec<[f64; 2]>>,
+    /// Set false for rigs (or FLRig back-ends) with no data mode.  FT8 and
+    /// other digital QSYs then use plain USB instead of D-USB/DATA-U etc.
+    /// Defaults to true.
+    pub use_data_mode: Option<bool>,
+    /// Power ceiling in watts per FLRig mode, e.g. `{ "D-USB" = 30, RTTY = 30 }`
+    /// to protect the finals during high duty-cycle digital transmission.
+    /// Consulted whenever wlrigctl sets the power.
+    pub mode_power_limits: Option<HashMap<String, u32>>,
+    /// When true, a QSY into a mode with a power ceiling also turns the power
+    /// down to that ceiling if it is currently higher.
+    pub enforce_mode_power: Option<bool>,
+    /// CW pitch in Hz.  Spots give the signal frequency; on a QSY to CW the
+    /// dial is set this far below it (above it for CW-R/CW-L) so the signal
+    /// is heard at this pitch.  Defaults to 0 (no offset), which suits rigs
+    /// that already display the signal frequency in CW.
+    pub cw_pitch_hz: Option<u32>,
+    /// Read the frequency and mode from FLRig's one-line `rig.get_info`
+    /// summary instead of `rig.get_vfo` and `rig.get_mode`.  Falls back to
+    /// the separate calls for good if this FLRig does not answer it in the
+    /// expected format.  Defaults to false.
+    pub use_get_info: Option<bool>,
+    /// After a QSY, read the frequency and mode back from FLRig and report
+    /// in the CAT answer whether the rig actually took them.  Costs one more
+    /// FLRig round-trip per QSY.  Defaults to false.
+    pub verify_set: Option<bool>,
+    /// A second FLRig to use while this one is unreachable, e.g. on another
+    /// PC connected to the same rig.  Include the "http://", as for `host`.
+    pub backup_host: Option<String>,
+    /// Port of the backup FLRig.  Defaults to `port`.
+    pub backup_port: Option<u16>,
+    /// Calls in a row that must fail to reach the primary FLRig before
+    /// switching to the backup.  Defaults to 3.
+    pub failover_after: Option<u32>,
+    /// Also read VFO B whenever the poll loop sees the rig change, and log
+    /// its frequency when that moves, for watching split operation.  Not
+    /// uploaded.  Defaults to false.
+    pub log_vfob: Option<bool>,
+    /// At startup, tune back to the frequency and mode the rig was last
+    /// seen on, as saved in `state_file`, e.g. after a power cut at an
+    /// unattended station.  Commands the rig at boot, so off by default.
+    pub restore_on_start: Option<bool>,
+    /// Where the last frequency and mode are saved for `restore_on_start`.
+    /// Defaults to rig-state.json in the wlrigctl config directory.
+    pub state_file: Option<String>,
+    /// Take `rig.get_power` as watts rather than a fraction of
+    /// `rig.get_maxpwr`, for rig back-ends that report the power setting
+    /// directly in watts.  Readings are then only capped at `maxpower`.
+    /// Defaults to false.  See the `maxpower` notes in README.md for how to
+    /// tell which a rig does.
+    pub power_is_watts: Option<bool>,
+    /// State file remembering the last voice mode the rig was seen in on
+    /// each band.  When set, a "phone" QSY uses the remembered mode instead
+    /// of guessing LSB/USB from the frequency.
+    /// Example: band_mode_file = "/home/me/.config/wlrigctl/band-modes.json"
+    pub band_mode_file: Option<String>,
+}
+
+/// An arbitrary FLRig XML-RPC call: a method name with an optional single
+/// string argument, e.g. `{ method = "rig.cat_string", arg = "..." }`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FlrigCommand {
+    pub method: String,
+    pub arg: Option<String>,
 }
 
 // Internal state
 #[allow(non_snake_case)]
 pub struct FLRig {
     maxpower: u32, // Watts
+    // rig.get_power is already watts, not a fraction of rig.get_maxpwr.
+    power_is_watts: bool,
     client: Client,
     identifier: String,
     cw_bw_index: Option<u32>,
+    mode_bw_index: Vec<(Mode, u32)>,
+    narrow_delay: Duration,
+    band_memory: Option<BandModeMemory>,
+    state_file: Option<RigStateFile>,
+    power_on: Option<FlrigCommand>,
+    power_off: Option<FlrigCommand>,
+    report_dsp: bool,
+    report_passband: bool,
+    report_smeter: bool,
+    // report_ptt, cleared the first time the rig turns rig.get_ptt down.
+    report_ptt: AtomicBool,
+    verify_set: bool,
+    smeter_calibration: SmeterCalibration,
+    // Replaced wholesale on a SIGHUP config reload.
+    power_limits: RwLock<PowerLimits>,
+    // The backup FLRig and which of the two calls go to, when configured.
+    failover: Option<Failover>,
+    // Calls in a row that could not reach FLRig, whoever made them.
+    failures: AtomicU32,
+    // Round-trip times of FLRig calls, for /stats; see `with_latency`.
+    latency: SharedLatency,
+    // use_get_info, cleared the first time rig.get_info lets us down.
+    get_info: AtomicBool,
+    // Held across a multi-call tuning sequence (frequency, mode, filter) so
+    // concurrent QSYs cannot interleave their calls.  Reads do not take it.
+    tuning: tokio::sync::Mutex<()>,
+    // What log_vfob has logged so far; None when log_vfob is off.
+    vfo_b_log: Option<Mutex<VfoBLog>>,
+    // Why maxpower looks wrong for this rig, from the latest reading.
+    maxpower_warning: Mutex<Option<String>>,
+}
+
+// The last thing logged about VFO B, so only changes are logged.
+#[derive(Debug, PartialEq)]
+enum VfoBLog {
+    Nothing,
+    Frequency(String),
+    // The rig has no VFO B that FLRig can read; never asked again.
+    Unsupported,
+}
+
+// Every FLRig XML-RPC method wlrigctl may call, reported by the CAT
+// /flrig/methods route.  Add new calls here too.  The configured power_on and
+// power_off methods are added at runtime.
+const USED_METHODS: &[&str] = &[
+    "main.get_version",
+    "rig.get_update",
+    "rig.get_info",
+    "rig.get_vfo",
+    "rig.set_vfo",
+    "rig.get_vfoB",
+    "rig.set_vfoB",
+    "rig.get_mode",
+    "rig.set_mode",
+    "rig.get_modeB",
+    "rig.set_modeB",
+    "rig.get_modes",
+    "rig.get_power",
+    "rig.set_power",
+    "rig.get_maxpwr",
+    "rig.get_bw",
+    "rig.set_bw",
+    "rig.get_pbt_inner",
+    "rig.get_pbt_outer",
+    "rig.get_noise",
+    "rig.get_nr",
+    "rig.get_notch",
+    "rig.get_smeter",
+    "rig.get_ptt",
+];
+
+// Default [flrig] narrow_delay_ms.
+const DEFAULT_NARROW_DELAY_MS: u64 = 100;
+
+const DEFAULT_FAILOVER_AFTER: u32 = 3;
+
+// How often, while on the backup FLRig, a call is tried on the primary first
+// to see whether it has come back.
+const PRIMARY_RECHECK: Duration = Duration::from_secs(30);
+
+// Which FLRig instance a call is sent to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Target {
+    Primary,
+    Backup,
+}
+
+// A backup FLRig, switched to after `after` calls in a row fail to reach the
+// primary and switched away from as soon as the primary answers again.
+struct Failover {
+    client: Client,
+    after: u32,
+    primary_failures: AtomicU32,
+    on_backup: AtomicBool,
+    // When the primary was last tried while on the backup.
+    last_recheck: Mutex<Instant>,
+    recheck: Duration,
+}
+
+impl Failover {
+    fn new(settings: &FlrigSettings) -> Option<Failover> {
+        let host = settings.backup_host.as_ref()?;
+        let port = settings.backup_port.unwrap_or(settings.port);
+        info!("Backup FLRig: {host}:{port}");
+        Some(Failover {
+            client: ClientBuilder::new(checked_flrig_url(host, port)).build(),
+            after: settings
+                .failover_after
+                .unwrap_or(DEFAULT_FAILOVER_AFTER)
+                .max(1),
+            primary_failures: AtomicU32::new(0),
+            on_backup: AtomicBool::new(false),
+            last_recheck: Mutex::new(Instant::now()),
+            recheck: PRIMARY_RECHECK,
+        })
+    }
+
+    // Where the next call should go: the backup once failed over, except for
+    // one call every `recheck` that tries the primary again.
+    fn target(&self) -> Target {
+        if !self.on_backup.load(Ordering::Relaxed) {
+            return Target::Primary;
+        }
+        let mut last = self.last_recheck.lock().unwrap();
+        if last.elapsed() >= self.recheck {
+            *last = Instant::now();
+            return Target::Primary;
+        }
+        Target::Backup
+    }
+
+    // Note the outcome of a call to `target`.  Returns true when the call
+    // should be repeated on the backup: the primary did not answer and we
+    // are, or have just become, failed over.
+    fn observe<R>(&self, target: Target, result: &Result<R, ClientError>) -> bool {
+        if target == Target::Backup {
+            return false;
+        }
+        if !matches!(result, Err(ClientError::Net { .. })) {
+            self.primary_failures.store(0, Ordering::Relaxed);
+            if self.on_backup.swap(false, Ordering::Relaxed) {
+                info!("Primary FLRig is answering again; switching back from the backup");
+            }
+            return false;
+        }
+        let failures = self.primary_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.on_backup.load(Ordering::Relaxed) {
+            return true;
+        }
+        if failures >= self.after {
+            warn!("Primary FLRig failed {failures} calls in a row; switching to the backup");
+            self.on_backup.store(true, Ordering::Relaxed);
+            *self.last_recheck.lock().unwrap() = Instant::now();
+            return true;
+        }
+        false
+    }
+}
+
+/// The XML-RPC URL of the FLRig at `host` and `port`.  `host` may leave out
+/// the "http://", and a port typed into it as well ("localhost:12345") is
+/// replaced by `port`, with a warning when the two differ.
+pub fn flrig_url(host: &str, port: u16) -> Result<Url, String> {
+    let host = host.trim();
+    let with_scheme = if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("http://{host}")
+    };
+    let mut url = Url::parse(&with_scheme)
+        .map_err(|e| format!("FLRig host '{host}' is not a host name or address: {e}"))?;
+    if !matches!(url.scheme(), "http" | "https") || url.path() != "/" {
+        return Err(format!(
+            "FLRig host '{host}' should be a host such as \"http://127.0.0.1\", without a path"
+        ));
+    }
+    if let Some(given) = url.port().filter(|&given| given != port) {
+        warn!("FLRig host '{host}' names port {given}; using port {port} from the config");
+    }
+    url.set_port(Some(port))
+        .map_err(|()| format!("FLRig host '{host}' cannot take a port"))?;
+    Ok(url)
+}
+
+// As flrig_url, for settings already checked by FlrigSettings::validate.
+fn checked_flrig_url(host: &str, port: u16) -> Url {
+    flrig_url(host, port).unwrap_or_else(|e| panic!("{e}"))
+}
+
+impl FlrigSettings {
+    /// Check that `host` and `backup_host` make usable FLRig URLs, so a bad
+    /// one stops wlrigctl at startup rather than on the first call.
+    pub fn validate(&self) -> Result<(), String> {
+        flrig_url(&self.host, self.port)?;
+        if let Some(host) = &self.backup_host {
+            flrig_url(host, self.backup_port.unwrap_or(self.port))?;
+        }
+        Ok(())
+    }
+}
+
+// Per-mode power ceilings and whether a QSY enforces them.
+struct PowerLimits {
+    caps: Vec<(Mode, u32)>,
+    enforce: bool,
+}
+
+impl PowerLimits {
+    fn new(settings: &FlrigSettings) -> PowerLimits {
+        PowerLimits {
+            caps: parse_mode_power_limits(settings.mode_power_limits.as_ref()),
+            enforce: settings.enforce_mode_power.unwrap_or(false),
+        }
+    }
+}
+
+/// On/off state of the rig's DSP features.  A field is None when the rig (or
+/// its FLRig back-end) does not report it, and is then omitted from JSON.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct DspState {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nb: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nr: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notch: Option<bool>,
+}
+
+/// The rig's receive filter setup.  As with `DspState`, a field the rig does
+/// not report is None and omitted from JSON.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct PassbandState {
+    /// Filter width as FLRig shows it: Hz on most rigs, a filter name
+    /// (e.g. "FIL2") on some.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<String>,
+    /// Inner and outer passband tuning, in the rig's own units.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pbt_inner: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pbt_outer: Option<i32>,
 }
 
 #[derive(Debug)]
@@ -47,6 +401,7 @@ impl std::error::Error for UnknownModeError {}
 pub enum FlrigError {
     DxrClient(ClientError),
     UnknownMode(UnknownModeError),
+    Unsupported(String),
 }
 
 impl fmt::Display for FlrigError {
@@ -54,6 +409,7 @@ impl fmt::Display for FlrigError {
         match self {
             FlrigError::DxrClient(err) => write!(f, "DxrClient error: {}", err),
             FlrigError::UnknownMode(err) => write!(f, "UnknownMode error: {}", err),
+            FlrigError::Unsupported(what) => write!(f, "Unsupported: {}", what),
         }
     }
 }
@@ -192,6 +548,9 @@ pub struct ModeMap {
     pub cw: Mode,
     pub rtty: Mode,
     pub digital: Mode,
+    /// Mode for a generic "digi" click outside every FT8 window; `rtty`
+    /// when None.
+    pub digi_fallback: Option<Mode>,
 }
 
 /// Build a [`ModeMap`] from the optional mode strings supplied in the [CAT]
@@ -204,22 +563,11 @@ pub struct ModeMap {
 /// | rtty    | `RTTY`  |
 /// | digital | `D-USB` |
 pub fn build_mode_map(cw: Option<&str>, rtty: Option<&str>, digital: Option<&str>) -> ModeMap {
-    fn resolve(s: Option<&str>, default: Mode, field: &str) -> Mode {
-        match s {
-            None => default,
-            Some(name) => match name.parse::<Mode>() {
-                Ok(m) => m,
-                Err(_) => {
-                    warn!("Unrecognised mode '{name}' for {field}; using default '{default}'");
-                    default
-                }
-            },
-        }
-    }
     let map = ModeMap {
-        cw: resolve(cw, Mode::CW, "cw_mode"),
-        rtty: resolve(rtty, Mode::RTTY, "rtty_mode"),
-        digital: resolve(digital, Mode::D_USB, "digital_mode"),
+        cw: resolve_mode(cw, Mode::CW, "cw_mode"),
+        rtty: resolve_mode(rtty, Mode::RTTY, "rtty_mode"),
+        digital: resolve_mode(digital, Mode::D_USB, "digital_mode"),
+        digi_fallback: None,
     };
     info!(
         "Mode map: CW='{}' RTTY='{}' Digital='{}'",
@@ -228,6 +576,52 @@ pub fn build_mode_map(cw: Option<&str>, rtty: Option<&str>, digital: Option<&str
     map
 }
 
+// Parse a configured mode name, falling back to `default` with a warning if
+// it is not one FLRig reports.
+fn resolve_mode(s: Option<&str>, default: Mode, field: &str) -> Mode {
+    match s {
+        None => default,
+        Some(name) => match name.parse::<Mode>() {
+            Ok(m) => m,
+            Err(_) => {
+                warn!("Unrecognised mode '{name}' for {field}; using default '{default}'");
+                default
+            }
+        },
+    }
+}
+
+impl ModeMap {
+    /// The same map with generic digital clicks outside the FT8 windows
+    /// sent as `mode` (`[CAT] default_digi_mode`) instead of RTTY.
+    pub fn with_digi_fallback(self, mode: Option<&str>) -> ModeMap {
+        let Some(name) = mode else {
+            return self;
+        };
+        let fallback = resolve_mode(Some(name), self.rtty, "default_digi_mode");
+        info!("Digital clicks outside the FT8 windows will use '{fallback}'");
+        ModeMap {
+            digi_fallback: Some(fallback),
+            ..self
+        }
+    }
+
+    /// The mode for a generic digital click outside the FT8 windows.
+    pub fn digi_outside_ft8(&self) -> Mode {
+        self.digi_fallback.unwrap_or(self.rtty)
+    }
+
+    /// The same map with digital QSYs sent as plain USB, for rigs that have
+    /// no data mode (`[flrig] use_data_mode = false`).
+    pub fn without_data_mode(self) -> ModeMap {
+        info!("No data mode on this rig: digital QSYs will use USB");
+        ModeMap {
+            digital: Mode::USB,
+            ..self
+        }
+    }
+}
+
 // Returns the bandwidth index to pass to set_narrow when entering CW mode,
 // or None when no filter adjustment is needed.  Extracted as a pure function
 // so the logic can be tested without touching the async XMLRPC path.
@@ -235,26 +629,426 @@ fn cw_narrow_index(mode: Mode, cw_bw_index: Option<u32>) -> Option<u32> {
     cw_bw_index.filter(|_| mode == Mode::CW)
 }
 
+// The bandwidth index to apply after switching into `mode`: its mode_bw_index
+// entry if there is one, otherwise cw_bw_index for CW.  FLRig's rig.set_bw
+// indexes the bandwidth table of the mode currently selected, so an entry for
+// a data mode picks from that mode's own filter list (see rig.get_bws with
+// the rig in that mode).
+fn mode_bw_index(mode: Mode, table: &[(Mode, u32)], cw_bw_index: Option<u32>) -> Option<u32> {
+    table
+        .iter()
+        .find(|(m, _)| *m == mode)
+        .map(|&(_, idx)| idx)
+        .or_else(|| cw_narrow_index(mode, cw_bw_index))
+}
+
+// rig.get_bw answers with two strings: the filter width and, on rigs with
+// separate high/low cut controls, the second edge ("" otherwise).  The width
+// is what a display wants; an empty one means the rig has no reading.
+fn bandwidth_label(bw: &[String]) -> Option<String> {
+    bw.first()
+        .map(|w| w.trim())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+}
+
+// Parse the mode string returned by FLRig's get_mode() before a set_mode.
+// A mode unknown to flrig::Mode is an error, but some rigs momentarily report
+// an empty string while changing band; that is returned as None ("unknown, go
+// ahead and set") so the QSY is not aborted.
+fn parse_current_mode(existing_mode_str: &str) -> Result<Option<Mode>, FlrigError> {
+    if existing_mode_str.trim().is_empty() {
+        debug!("FLRig returned an empty mode string; setting mode regardless");
+        return Ok(None);
+    }
+    existing_mode_str.parse::<Mode>().map(Some).map_err(|_| {
+        FlrigError::UnknownMode(UnknownModeError {
+            msg: format!("mode {existing_mode_str} is unknown"),
+        })
+    })
+}
+
+// A failure to get a request onto the wire or a response back, as opposed to
+// an XML-RPC fault or a malformed reply.  The usual cause is a keep-alive
+// socket that a firewall dropped while idle; timeouts are excluded because
+// repeating the call would only wait again.
+fn is_connection_error(err: &ClientError) -> bool {
+    matches!(err, ClientError::Net { error }
+        if !error.is_timeout() && (error.is_connect() || error.is_request()))
+}
+
+// Whether a call that failed with `err` may be sent once more: a read
+// always, a set only if no connection was made, so it cannot have reached
+// FLRig and be applied twice.
+fn may_retry(err: &ClientError, read_only: bool) -> bool {
+    is_connection_error(err)
+        && (read_only || matches!(err, ClientError::Net { error } if error.is_connect()))
+}
+
+// Whether `method` only reads from the rig, so that sending it again, or to
+// the other FLRig, cannot change anything.  A set that timed out may still
+// have reached the rig, and must not be applied twice or to a second rig.
+fn is_read_only(method: &str) -> bool {
+    method.starts_with("system.")
+        || method
+            .split('.')
+            .nth(1)
+            .is_some_and(|name| name.starts_with("get_"))
+}
+
 impl FLRig {
+    // Every FLRig call goes through here and the multicall equivalent below so
+    // a stale pooled connection costs a read one silent retry instead of an
+    // error.  The failed socket is discarded by the pool, so the retry
+    // connects afresh.
+    // With a backup configured, a read the primary does not answer may then
+    // be repeated on the backup.
+    async fn call<P, R>(&self, method: &str, args: P) -> Result<R, ClientError>
+    where
+        P: TryToParams + Clone,
+        R: TryFromValue,
+    {
+        let started = Instant::now();
+        let mut target = self.target();
+        let result = loop {
+            let client = self.client_for(target);
+            let result = match client.call(method, args.clone()).await {
+                Err(e) if may_retry(&e, is_read_only(method)) => {
+                    debug!("FLRig {method} failed ({e}), retrying once");
+                    client.call(method, args.clone()).await
+                }
+                result => result,
+            };
+            match &self.failover {
+                Some(failover) if failover.observe(target, &result) && is_read_only(method) => {
+                    target = Target::Backup
+                }
+                _ => break result,
+            }
+        };
+        self.track(result, started)
+    }
+
+    async fn multicall<P>(
+        &self,
+        calls: Vec<(String, P)>,
+    ) -> Result<Vec<Result<dxr::Value, dxr::Fault>>, ClientError>
+    where
+        P: TryToParams + Clone,
+    {
+        let started = Instant::now();
+        let read_only = calls.iter().all(|(method, _)| is_read_only(method));
+        let mut target = self.target();
+        let result = loop {
+            let client = self.client_for(target);
+            let result = match client.multicall(calls.clone()).await {
+                Err(e) if may_retry(&e, read_only) => {
+                    debug!("FLRig multicall failed ({e}), retrying once");
+                    client.multicall(calls.clone()).await
+                }
+                result => result,
+            };
+            match &self.failover {
+                Some(failover) if failover.observe(target, &result) && read_only => {
+                    target = Target::Backup
+                }
+                _ => break result,
+            }
+        };
+        self.track(result, started)
+    }
+
+    fn target(&self) -> Target {
+        self.failover
+            .as_ref()
+            .map_or(Target::Primary, Failover::target)
+    }
+
+    fn client_for(&self, target: Target) -> &Client {
+        match (target, &self.failover) {
+            (Target::Backup, Some(failover)) => &failover.client,
+            _ => &self.client,
+        }
+    }
+
+    // Count a call that never got an answer from FLRig; any answer, even a
+    // fault, shows FLRig is there, resets the count and has its round trip
+    // (including any retry) recorded.
+    fn track<R>(&self, result: Result<R, ClientError>, started: Instant) -> Result<R, ClientError> {
+        if matches!(result, Err(ClientError::Net { .. })) {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.failures.store(0, Ordering::Relaxed);
+            self.latency.record_flrig(started.elapsed());
+        }
+        result
+    }
+
+    /// Record FLRig round trips into `latency`, shared with the Wavelog
+    /// uploaders, rather than into stats of the rig's own.
+    pub fn with_latency(mut self, latency: SharedLatency) -> FLRig {
+        self.latency = latency;
+        self
+    }
+
+    /// How many FLRig calls in a row, from any caller, have failed to get an
+    /// answer.  Zero once any call succeeds.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Check that FLRig answers.  A fault still counts as an answer.
+    pub async fn probe(&self) -> Result<(), ClientError> {
+        match self.call::<_, String>("main.get_version", ()).await {
+            Ok(_) | Err(ClientError::Fault { .. }) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
     pub fn new(settings: FlrigSettings, identifier: String) -> FLRig {
-        let url = format!("{0}:{1}/", settings.host, settings.port);
-        let url = Url::parse(&url).unwrap_or_else(|_| panic!("{url} does not parse as a URL"));
-        let client: Client = ClientBuilder::new(url).build();
+        let client: Client =
+            ClientBuilder::new(checked_flrig_url(&settings.host, settings.port)).build();
+        let failover = Failover::new(&settings);
+        let power_limits = PowerLimits::new(&settings);
         FLRig {
             maxpower: settings.maxpower,
+            power_is_watts: settings.power_is_watts.unwrap_or(false),
             client,
             identifier,
             cw_bw_index: settings.cw_bw_index,
+            mode_bw_index: parse_mode_table(settings.mode_bw_index.as_ref(), "mode_bw_index"),
+            narrow_delay: Duration::from_millis(
+                settings.narrow_delay_ms.unwrap_or(DEFAULT_NARROW_DELAY_MS),
+            ),
+            band_memory: None,
+            state_file: None,
+            power_on: settings.power_on,
+            power_off: settings.power_off,
+            power_limits: RwLock::new(power_limits),
+            report_dsp: settings.report_dsp.unwrap_or(false),
+            report_passband: settings.report_passband.unwrap_or(false),
+            report_smeter: settings.report_smeter.unwrap_or(false),
+            report_ptt: AtomicBool::new(settings.report_ptt.unwrap_or(false)),
+            verify_set: settings.verify_set.unwrap_or(false),
+            smeter_calibration: SmeterCalibration::new(settings.smeter_calibration.as_deref()),
+            failover,
+            failures: AtomicU32::new(0),
+            latency: SharedLatency::default(),
+            get_info: AtomicBool::new(settings.use_get_info.unwrap_or(false)),
+            tuning: tokio::sync::Mutex::new(()),
+            vfo_b_log: settings
+                .log_vfob
+                .unwrap_or(false)
+                .then(|| Mutex::new(VfoBLog::Nothing)),
+            maxpower_warning: Mutex::new(None),
+        }
+    }
+
+    /// Remember the voice mode seen on each band, see `BandModeMemory`.
+    pub fn with_band_memory(mut self, memory: BandModeMemory) -> FLRig {
+        self.band_memory = Some(memory);
+        self
+    }
+
+    /// Save the frequency and mode seen on each reading, see `RigStateFile`.
+    pub fn with_state_file(mut self, state_file: RigStateFile) -> FLRig {
+        self.state_file = Some(state_file);
+        self
+    }
+
+    /// Write any state-file or band-memory change still waiting to be
+    /// saved, for shutdown.
+    pub async fn flush_state(&self) {
+        if let Some(state_file) = &self.state_file {
+            state_file.flush().await;
+        }
+        if let Some(memory) = &self.band_memory {
+            memory.flush().await;
+        }
+    }
+
+    /// Tune to the frequency and mode saved before this run started, as one
+    /// QSY.  None, without touching the rig, if nothing was saved.
+    pub async fn restore_saved_state(&self) -> Result<Option<SavedRigState>, FlrigError> {
+        let Some(saved) = self.state_file.as_ref().and_then(RigStateFile::loaded) else {
+            return Ok(None);
+        };
+        let mode = saved.mode.parse::<Mode>().map_err(|_| {
+            FlrigError::UnknownMode(UnknownModeError {
+                msg: format!("saved mode {} is unknown", saved.mode),
+            })
+        })?;
+        let _tuning = self.lock_tuning().await;
+        self.set_vfo(saved.frequency as f64).await?;
+        self.set_mode(mode, false).await?;
+        Ok(Some(saved.clone()))
+    }
+
+    /// Apply reloaded `mode_power_limits` and `enforce_mode_power`.
+    pub fn reload_power_limits(&self, settings: &FlrigSettings) {
+        *self.power_limits.write().unwrap() = PowerLimits::new(settings);
+    }
+
+    /// Whether `[flrig] report_dsp` is enabled.
+    pub fn reports_dsp(&self) -> bool {
+        self.report_dsp
+    }
+
+    /// Whether `[flrig] report_passband` is enabled.
+    pub fn reports_passband(&self) -> bool {
+        self.report_passband
+    }
+
+    /// Whether `[flrig] report_smeter` is enabled.
+    pub fn reports_smeter(&self) -> bool {
+        self.report_smeter
+    }
+
+    /// Whether the rig is transmitting, from `rig.get_ptt`, with `[flrig]
+    /// report_ptt` set.  None when it is not set or the rig cannot report
+    /// PTT; a rig that cannot is not asked again.
+    pub async fn get_ptt(&self) -> Result<Option<bool>, ClientError> {
+        if !self.report_ptt.load(Ordering::Relaxed) {
+            return Ok(None);
         }
+        match self.call::<_, i32>("rig.get_ptt", ()).await {
+            Ok(ptt) => Ok(Some(ptt != 0)),
+            Err(ClientError::Fault { .. }) => {
+                info!("This rig cannot report PTT; using WSJT-X for TX/RX state");
+                self.report_ptt.store(false, Ordering::Relaxed);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Whether `[flrig] verify_set` is enabled.
+    pub fn verifies_set(&self) -> bool {
+        self.verify_set
+    }
+
+    /// Read back the frequency and mode after a QSY, in one multicall, and
+    /// compare them with what was asked for.
+    pub async fn verify_set(&self, freq_hz: f64, mode: Mode) -> Result<SetCheck, ClientError> {
+        let calls: Vec<(String, ())> = vec![
+            ("rig.get_vfo".to_string(), ()),
+            ("rig.get_mode".to_string(), ()),
+        ];
+        let mut results = self.multicall(calls).await?;
+        let mode_r = results.pop().expect("multicall result count mismatch");
+        let vfo_r = results.pop().expect("multicall result count mismatch");
+        let vfo = String::try_from_value(&vfo_r.map_err(ClientError::from)?)?;
+        let actual_mode = String::try_from_value(&mode_r.map_err(ClientError::from)?)?;
+        let actual_freq = vfo.trim().parse::<f64>().ok();
+        Ok(SetCheck {
+            verified: set_took_effect(freq_hz, mode, actual_freq, &actual_mode),
+            frequency: normalize_freq(&vfo),
+            mode: actual_mode.trim().to_string(),
+        })
+    }
+
+    /// Read the filter width and passband tuning in one multicall.  As with
+    /// `get_dsp_state`, a call that faults leaves its field None.
+    pub async fn get_passband(&self) -> Result<PassbandState, ClientError> {
+        let calls: Vec<(String, ())> = vec![
+            ("rig.get_bw".to_string(), ()),
+            ("rig.get_pbt_inner".to_string(), ()),
+            ("rig.get_pbt_outer".to_string(), ()),
+        ];
+        let results = self.multicall(calls).await?;
+        let value = |i: usize| results.get(i).and_then(|r| r.as_ref().ok());
+        let pbt = |i: usize| value(i).and_then(|v| i32::try_from_value(v).ok());
+        Ok(PassbandState {
+            width: value(0).and_then(|v| bandwidth_label(&Vec::<String>::try_from_value(v).ok()?)),
+            pbt_inner: pbt(1),
+            pbt_outer: pbt(2),
+        })
+    }
+
+    /// Read NB, NR and notch state in one multicall.  A feature whose call
+    /// faults (unsupported by the rig) is reported as None.
+    pub async fn get_dsp_state(&self) -> Result<DspState, ClientError> {
+        let calls: Vec<(String, ())> = vec![
+            ("rig.get_noise".to_string(), ()),
+            ("rig.get_nr".to_string(), ()),
+            ("rig.get_notch".to_string(), ()),
+        ];
+        let results = self.multicall(calls).await?;
+        let flag = |i: usize| {
+            results
+                .get(i)
+                .and_then(|r| r.as_ref().ok())
+                .and_then(|v| i32::try_from_value(v).ok())
+                .map(|v| v != 0)
+        };
+        Ok(DspState {
+            nb: flag(0),
+            nr: flag(1),
+            notch: flag(2),
+        })
+    }
+
+    /// The last voice mode observed on the band containing `freq_hz`, or
+    /// None if there is no band memory or nothing remembered for that band.
+    pub fn remembered_mode(&self, freq_hz: u32) -> Option<Mode> {
+        self.band_memory.as_ref()?.remembered(freq_hz)
+    }
+
+    /// Every remembered band and its voice mode; empty without band memory.
+    pub fn remembered_modes(&self) -> BTreeMap<String, String> {
+        self.band_memory
+            .as_ref()
+            .map(BandModeMemory::snapshot)
+            .unwrap_or_default()
     }
 
     pub async fn get_mode(&self) -> Result<String, ClientError> {
-        let response: String = self.client.call("rig.get_mode", ()).await?;
+        let response: String = self.call("rig.get_mode", ()).await?;
+        Ok(response)
+    }
+
+    /// Raw S-meter reading, on FLRig's 0-100 meter scale.  Read-only.
+    pub async fn get_smeter(&self) -> Result<i32, ClientError> {
+        let response: i32 = self.call("rig.get_smeter", ()).await?;
+        Ok(response)
+    }
+
+    /// The S-meter reading, raw and interpreted through `smeter_calibration`.
+    pub async fn read_smeter(&self) -> Result<SmeterReading, ClientError> {
+        let raw = self.get_smeter().await?;
+        Ok(self.smeter_calibration.reading(raw))
+    }
+
+    /// Mode names the connected rig supports, as FLRig spells them.
+    pub async fn get_modes(&self) -> Result<Vec<String>, ClientError> {
+        let response: Vec<String> = self.call("rig.get_modes", ()).await?;
         Ok(response)
     }
 
+    /// The FLRig methods wlrigctl may call, including any configured
+    /// `power_on`/`power_off` methods.
+    pub fn used_methods(&self) -> Vec<&str> {
+        let mut methods = USED_METHODS.to_vec();
+        for command in [&self.power_on, &self.power_off].into_iter().flatten() {
+            if !methods.contains(&command.method.as_str()) {
+                methods.push(&command.method);
+            }
+        }
+        methods
+    }
+
+    /// The methods FLRig says it has, from XML-RPC introspection
+    /// (`system.listMethods`).  None if this FLRig does not support it.
+    pub async fn list_methods(&self) -> Result<Option<Vec<String>>, ClientError> {
+        match self.call("system.listMethods", ()).await {
+            Ok(methods) => Ok(Some(methods)),
+            Err(ClientError::Fault { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn get_update(&self) -> Result<String, ClientError> {
-        let response: String = self.client.call("rig.get_update", ()).await?;
+        let response: String = self.call("rig.get_update", ()).await?;
         Ok(response)
     }
 
@@ -268,6 +1062,19 @@ impl FLRig {
             return Ok(None);
         }
 
+        self.fetch_radio_data().await.map(Some)
+    }
+
+    /// Fetch current radio state unconditionally, skipping the `get_update`
+    /// change check.  Used by on-demand readers such as the CAT /status route.
+    pub async fn fetch_radio_data(&self) -> Result<RadioData, ClientError> {
+        if self.get_info.load(Ordering::Relaxed) {
+            match self.fetch_via_info().await? {
+                Some(reading) => return Ok(self.radio_data(reading)),
+                None => self.get_info.store(false, Ordering::Relaxed),
+            }
+        }
+
         // Fetch vfo, mode, maxpwr and power in a single XMLRPC round-trip.
         let calls: Vec<(String, ())> = vec![
             ("rig.get_vfo".to_string(), ()),
@@ -275,26 +1082,83 @@ impl FLRig {
             ("rig.get_maxpwr".to_string(), ()),
             ("rig.get_power".to_string(), ()),
         ];
-        let mut results = self.client.multicall(calls).await?;
+        let mut results = self.multicall(calls).await?;
         // Pop in reverse call order; the Vec always has exactly as many entries as calls sent.
         let power_r = results.pop().expect("multicall result count mismatch");
         let maxpwr_r = results.pop().expect("multicall result count mismatch");
         let mode_r = results.pop().expect("multicall result count mismatch");
         let vfo_r = results.pop().expect("multicall result count mismatch");
 
-        let vfo = String::try_from_value(&vfo_r.map_err(ClientError::from)?)?;
-        let mode_raw = String::try_from_value(&mode_r.map_err(ClientError::from)?)?;
-        let maxpwr = i32::try_from_value(&maxpwr_r.map_err(ClientError::from)?)?;
-        let power = i32::try_from_value(&power_r.map_err(ClientError::from)?)?;
+        Ok(self.radio_data(RawReading {
+            vfo: String::try_from_value(&vfo_r.map_err(ClientError::from)?)?,
+            mode: String::try_from_value(&mode_r.map_err(ClientError::from)?)?,
+            maxpwr: i32::try_from_value(&maxpwr_r.map_err(ClientError::from)?)?,
+            power: i32::try_from_value(&power_r.map_err(ClientError::from)?)?,
+        }))
+    }
+
+    // As fetch_radio_data, but with the frequency and mode taken from
+    // rig.get_info.  None if FLRig faulted on it or the answer did not
+    // parse, in which case the caller should use the separate calls.
+    async fn fetch_via_info(&self) -> Result<Option<RawReading>, ClientError> {
+        let calls: Vec<(String, ())> = vec![
+            ("rig.get_info".to_string(), ()),
+            ("rig.get_maxpwr".to_string(), ()),
+            ("rig.get_power".to_string(), ()),
+        ];
+        let mut results = self.multicall(calls).await?;
+        let power_r = results.pop().expect("multicall result count mismatch");
+        let maxpwr_r = results.pop().expect("multicall result count mismatch");
+        let info_r = results.pop().expect("multicall result count mismatch");
+
+        let info = match info_r.map_err(ClientError::from) {
+            Ok(value) => String::try_from_value(&value)?,
+            Err(e) => {
+                warn!("FLRig rig.get_info failed ({e}); using rig.get_vfo and rig.get_mode");
+                return Ok(None);
+            }
+        };
+        let Some(info) = RigInfo::parse(&info) else {
+            warn!("Unrecognised rig.get_info reply {info:?}; using rig.get_vfo and rig.get_mode");
+            return Ok(None);
+        };
+        Ok(Some(RawReading {
+            vfo: info.frequency,
+            mode: info.mode,
+            maxpwr: i32::try_from_value(&maxpwr_r.map_err(ClientError::from)?)?,
+            power: i32::try_from_value(&power_r.map_err(ClientError::from)?)?,
+        }))
+    }
 
+    // Turn FLRig's answers into the state reported to Wavelog, noting the
+    // mode in band memory on the way.
+    fn radio_data(&self, reading: RawReading) -> RadioData {
+        let vfo = normalize_freq(&reading.vfo);
+        let mode_raw = reading.mode;
+        let (maxpwr, power) = (reading.maxpwr, reading.power);
+
+        // Readings in watts are only capped at maxpower, so the rig's
+        // maximum has nothing to be checked against.
+        if !self.power_is_watts {
+            self.note_maxpower(maxpwr);
+        }
         let maxpwr_u = if maxpwr < 0 { 0u32 } else { maxpwr as u32 };
         let power_u = if power < 0 { 0u32 } else { power as u32 };
 
         // Translate the rig-specific FLRig mode string to one Wavelog understands.
         // If the string isn't in our Mode enum (e.g. a new rig adds an unknown mode),
         // pass it through unchanged rather than dropping or erroring.
+        let rig_mode = mode_raw.clone();
         let mode = match mode_raw.parse::<Mode>() {
-            Ok(m) => m.to_wavelog_mode().to_string(),
+            Ok(m) => {
+                if let (Some(memory), Ok(freq)) = (&self.band_memory, vfo.parse::<f64>()) {
+                    memory.observe(freq as u32, m);
+                }
+                if let (Some(state_file), Ok(freq)) = (&self.state_file, vfo.parse::<f64>()) {
+                    state_file.observe(freq as u64, &m.to_string());
+                }
+                m.to_wavelog_mode().to_string()
+            }
             Err(_) => {
                 debug!("Unknown FLRig mode '{mode_raw}', forwarding as-is");
                 mode_raw
@@ -303,76 +1167,540 @@ impl FLRig {
 
         debug!("freq:{vfo} mode:{mode} power:{power} max:{maxpwr}");
 
-        Ok(Some(RadioData {
+        RadioData {
             key: String::new(),
             radio: String::new(),
             frequency: vfo,
             mode,
-            power: rig_power_watts(power_u, maxpwr_u, self.maxpower),
+            rig_mode,
+            power: self.power_watts(power_u, maxpwr_u).to_string(),
             cat_url: None,
-        }))
+            my_grid: None,
+        }
+    }
+
+    // Check maxpower against the rig's reported maximum, logging when the
+    // verdict changes so a misconfiguration is reported once, at startup.
+    fn note_maxpower(&self, rig_max: i32) {
+        let warning = maxpower_mismatch(self.maxpower, rig_max);
+        let mut last = self.maxpower_warning.lock().unwrap();
+        if *last != warning {
+            match &warning {
+                Some(w) => warn!("{w}"),
+                None => info!("[flrig] maxpower now agrees with the rig's maximum"),
+            }
+            *last = warning;
+        }
+    }
+
+    /// Why the configured `maxpower` looks inconsistent with the maximum
+    /// the rig last reported, if it does.
+    pub fn maxpower_warning(&self) -> Option<String> {
+        self.maxpower_warning.lock().unwrap().clone()
+    }
+
+    /// Wait for exclusive use of the rig's tuning.  Hold the guard across a
+    /// sequence such as `set_vfo` then `set_mode`, so that another request
+    /// doing the same cannot leave the rig with its frequency and our mode.
+    pub async fn lock_tuning(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.tuning.lock().await
     }
 
     pub async fn set_vfo(&self, freq_hz: f64) -> Result<(), ClientError> {
-        let _response: String = self.client.call("rig.set_vfo", freq_hz).await?;
+        let _response: String = self.call("rig.set_vfo", freq_hz).await?;
+
+        Ok(())
+    }
+
+    /// VFO B's frequency, as FLRig reports it.
+    pub async fn get_vfo_b(&self) -> Result<String, ClientError> {
+        self.call("rig.get_vfoB", ()).await
+    }
+
+    /// With `[flrig] log_vfob` set, read VFO B and log its frequency if it
+    /// has moved since it was last logged.  A rig without VFO B is logged as
+    /// unsupported once and not asked again.
+    pub async fn log_vfo_b(&self) {
+        let Some(log) = &self.vfo_b_log else {
+            return;
+        };
+        if *log.lock().unwrap() == VfoBLog::Unsupported {
+            return;
+        }
+        match self.get_vfo_b().await {
+            Ok(freq) => {
+                let mut log = log.lock().unwrap();
+                if *log != VfoBLog::Frequency(freq.clone()) {
+                    info!("VFO B: {freq} Hz");
+                    *log = VfoBLog::Frequency(freq);
+                }
+            }
+            Err(ClientError::Fault { .. }) => {
+                info!("VFO B: unsupported by this rig; no longer reading it");
+                *log.lock().unwrap() = VfoBLog::Unsupported;
+            }
+            Err(e) => debug!("Could not read VFO B: {e}"),
+        }
+    }
+
+    /// Set VFO B's frequency without selecting VFO B or enabling split.
+    pub async fn set_vfo_b(&self, freq_hz: f64) -> Result<(), ClientError> {
+        let _response: dxr::Value = self.call("rig.set_vfoB", freq_hz).await?;
+
+        Ok(())
+    }
+
+    /// Set VFO B's mode.  Some rigs share one mode between both VFOs and
+    /// quietly keep VFO A's, so the mode is read back and a mismatch is
+    /// reported as `Unsupported`.
+    pub async fn set_mode_b(&self, mode: Mode) -> Result<(), FlrigError> {
+        info!("calling rig.set_modeB with mode:{mode}");
+        let _response: dxr::Value = self.call("rig.set_modeB", mode.to_string()).await?;
+
+        let actual: String = self.call("rig.get_modeB", ()).await?;
+        if parse_current_mode(&actual)? != Some(mode) {
+            return Err(FlrigError::Unsupported(format!(
+                "this rig cannot set VFO B's mode independently \
+                 (asked for {mode}, VFO B reports '{actual}')"
+            )));
+        }
 
         Ok(())
     }
 
-    pub async fn set_mode(&self, mode: Mode) -> Result<(), FlrigError> {
+    /// Put the rig in `mode`.  The mode is only sent if the rig is not
+    /// already in it, unless `force` is set.
+    pub async fn set_mode(&self, mode: Mode, force: bool) -> Result<(), FlrigError> {
         // Avoid re-sending the mode command if the rig is already in the target mode.
         // rig.set_mode causes FLRig to momentarily apply a wide default bandwidth before the
         // mode settles; skipping the redundant call prevents an audible filter glitch.
         // Note: this guard only wraps rig.set_mode, not set_narrow — see below.
-        let existing_mode_str: String = self.get_mode().await?;
-
-        // Since we're converting the mode returned from FLRig's get_mode(), we have to handle the
-        // prospect that a new mode is returned that is unknown to flrig::Mode
-        let existing_mode: Mode = existing_mode_str.parse::<Mode>().map_err(|_| {
-            FlrigError::UnknownMode(UnknownModeError {
-                msg: format!("mode {existing_mode_str} is unknown"),
-            })
-        })?;
+        let existing_mode = if force {
+            None
+        } else {
+            parse_current_mode(&self.get_mode().await?)?
+        };
 
-        if mode != existing_mode {
+        let mode_changed = existing_mode != Some(mode);
+        if mode_changed {
             info!("calling rig.set_mode with mode:{mode}");
-            let _response: i32 = self.client.call("rig.set_mode", mode.to_string()).await?;
+            let _response: i32 = self.call("rig.set_mode", mode.to_string()).await?;
         }
 
         // Always restore narrow filter when targeting CW. Band memory may have
         // already switched the rig to CW (bypassing the mode-change branch above),
         // so this must not be gated on whether the mode actually changed.
-        if let Some(idx) = cw_narrow_index(mode, self.cw_bw_index) {
-            info!("Bodging narrow filter on IC-703");
+        // The same applies to any other mode with a configured bandwidth,
+        // e.g. the independent data-mode filter on Icom rigs.
+        if let Some(idx) = mode_bw_index(mode, &self.mode_bw_index, self.cw_bw_index) {
+            if mode == Mode::CW {
+                info!("Bodging narrow filter on IC-703");
+            } else {
+                info!("Setting {mode} bandwidth index {idx}");
+            }
+            // Some rigs ignore a bandwidth set while the mode change is
+            // still settling.
+            if mode_changed && !self.narrow_delay.is_zero() {
+                tokio::time::sleep(self.narrow_delay).await;
+            }
             self.set_narrow(idx as i32).await?;
         }
 
         Ok(())
     }
 
-    pub async fn set_narrow(&self, bw_index: i32) -> Result<(), ClientError> {
-        let _response: i32 = self.client.call("rig.set_bw", bw_index).await?;
+    /// Switch the rig on using the configured `power_on` command.
+    pub async fn power_on(&self) -> Result<(), FlrigError> {
+        self.power_command(self.power_on.as_ref(), "on").await
+    }
+
+    /// Switch the rig off using the configured `power_off` command.
+    pub async fn power_off(&self) -> Result<(), FlrigError> {
+        self.power_command(self.power_off.as_ref(), "off").await
+    }
+
+    async fn power_command(
+        &self,
+        cmd: Option<&FlrigCommand>,
+        what: &str,
+    ) -> Result<(), FlrigError> {
+        let cmd = cmd.ok_or_else(|| {
+            FlrigError::Unsupported(format!("no power_{what} command configured in [flrig]"))
+        })?;
+
+        warn!("Powering rig {what} via FLRig {}", cmd.method);
+        let _response: dxr::Value = match &cmd.arg {
+            Some(arg) => self.call(cmd.method.as_str(), arg.clone()).await?,
+            None => self.call(cmd.method.as_str(), ()).await?,
+        };
 
         Ok(())
     }
 
-    // Read back the string identifier, supplied in the .toml config file
-    pub fn get_identifier(&self) -> String {
-        self.identifier.clone()
+    /// Set the output power in watts, capped at `maxpower` and at the
+    /// `mode_power_limits` ceiling for `mode`.  Returns the watts applied.
+    pub async fn set_power_watts(&self, watts: u32, mode: Mode) -> Result<u32, ClientError> {
+        let maxpwr: i32 = self.call("rig.get_maxpwr", ()).await?;
+        self.apply_power_watts(watts, Some(mode), maxpwr).await
     }
-}
 
-fn rig_power_watts(power: u32, max_power: u32, max_watts: u32) -> String {
-    if max_power == 0 {
-        return "0".to_string();
+    // set_power_watts with the rig's full-scale value already read.  A mode
+    // of None (one flrig::Mode does not know) has no ceiling but maxpower.
+    async fn apply_power_watts(
+        &self,
+        watts: u32,
+        mode: Option<Mode>,
+        maxpwr: i32,
+    ) -> Result<u32, ClientError> {
+        let mut watts = watts.min(self.maxpower);
+        let cap = mode.and_then(|m| power_cap(&self.power_limits.read().unwrap().caps, m));
+        if let Some(cap) = cap {
+            watts = watts.min(cap);
+        }
+        let units = self.power_units(watts, maxpwr.max(0) as u32);
+        match mode {
+            Some(mode) => info!("Setting power to {watts}W ({units} of {maxpwr}) for {mode}"),
+            None => info!("Setting power to {watts}W ({units} of {maxpwr})"),
+        }
+        let _response: dxr::Value = self.call("rig.set_power", units).await?;
+        Ok(watts)
     }
-    let watts = (power as f32 * max_watts as f32 / max_power as f32).round() as u32;
-    watts.to_string()
-}
 
-#[cfg(test)]
+    /// Turn the output power up or down by `step` watts from its current
+    /// setting, clamped to 0 and `maxpower` and to the current mode's
+    /// `mode_power_limits` ceiling.  Returns the watts applied.
+    pub async fn step_power_watts(&self, step: u32, up: bool) -> Result<u32, ClientError> {
+        let power: i32 = self.call("rig.get_power", ()).await?;
+        let maxpwr: i32 = self.call("rig.get_maxpwr", ()).await?;
+        let mode = self.get_mode().await?.trim().parse::<Mode>().ok();
+        let watts = self.power_watts(power.max(0) as u32, maxpwr.max(0) as u32);
+        let target = stepped_power(watts, step, up, self.maxpower);
+        self.apply_power_watts(target, mode, maxpwr).await
+    }
+
+    /// The configured `maxpower`, in watts.
+    pub fn maxpower(&self) -> u32 {
+        self.maxpower
+    }
+
+    // A rig.get_power reading in watts, given the rig's rig.get_maxpwr.
+    fn power_watts(&self, power: u32, maxpwr: u32) -> u32 {
+        if self.power_is_watts {
+            return power.min(self.maxpower);
+        }
+        rig_power_watts(power, maxpwr, self.maxpower)
+            .parse()
+            .unwrap_or(0)
+    }
+
+    // Inverse of power_watts: the value to pass to rig.set_power for `watts`.
+    fn power_units(&self, watts: u32, maxpwr: u32) -> i32 {
+        if self.power_is_watts {
+            return watts.min(self.maxpower) as i32;
+        }
+        watts_to_rig_power(watts, maxpwr, self.maxpower)
+    }
+
+    /// With `enforce_mode_power`, turn the power down to `mode`'s ceiling if
+    /// it is currently above it.  Returns the new wattage if it was reduced.
+    pub async fn enforce_mode_power(&self, mode: Mode) -> Result<Option<u32>, ClientError> {
+        let cap = {
+            let limits = self.power_limits.read().unwrap();
+            limits
+                .enforce
+                .then(|| power_cap(&limits.caps, mode))
+                .flatten()
+        };
+        let Some(cap) = cap else {
+            return Ok(None);
+        };
+        let power: i32 = self.call("rig.get_power", ()).await?;
+        let maxpwr: i32 = self.call("rig.get_maxpwr", ()).await?;
+        let watts = self.power_watts(power.max(0) as u32, maxpwr.max(0) as u32);
+        if watts <= cap {
+            return Ok(None);
+        }
+        warn!("{mode} is limited to {cap}W; reducing power from {watts}W");
+        self.set_power_watts(cap, mode).await.map(Some)
+    }
+
+    pub async fn set_narrow(&self, bw_index: i32) -> Result<(), ClientError> {
+        let _response: i32 = self.call("rig.set_bw", bw_index).await?;
+
+        Ok(())
+    }
+
+    // Read back the string identifier, supplied in the .toml config file
+    pub fn get_identifier(&self) -> String {
+        self.identifier.clone()
+    }
+}
+
+// The four values a rig state read is built from, as FLRig gave them.
+struct RawReading {
+    vfo: String,
+    mode: String,
+    maxpwr: i32,
+    power: i32,
+}
+
+/// The fields of FLRig's `rig.get_info` summary, one `KEY:value` per line:
+///
+/// ```text
+/// R:IC-7300
+/// T:R
+/// FA:14074000
+/// M:USB
+/// L:100
+/// U:3000
+/// ```
+///
+/// Only the frequency (`FA`, or `FB` when VFO B is in use) and mode (`M`)
+/// are required; unknown keys are ignored.
+#[derive(Debug, PartialEq)]
+pub struct RigInfo {
+    /// Rig model name (`R`).
+    pub rig: Option<String>,
+    /// Whether the rig is transmitting (`T:X`) or receiving (`T:R`).
+    pub transmitting: Option<bool>,
+    /// Frequency in Hz, as FLRig wrote it.
+    pub frequency: String,
+    pub mode: String,
+    /// Passband lower and upper edges (`L`, `U`), in the rig's units.
+    pub bw_lower: Option<String>,
+    pub bw_upper: Option<String>,
+}
+
+impl RigInfo {
+    /// Parse a `rig.get_info` reply, or None if it lacks a frequency or mode.
+    pub fn parse(text: &str) -> Option<RigInfo> {
+        let fields: HashMap<&str, &str> = text
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .map(|(key, value)| (key.trim(), value.trim()))
+            .collect();
+        let present = |key: &str| {
+            fields
+                .get(key)
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+        };
+
+        let frequency = present("FA").or_else(|| present("FB"))?;
+        frequency.parse::<f64>().ok()?;
+        Some(RigInfo {
+            rig: present("R"),
+            transmitting: match fields.get("T") {
+                Some(&"X") => Some(true),
+                Some(&"R") => Some(false),
+                _ => None,
+            },
+            frequency,
+            mode: present("M")?,
+            bw_lower: present("L"),
+            bw_upper: present("U"),
+        })
+    }
+}
+
+// Some rig/FLRig combinations report the VFO as "14074000.000000" and others
+// as "14074000".  Format it canonically as whole Hz so the poll loop's string
+// comparison does not see a change when there is none.  Anything that does
+// not parse as a number is passed through (trimmed) for Wavelog to judge.
+fn normalize_freq(raw: &str) -> String {
+    match raw.trim().parse::<f64>() {
+        Ok(hz) if hz.is_finite() && hz >= 0.0 => format!("{}", hz.round() as u64),
+        _ => raw.trim().to_string(),
+    }
+}
+
+/// What the rig reported after a QSY, and whether it matched the request.
+#[derive(Debug, PartialEq)]
+pub struct SetCheck {
+    pub verified: bool,
+    pub frequency: String,
+    pub mode: String,
+}
+
+// Rigs tune in steps (often 10 Hz), so a read-back this close counts.
+const VERIFY_TOLERANCE_HZ: f64 = 10.0;
+
+// Whether a read-back frequency and mode match what was set.  A frequency
+// that does not parse never matches.
+fn set_took_effect(freq_hz: f64, mode: Mode, actual_freq: Option<f64>, actual_mode: &str) -> bool {
+    let freq_ok = actual_freq.is_some_and(|f| (f - freq_hz).abs() < VERIFY_TOLERANCE_HZ);
+    freq_ok && actual_mode.trim().parse::<Mode>() == Ok(mode)
+}
+
+/// An S-meter reading: FLRig's raw value and what it means.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct SmeterReading {
+    pub raw: i32,
+    /// e.g. "S7" or "S9+20".
+    pub s_units: String,
+    /// Signal strength relative to S9, to 0.1 dB.
+    pub db_over_s9: f64,
+}
+
+/// Converts raw FLRig S-meter values to dB relative to S9 by interpolating
+/// between `[flrig] smeter_calibration` breakpoints.
+///
+/// FLRig normalises every rig's meter to 0-100 with S9 at 50: 0-50 spans
+/// S0-S9 and 50-100 spans S9 to S9+60dB, which is the default.  Individual
+/// rigs are only roughly calibrated to that scale, hence the setting.
+#[derive(Debug, Clone)]
+pub struct SmeterCalibration {
+    // (raw, dB over S9), sorted by raw, at least two points.
+    points: Vec<(f64, f64)>,
+}
+
+// One S-unit, in dB.
+const DB_PER_S_UNIT: f64 = 6.0;
+
+impl Default for SmeterCalibration {
+    fn default() -> Self {
+        SmeterCalibration {
+            points: vec![(0.0, -9.0 * DB_PER_S_UNIT), (50.0, 0.0), (100.0, 60.0)],
+        }
+    }
+}
+
+impl SmeterCalibration {
+    /// The configured breakpoints, or the default scale if there are none or
+    /// they are unusable (fewer than two, or not finite).
+    pub fn new(points: Option<&[[f64; 2]]>) -> SmeterCalibration {
+        let Some(points) = points else {
+            return SmeterCalibration::default();
+        };
+        let mut points: Vec<(f64, f64)> = points.iter().map(|&[raw, db]| (raw, db)).collect();
+        if points.len() < 2 || points.iter().any(|(r, d)| !r.is_finite() || !d.is_finite()) {
+            warn!("Ignoring smeter_calibration: need at least two finite [raw, dB] points");
+            return SmeterCalibration::default();
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        SmeterCalibration { points }
+    }
+
+    /// dB relative to S9 for a raw reading.
+    pub fn db_over_s9(&self, raw: i32) -> f64 {
+        let raw = raw as f64;
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+        if raw <= first.0 {
+            return first.1;
+        }
+        if raw >= last.0 {
+            return last.1;
+        }
+        let i = self.points.partition_point(|&(r, _)| r <= raw);
+        let ((r0, d0), (r1, d1)) = (self.points[i - 1], self.points[i]);
+        d0 + (d1 - d0) * (raw - r0) / (r1 - r0)
+    }
+
+    /// A raw reading as S-units, e.g. "S7" or "S9+20".
+    pub fn s_units(&self, raw: i32) -> String {
+        let db = self.db_over_s9(raw).round();
+        if db <= 0.0 {
+            format!("S{}", (9.0 + db / DB_PER_S_UNIT).round().max(0.0) as u32)
+        } else {
+            format!("S9+{}", db as u32)
+        }
+    }
+
+    pub fn reading(&self, raw: i32) -> SmeterReading {
+        SmeterReading {
+            raw,
+            s_units: self.s_units(raw),
+            db_over_s9: (self.db_over_s9(raw) * 10.0).round() / 10.0,
+        }
+    }
+}
+
+// Resolve a per-mode table from the config, keyed by FLRig mode name.  Keys
+// that are not FLRig mode names are reported and ignored rather than failing
+// startup.
+fn parse_mode_table(table: Option<&HashMap<String, u32>>, setting: &str) -> Vec<(Mode, u32)> {
+    table
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, &value)| match name.parse::<Mode>() {
+            Ok(mode) => Some((mode, value)),
+            Err(_) => {
+                warn!("Ignoring {setting} entry for unknown mode '{name}'");
+                None
+            }
+        })
+        .collect()
+}
+
+// The configured per-mode power ceilings.
+fn parse_mode_power_limits(limits: Option<&HashMap<String, u32>>) -> Vec<(Mode, u32)> {
+    parse_mode_table(limits, "mode_power_limits")
+}
+
+// The power ceiling in watts for `mode`, if one is configured.
+fn power_cap(limits: &[(Mode, u32)], mode: Mode) -> Option<u32> {
+    limits.iter().find(|(m, _)| *m == mode).map(|&(_, w)| w)
+}
+
+// Inverse of rig_power_watts: the value to pass to rig.set_power for `watts`,
+// given the rig's full-scale value and what that corresponds to in watts.
+fn watts_to_rig_power(watts: u32, max_power: u32, max_watts: u32) -> i32 {
+    if max_watts == 0 {
+        return 0;
+    }
+    (watts as f32 * max_power as f32 / max_watts as f32).round() as i32
+}
+
+// `watts` moved `step` up or down, kept within 0..=max_watts.  Already
+// beyond max_watts (maxpower lowered since) counts as at it.
+fn stepped_power(watts: u32, step: u32, up: bool, max_watts: u32) -> u32 {
+    let watts = watts.min(max_watts);
+    if up {
+        watts.saturating_add(step).min(max_watts)
+    } else {
+        watts.saturating_sub(step)
+    }
+}
+
+// rig.get_maxpwr values that are a power scale rather than watts.
+const RIG_POWER_SCALES: &[u32] = &[100, 255];
+
+// Whether `maxpower` (watts) and the rig's `rig.get_maxpwr` look like they
+// describe different rigs.  A maximum of 100 (percent, or a 100W rig) or
+// 255 (a raw byte) is a scale and says nothing; any other value is taken
+// to be watts, and more than a factor of two from maxpower is reported.
+// So is no maximum at all, which makes every reading 0W.
+fn maxpower_mismatch(maxpower: u32, rig_max: i32) -> Option<String> {
+    if rig_max <= 0 {
+        return Some(format!(
+            "FLRig reports a maximum power of {rig_max}, so power reads as 0W"
+        ));
+    }
+    let rig_max = rig_max as u32;
+    if RIG_POWER_SCALES.contains(&rig_max) {
+        return None;
+    }
+    let (lo, hi) = (maxpower.min(rig_max), maxpower.max(rig_max));
+    (hi > lo.saturating_mul(2)).then(|| {
+        format!(
+            "[flrig] maxpower is {maxpower}W but the rig reports a maximum of {rig_max}; \
+             reported power will be wrong unless maxpower is corrected"
+        )
+    })
+}
+
+fn rig_power_watts(power: u32, max_power: u32, max_watts: u32) -> String {
+    if max_power == 0 {
+        return "0".to_string();
+    }
+    let watts = (power as f32 * max_watts as f32 / max_power as f32).round() as u32;
+    watts.to_string()
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_flrig::MockFlrig;
 
     fn test_settings() -> FlrigSettings {
         FlrigSettings {
@@ -380,7 +1708,83 @@ mod tests {
             port: 19999,
             maxpower: 100,
             cw_bw_index: None,
+            mode_bw_index: None,
+            narrow_delay_ms: None,
+            power_on: None,
+            power_off: None,
+            poll_interval_ms: None,
+            report_dsp: None,
+            report_passband: None,
+            report_smeter: None,
+            report_ptt: None,
+            smeter_calibration: None,
+            use_data_mode: None,
+            mode_power_limits: None,
+            enforce_mode_power: None,
+            cw_pitch_hz: None,
+            use_get_info: None,
+            backup_host: None,
+            backup_port: None,
+            failover_after: None,
+            verify_set: None,
+            log_vfob: None,
+            restore_on_start: None,
+            state_file: None,
+            power_is_watts: None,
+            band_mode_file: None,
+        }
+    }
+
+    #[test]
+    fn flrig_url_with_and_without_scheme() {
+        for host in [
+            "http://127.0.0.1",
+            "127.0.0.1",
+            "http://127.0.0.1/",
+            " 127.0.0.1 ",
+        ] {
+            assert_eq!(
+                flrig_url(host, 12345).unwrap().as_str(),
+                "http://127.0.0.1:12345/",
+                "{host}"
+            );
         }
+        assert_eq!(
+            flrig_url("https://shack-pc", 12345).unwrap().as_str(),
+            "https://shack-pc:12345/"
+        );
+        assert_eq!(
+            flrig_url("[::1]", 12345).unwrap().as_str(),
+            "http://[::1]:12345/"
+        );
+    }
+
+    #[test]
+    fn flrig_url_port_in_host_gives_way_to_port() {
+        for host in ["localhost:12345", "http://localhost:8080"] {
+            assert_eq!(
+                flrig_url(host, 12345).unwrap().as_str(),
+                "http://localhost:12345/",
+                "{host}"
+            );
+        }
+    }
+
+    #[test]
+    fn bad_flrig_host_is_an_error() {
+        for host in [
+            "",
+            "http://",
+            "ftp://localhost",
+            "http://localhost/RPC2",
+            "bad host",
+        ] {
+            assert!(flrig_url(host, 12345).is_err(), "{host}");
+        }
+        let mut settings = test_settings();
+        assert!(settings.validate().is_ok());
+        settings.backup_host = Some("http://".to_string());
+        assert!(settings.validate().is_err());
     }
 
     #[test]
@@ -434,6 +1838,664 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn power_without_command_is_unsupported() {
+        let rig = FLRig::new(test_settings(), "test".to_string());
+        assert!(matches!(
+            rig.power_on().await,
+            Err(FlrigError::Unsupported(_))
+        ));
+        assert!(matches!(
+            rig.power_off().await,
+            Err(FlrigError::Unsupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn stale_connection_is_retried_once() {
+        let flrig = MockFlrig::start(&[("rig.get_mode", "<string>USB</string>")]).await;
+        flrig.drop_connections(1);
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        assert_eq!(rig.get_mode().await.unwrap(), "USB");
+        assert_eq!(flrig.methods(), vec!["rig.get_mode"]);
+    }
+
+    #[tokio::test]
+    async fn set_lost_on_a_stale_connection_is_not_retried() {
+        let flrig = MockFlrig::start(&[("rig.set_vfo", "<string></string>")]).await;
+        flrig.drop_connections(1);
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        let err = rig.set_vfo(7_074_000.0).await.unwrap_err();
+        assert!(is_connection_error(&err), "{err}");
+        // The mock records a call only when it answers, so the next set is
+        // the first it has seen.
+        rig.set_vfo(7_074_000.0).await.unwrap();
+        assert_eq!(flrig.methods(), vec!["rig.set_vfo"]);
+    }
+
+    #[tokio::test]
+    async fn repeated_connection_failure_is_reported() {
+        let flrig = MockFlrig::start(&[("rig.get_mode", "<string>USB</string>")]).await;
+        flrig.drop_connections(2);
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        let err = rig.get_mode().await.unwrap_err();
+        assert!(is_connection_error(&err), "{err}");
+        assert!(flrig.methods().is_empty());
+    }
+
+    #[tokio::test]
+    async fn log_vfob_reads_vfo_b() {
+        let flrig = MockFlrig::start(&[("rig.get_vfoB", "<string>14025000</string>")]).await;
+        let mut settings = flrig.settings();
+        settings.log_vfob = Some(true);
+        let rig = FLRig::new(settings, "test".to_string());
+        rig.log_vfo_b().await;
+        rig.log_vfo_b().await;
+        assert_eq!(flrig.methods(), vec!["rig.get_vfoB", "rig.get_vfoB"]);
+        assert_eq!(
+            *rig.vfo_b_log.as_ref().unwrap().lock().unwrap(),
+            VfoBLog::Frequency("14025000".to_string())
+        );
+
+        // Off by default.
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        rig.log_vfo_b().await;
+        assert_eq!(flrig.methods().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rig_without_vfo_b_is_asked_once() {
+        let flrig = MockFlrig::start(&[]).await;
+        let mut settings = flrig.settings();
+        settings.log_vfob = Some(true);
+        let rig = FLRig::new(settings, "test".to_string());
+        rig.log_vfo_b().await;
+        rig.log_vfo_b().await;
+        assert_eq!(flrig.methods(), vec!["rig.get_vfoB"]);
+    }
+
+    #[tokio::test]
+    async fn ptt_is_read_only_when_enabled_and_supported() {
+        let flrig = MockFlrig::start(&[("rig.get_ptt", "<i4>1</i4>")]).await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        assert_eq!(rig.get_ptt().await.unwrap(), None);
+        assert!(flrig.methods().is_empty());
+
+        let mut settings = flrig.settings();
+        settings.report_ptt = Some(true);
+        let rig = FLRig::new(settings, "test".to_string());
+        assert_eq!(rig.get_ptt().await.unwrap(), Some(true));
+
+        // A rig that cannot report PTT is asked once.
+        let flrig = MockFlrig::start(&[]).await;
+        let mut settings = flrig.settings();
+        settings.report_ptt = Some(true);
+        let rig = FLRig::new(settings, "test".to_string());
+        assert_eq!(rig.get_ptt().await.unwrap(), None);
+        assert_eq!(rig.get_ptt().await.unwrap(), None);
+        assert_eq!(flrig.methods(), vec!["rig.get_ptt"]);
+    }
+
+    #[tokio::test]
+    async fn failures_are_counted_until_flrig_answers() {
+        let flrig = MockFlrig::start(&[("rig.get_mode", "<string>USB</string>")]).await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        flrig.drop_connections(4);
+        rig.get_mode().await.unwrap_err();
+        rig.get_mode().await.unwrap_err();
+        assert_eq!(rig.consecutive_failures(), 2);
+
+        // A fault is still an answer.
+        rig.get_smeter().await.unwrap_err();
+        assert_eq!(rig.consecutive_failures(), 0);
+    }
+
+    #[tokio::test]
+    async fn unreachable_primary_fails_over_to_backup_and_back() {
+        let primary = MockFlrig::start(&[("rig.get_mode", "<string>USB</string>")]).await;
+        let backup = MockFlrig::start(&[("rig.get_mode", "<string>LSB</string>")]).await;
+        let settings = FlrigSettings {
+            backup_host: Some("http://127.0.0.1".to_string()),
+            backup_port: Some(backup.port),
+            failover_after: Some(2),
+            ..primary.settings()
+        };
+        let mut rig = FLRig::new(settings, "test".to_string());
+
+        // One failure (a call and its retry) is reported; the second fails over.
+        primary.drop_connections(4);
+        rig.get_mode().await.unwrap_err();
+        assert_eq!(rig.get_mode().await.unwrap(), "LSB");
+        assert_eq!(rig.get_mode().await.unwrap(), "LSB");
+        assert_eq!(backup.methods(), vec!["rig.get_mode", "rig.get_mode"]);
+        assert!(primary.methods().is_empty());
+
+        // Once the primary is rechecked and answers, calls go back to it.
+        rig.failover.as_mut().unwrap().recheck = Duration::ZERO;
+        assert_eq!(rig.get_mode().await.unwrap(), "USB");
+        rig.failover.as_mut().unwrap().recheck = PRIMARY_RECHECK;
+        assert_eq!(rig.get_mode().await.unwrap(), "USB");
+        assert_eq!(backup.methods().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sets_are_not_replayed_on_the_backup() {
+        let primary = MockFlrig::start(&[]).await;
+        let backup = MockFlrig::start(&[
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.get_mode", "<string>LSB</string>"),
+        ])
+        .await;
+        let settings = FlrigSettings {
+            backup_host: Some("http://127.0.0.1".to_string()),
+            backup_port: Some(backup.port),
+            failover_after: Some(1),
+            ..primary.settings()
+        };
+        let rig = FLRig::new(settings, "test".to_string());
+
+        // The set that tips over to the backup fails rather than being sent
+        // to a second rig; later calls go to the backup.
+        primary.drop_connections(1);
+        rig.set_vfo(7_074_000.0).await.unwrap_err();
+        assert!(backup.methods().is_empty());
+        assert_eq!(rig.get_mode().await.unwrap(), "LSB");
+        assert_eq!(backup.methods(), vec!["rig.get_mode"]);
+    }
+
+    #[test]
+    fn only_gets_are_read_only() {
+        assert!(is_read_only("rig.get_vfo"));
+        assert!(is_read_only("main.get_version"));
+        assert!(is_read_only("system.listMethods"));
+        assert!(!is_read_only("rig.set_vfo"));
+        assert!(!is_read_only("rig.set_power"));
+        assert!(!is_read_only("rig.cmd"));
+    }
+
+    #[tokio::test]
+    async fn faults_are_not_retried() {
+        let flrig = MockFlrig::start(&[]).await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        let err = rig.get_smeter().await.unwrap_err();
+        assert!(matches!(err, ClientError::Fault { .. }), "{err}");
+        assert_eq!(flrig.methods(), vec!["rig.get_smeter"]);
+    }
+
+    #[test]
+    fn passband_json_omits_unreported_fields() {
+        let full = PassbandState {
+            width: Some("2400".to_string()),
+            pbt_inner: Some(-120),
+            pbt_outer: Some(50),
+        };
+        assert_eq!(
+            serde_json::to_value(&full).unwrap(),
+            serde_json::json!({ "width": "2400", "pbt_inner": -120, "pbt_outer": 50 })
+        );
+        let width_only = PassbandState {
+            width: Some("FIL2".to_string()),
+            ..PassbandState::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&width_only).unwrap(),
+            serde_json::json!({ "width": "FIL2" })
+        );
+        assert_eq!(
+            serde_json::to_value(PassbandState::default()).unwrap(),
+            serde_json::json!({})
+        );
+    }
+
+    #[test]
+    fn bandwidth_label_takes_width() {
+        let bw = |a: &str, b: &str| vec![a.to_string(), b.to_string()];
+        assert_eq!(bandwidth_label(&bw("2400", "")), Some("2400".to_string()));
+        assert_eq!(
+            bandwidth_label(&bw(" 500 ", "100")),
+            Some("500".to_string())
+        );
+        assert_eq!(bandwidth_label(&bw("", "")), None);
+        assert_eq!(bandwidth_label(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn passband_reads_what_the_rig_reports() {
+        let flrig = MockFlrig::start(&[
+            (
+                "rig.get_bw",
+                "<array><data><value><string>2400</string></value>\
+                 <value><string></string></value></data></array>",
+            ),
+            ("rig.get_pbt_inner", "<i4>-120</i4>"),
+            // no rig.get_pbt_outer: the mock faults, as a rig without it would
+        ])
+        .await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        assert_eq!(
+            rig.get_passband().await.unwrap(),
+            PassbandState {
+                width: Some("2400".to_string()),
+                pbt_inner: Some(-120),
+                pbt_outer: None,
+            }
+        );
+    }
+
+    const SAMPLE_INFO: &str = "R:IC-7300\nT:R\nFA:14074000\nM:D-USB\nL:100\nU:3000\n\
+                               N:0\nVol:40\nMic:50\nRfg:100";
+
+    #[test]
+    fn rig_info_parses_sample() {
+        assert_eq!(
+            RigInfo::parse(SAMPLE_INFO),
+            Some(RigInfo {
+                rig: Some("IC-7300".to_string()),
+                transmitting: Some(false),
+                frequency: "14074000".to_string(),
+                mode: "D-USB".to_string(),
+                bw_lower: Some("100".to_string()),
+                bw_upper: Some("3000".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn rig_info_optional_fields() {
+        let info = RigInfo::parse("T:X\r\nFB:7074000.0\r\nM:USB\r\n").unwrap();
+        assert_eq!(info.frequency, "7074000.0");
+        assert_eq!(info.transmitting, Some(true));
+        assert_eq!(info.rig, None);
+        assert_eq!(info.bw_lower, None);
+    }
+
+    #[test]
+    fn rig_info_needs_frequency_and_mode() {
+        assert_eq!(RigInfo::parse("R:IC-7300\nM:USB"), None);
+        assert_eq!(RigInfo::parse("FA:14074000\nM:"), None);
+        assert_eq!(RigInfo::parse("FA:none\nM:USB"), None);
+        assert_eq!(RigInfo::parse("unknown method"), None);
+    }
+
+    #[tokio::test]
+    async fn fetch_radio_data_via_get_info() {
+        let info = format!("<string>{SAMPLE_INFO}</string>");
+        let flrig = MockFlrig::start(&[
+            ("rig.get_info", &info),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>30</i4>"),
+        ])
+        .await;
+        let settings = FlrigSettings {
+            use_get_info: Some(true),
+            ..flrig.settings()
+        };
+        let rig = FLRig::new(settings, "test".to_string());
+        let data = rig.fetch_radio_data().await.unwrap();
+        assert_eq!(data.frequency, "14074000");
+        assert_eq!(data.rig_mode, "D-USB");
+        assert_eq!(data.power, "30");
+        assert!(!flrig.methods().contains(&"rig.get_vfo".to_string()));
+    }
+
+    #[tokio::test]
+    async fn fetch_radio_data_falls_back_without_get_info() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>7074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>50</i4>"),
+        ])
+        .await;
+        let settings = FlrigSettings {
+            use_get_info: Some(true),
+            ..flrig.settings()
+        };
+        let rig = FLRig::new(settings, "test".to_string());
+        assert_eq!(rig.fetch_radio_data().await.unwrap().frequency, "7074000");
+        assert_eq!(rig.fetch_radio_data().await.unwrap().rig_mode, "USB");
+        // Given up on after the first fault.
+        let asked = flrig
+            .methods()
+            .iter()
+            .filter(|m| *m == "rig.get_info")
+            .count();
+        assert_eq!(asked, 1);
+    }
+
+    #[test]
+    fn mode_bw_index_applies_data_mode_entry() {
+        let table = [(Mode::D_USB, 3), (Mode::USB_D, 2)];
+        assert_eq!(mode_bw_index(Mode::D_USB, &table, None), Some(3));
+        assert_eq!(mode_bw_index(Mode::USB_D, &table, Some(1)), Some(2));
+        assert_eq!(mode_bw_index(Mode::USB, &table, Some(1)), None);
+        // cw_bw_index still covers CW when the table has no CW entry...
+        assert_eq!(mode_bw_index(Mode::CW, &table, Some(1)), Some(1));
+        // ...and an explicit CW entry wins over it.
+        assert_eq!(mode_bw_index(Mode::CW, &[(Mode::CW, 0)], Some(1)), Some(0));
+    }
+
+    #[tokio::test]
+    async fn restore_tunes_to_the_saved_state_in_one_qsy() {
+        let flrig = MockFlrig::start(&[
+            ("rig.set_vfo", "<string></string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+        ])
+        .await;
+        let dir = std::env::temp_dir().join("wlrigctl-restore-on-start");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rig-state.json");
+        std::fs::write(&path, r#"{"frequency": 7030000, "mode": "CW"}"#).unwrap();
+
+        let rig = FLRig::new(flrig.settings(), "test".to_string())
+            .with_state_file(RigStateFile::load(&path));
+        let restored = rig.restore_saved_state().await.unwrap();
+        assert_eq!(
+            restored,
+            Some(SavedRigState {
+                frequency: 7_030_000,
+                mode: "CW".to_string(),
+            })
+        );
+        assert_eq!(
+            flrig.methods(),
+            vec!["rig.set_vfo", "rig.get_mode", "rig.set_mode"]
+        );
+        assert!(flrig.last_call("rig.set_vfo").unwrap().contains("7030000"));
+        assert!(flrig.last_call("rig.set_mode").unwrap().contains("CW"));
+    }
+
+    #[tokio::test]
+    async fn restore_without_saved_state_leaves_the_rig_alone() {
+        let flrig = MockFlrig::start(&[]).await;
+        let dir = std::env::temp_dir().join("wlrigctl-restore-nothing");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let rig = FLRig::new(flrig.settings(), "test".to_string())
+            .with_state_file(RigStateFile::load(dir.join("rig-state.json")));
+        assert_eq!(rig.restore_saved_state().await.unwrap(), None);
+        assert!(flrig.methods().is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_mode_applies_data_mode_bandwidth() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+            ("rig.set_bw", "<i4>0</i4>"),
+        ])
+        .await;
+        let table = [("D-USB".to_string(), 3)].into_iter().collect();
+        let rig = FLRig::new(
+            FlrigSettings {
+                mode_bw_index: Some(table),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+
+        rig.set_mode(Mode::D_USB, false).await.unwrap();
+        assert_eq!(
+            flrig.methods(),
+            vec!["rig.get_mode", "rig.set_mode", "rig.set_bw"]
+        );
+        assert!(flrig
+            .last_call("rig.set_bw")
+            .unwrap()
+            .contains("<i4>3</i4>"));
+
+        // No entry for USB: the bandwidth is left alone.
+        rig.set_mode(Mode::USB, false).await.unwrap();
+        assert_eq!(
+            flrig
+                .methods()
+                .iter()
+                .filter(|m| *m == "rig.set_bw")
+                .count(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn narrow_delay_separates_mode_change_from_bandwidth() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_mode", "<i4>0</i4>"),
+            ("rig.set_bw", "<i4>0</i4>"),
+        ])
+        .await;
+        let rig = FLRig::new(
+            FlrigSettings {
+                cw_bw_index: Some(1),
+                narrow_delay_ms: Some(300),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+
+        let started = Instant::now();
+        rig.set_mode(Mode::CW, false).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert_eq!(
+            flrig.methods(),
+            vec!["rig.get_mode", "rig.set_mode", "rig.set_bw"]
+        );
+
+        // Already in CW: nothing to settle, so no wait.
+        let flrig = MockFlrig::start(&[
+            ("rig.get_mode", "<string>CW</string>"),
+            ("rig.set_bw", "<i4>0</i4>"),
+        ])
+        .await;
+        let rig = FLRig::new(
+            FlrigSettings {
+                cw_bw_index: Some(1),
+                narrow_delay_ms: Some(5000),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+        let started = Instant::now();
+        rig.set_mode(Mode::CW, false).await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(5000));
+        assert_eq!(flrig.methods(), vec!["rig.get_mode", "rig.set_bw"]);
+    }
+
+    #[tokio::test]
+    async fn set_mode_b_reports_shared_mode_as_unsupported() {
+        let flrig = MockFlrig::start(&[
+            ("rig.set_modeB", "<i4>0</i4>"),
+            ("rig.get_modeB", "<string>USB</string>"),
+        ])
+        .await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+
+        rig.set_mode_b(Mode::USB).await.unwrap();
+        assert!(matches!(
+            rig.set_mode_b(Mode::FM).await,
+            Err(FlrigError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn normalize_freq_ignores_decimal_formatting() {
+        assert_eq!(normalize_freq("14074000.0"), normalize_freq("14074000"));
+        assert_eq!(normalize_freq("14074000.000000"), "14074000");
+        assert_eq!(normalize_freq(" 7074000 "), "7074000");
+    }
+
+    #[test]
+    fn set_check_allows_tuning_step_only() {
+        assert!(set_took_effect(
+            14_074_000.0,
+            Mode::USB,
+            Some(14_074_000.0),
+            "USB"
+        ));
+        assert!(set_took_effect(
+            14_074_005.0,
+            Mode::USB,
+            Some(14_074_000.0),
+            "USB"
+        ));
+        assert!(!set_took_effect(
+            14_074_000.0,
+            Mode::USB,
+            Some(14_000_000.0),
+            "USB"
+        ));
+        assert!(!set_took_effect(
+            14_074_000.0,
+            Mode::D_USB,
+            Some(14_074_000.0),
+            "USB"
+        ));
+        assert!(!set_took_effect(14_074_000.0, Mode::USB, None, "USB"));
+    }
+
+    #[test]
+    fn normalize_freq_passes_garbage_through() {
+        assert_eq!(normalize_freq("unknown"), "unknown");
+    }
+
+    fn limits(entries: &[(&str, u32)]) -> Vec<(Mode, u32)> {
+        let map: HashMap<String, u32> = entries.iter().map(|(m, w)| (m.to_string(), *w)).collect();
+        parse_mode_power_limits(Some(&map))
+    }
+
+    #[test]
+    fn stepped_power_clamps_at_the_ends() {
+        assert_eq!(stepped_power(50, 10, true, 100), 60);
+        assert_eq!(stepped_power(50, 10, false, 100), 40);
+        assert_eq!(stepped_power(95, 10, true, 100), 100);
+        assert_eq!(stepped_power(100, 10, true, 100), 100);
+        assert_eq!(stepped_power(5, 10, false, 100), 0);
+        assert_eq!(stepped_power(0, 10, false, 100), 0);
+        // Above a since-lowered maxpower: up stays at it, down steps from it.
+        assert_eq!(stepped_power(100, 1, true, 10), 10);
+        assert_eq!(stepped_power(100, 1, false, 10), 9);
+    }
+
+    #[tokio::test]
+    async fn step_power_watts_clamps_to_maxpower() {
+        // 90 of 100 on a 10W rig: 9W, so a 5W step up stops at 10W.
+        let flrig = MockFlrig::start(&[
+            ("rig.get_power", "<i4>90</i4>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_power", "<i4>0</i4>"),
+        ])
+        .await;
+        let rig = FLRig::new(
+            FlrigSettings {
+                maxpower: 10,
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+
+        assert_eq!(rig.step_power_watts(5, true).await.unwrap(), 10);
+        let set = flrig.last_call("rig.set_power").unwrap();
+        assert!(set.contains("<i4>100</i4>"), "{set}");
+
+        assert_eq!(rig.step_power_watts(5, false).await.unwrap(), 4);
+        let set = flrig.last_call("rig.set_power").unwrap();
+        assert!(set.contains("<i4>40</i4>"), "{set}");
+    }
+
+    #[test]
+    fn power_cap_lookup_by_mode() {
+        let l = limits(&[("D-USB", 30), ("RTTY", 25)]);
+        assert_eq!(power_cap(&l, Mode::D_USB), Some(30));
+        assert_eq!(power_cap(&l, Mode::RTTY), Some(25));
+        assert_eq!(power_cap(&l, Mode::CW), None);
+        assert_eq!(power_cap(&l, Mode::USB), None);
+    }
+
+    #[test]
+    fn power_limits_skip_unknown_modes() {
+        let l = limits(&[("D-USB", 30), ("WIBBLE", 5)]);
+        assert_eq!(l, vec![(Mode::D_USB, 30)]);
+        assert!(parse_mode_power_limits(None).is_empty());
+    }
+
+    #[test]
+    fn watts_to_rig_power_inverts_rig_power_watts() {
+        assert_eq!(watts_to_rig_power(30, 100, 100), 30);
+        assert_eq!(watts_to_rig_power(5, 100, 10), 50);
+        assert_eq!(watts_to_rig_power(5, 100, 0), 0);
+        assert_eq!(
+            rig_power_watts(watts_to_rig_power(30, 100, 100) as u32, 100, 100),
+            "30"
+        );
+    }
+
+    #[test]
+    fn s_units_scale() {
+        let s_units = |raw| SmeterCalibration::default().s_units(raw);
+        assert_eq!(s_units(0), "S0");
+        assert_eq!(s_units(28), "S5");
+        assert_eq!(s_units(50), "S9");
+        assert_eq!(s_units(67), "S9+20");
+        assert_eq!(s_units(100), "S9+60");
+    }
+
+    #[test]
+    fn s_units_clamps_out_of_range() {
+        let s_units = |raw| SmeterCalibration::default().s_units(raw);
+        assert_eq!(s_units(-5), "S0");
+        assert_eq!(s_units(250), "S9+60");
+    }
+
+    #[test]
+    fn smeter_calibration_interpolates_table() {
+        // A rig whose meter reads S9 at 120 and S9+40 at 200 on a 0-255 scale.
+        let cal = SmeterCalibration::new(Some(&[[200.0, 40.0], [0.0, -54.0], [120.0, 0.0]]));
+        assert_eq!(cal.db_over_s9(0), -54.0);
+        assert_eq!(cal.db_over_s9(60), -27.0);
+        assert_eq!(cal.db_over_s9(120), 0.0);
+        assert_eq!(cal.db_over_s9(160), 20.0);
+        assert_eq!(cal.db_over_s9(255), 40.0);
+        assert_eq!(cal.s_units(60), "S5");
+        assert_eq!(cal.s_units(160), "S9+20");
+        assert_eq!(
+            cal.reading(100),
+            SmeterReading {
+                raw: 100,
+                s_units: "S8".to_string(),
+                db_over_s9: -9.0,
+            }
+        );
+    }
+
+    #[test]
+    fn smeter_calibration_rejects_short_table() {
+        let cal = SmeterCalibration::new(Some(&[[50.0, 0.0]]));
+        assert_eq!(cal.s_units(67), "S9+20");
+    }
+
+    #[test]
+    fn parse_current_mode_empty_string_is_unknown_not_error() {
+        for raw in ["", "   "] {
+            let current = parse_current_mode(raw).expect("empty mode must not be an error");
+            assert_eq!(current, None);
+            // set_mode compares against Some(target), so the set proceeds.
+            assert_ne!(current, Some(Mode::USB));
+        }
+    }
+
+    #[test]
+    fn parse_current_mode_known_and_unknown() {
+        assert_eq!(parse_current_mode("CW").unwrap(), Some(Mode::CW));
+        assert!(matches!(
+            parse_current_mode("WIBBLE"),
+            Err(FlrigError::UnknownMode(_))
+        ));
+    }
+
     #[test]
     fn mode_map_defaults_to_icom() {
         let m = build_mode_map(None, None, None);
@@ -464,6 +2526,17 @@ mod tests {
         assert_eq!(m.digital, Mode::DATA);
     }
 
+    #[test]
+    fn digi_fallback_defaults_to_rtty() {
+        let m = build_mode_map(None, Some("FSK"), None);
+        assert_eq!(m.digi_outside_ft8(), Mode::FSK);
+        let m = m.with_digi_fallback(Some("D-USB"));
+        assert_eq!(m.digi_outside_ft8(), Mode::D_USB);
+        assert_eq!(m.rtty, Mode::FSK);
+        let m = build_mode_map(None, None, None).with_digi_fallback(Some("PKT-U"));
+        assert_eq!(m.digi_outside_ft8(), Mode::RTTY);
+    }
+
     #[test]
     fn mode_map_unknown_mode_falls_back_to_default() {
         let m = build_mode_map(Some("CW-UNKNOWN"), Some("FSK"), Some("D-USB"));
@@ -592,4 +2665,113 @@ mod tests {
         assert_eq!(rig_power_watts(80, 80, 100), "100");
         assert_eq!(rig_power_watts(255, 255, 50), "50");
     }
+
+    #[test]
+    fn maxpower_mismatch_flags_a_rig_reporting_other_watts() {
+        // Percentage and raw scales say nothing about the rig's wattage.
+        assert_eq!(maxpower_mismatch(5, 100), None);
+        assert_eq!(maxpower_mismatch(1500, 100), None);
+        assert_eq!(maxpower_mismatch(50, 255), None);
+        // Watts within a factor of two of maxpower pass.
+        assert_eq!(maxpower_mismatch(100, 80), None);
+        assert_eq!(maxpower_mismatch(10, 5), None);
+        // A 5W rig configured as 100W, and the other way round.
+        assert!(maxpower_mismatch(100, 5).unwrap().contains("maximum of 5"));
+        assert!(maxpower_mismatch(5, 50).is_some());
+        assert!(maxpower_mismatch(100, 0).is_some());
+    }
+
+    #[tokio::test]
+    async fn maxpower_warning_follows_the_latest_reading() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>5</i4>"),
+            ("rig.get_power", "<i4>5</i4>"),
+        ])
+        .await;
+        let rig = FLRig::new(flrig.settings(), "test".to_string());
+        assert_eq!(rig.maxpower_warning(), None);
+
+        rig.fetch_radio_data().await.unwrap();
+        let warning = rig.maxpower_warning().expect("mismatch not noticed");
+        assert!(warning.contains("maxpower is 100W"), "{warning}");
+
+        // Not checked at all when the readings are in watts.
+        let rig = FLRig::new(
+            FlrigSettings {
+                power_is_watts: Some(true),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+        rig.fetch_radio_data().await.unwrap();
+        assert_eq!(rig.maxpower_warning(), None);
+    }
+
+    #[tokio::test]
+    async fn power_reading_is_a_fraction_or_watts() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_vfo", "<string>14074000</string>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.get_maxpwr", "<i4>255</i4>"),
+            ("rig.get_power", "<i4>25</i4>"),
+        ])
+        .await;
+        let rig = |power_is_watts| {
+            FLRig::new(
+                FlrigSettings {
+                    maxpower: 50,
+                    power_is_watts: Some(power_is_watts),
+                    ..flrig.settings()
+                },
+                "test".to_string(),
+            )
+        };
+
+        // 25 of 255 on a 50W rig.
+        let data = rig(false).fetch_radio_data().await.unwrap();
+        assert_eq!(data.power, "5");
+        // 25W as it stands.
+        let data = rig(true).fetch_radio_data().await.unwrap();
+        assert_eq!(data.power, "25");
+
+        // A reading in watts is still capped at maxpower.
+        let rig = FLRig::new(
+            FlrigSettings {
+                maxpower: 20,
+                power_is_watts: Some(true),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+        assert_eq!(rig.fetch_radio_data().await.unwrap().power, "20");
+    }
+
+    #[tokio::test]
+    async fn power_is_watts_sets_watts_unscaled() {
+        let flrig = MockFlrig::start(&[
+            ("rig.get_power", "<i4>30</i4>"),
+            ("rig.get_maxpwr", "<i4>255</i4>"),
+            ("rig.get_mode", "<string>USB</string>"),
+            ("rig.set_power", "<i4>0</i4>"),
+        ])
+        .await;
+        let rig = FLRig::new(
+            FlrigSettings {
+                maxpower: 50,
+                power_is_watts: Some(true),
+                ..flrig.settings()
+            },
+            "test".to_string(),
+        );
+
+        assert_eq!(rig.step_power_watts(5, true).await.unwrap(), 35);
+        let set = flrig.last_call("rig.set_power").unwrap();
+        assert!(set.contains("<i4>35</i4>"), "{set}");
+
+        assert_eq!(rig.set_power_watts(80, Mode::USB).await.unwrap(), 50);
+        let set = flrig.last_call("rig.set_power").unwrap();
+        assert!(set.contains("<i4>50</i4>"), "{set}");
+    }
 }
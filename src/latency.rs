@@ -0,0 +1,144 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// How many of the most recent round trips each window keeps.  Enough for a
+// steady p95 at the default poll rate without holding more than a few KiB.
+const WINDOW: usize = 256;
+
+// The latest round-trip times of one kind of call, oldest first.
+#[derive(Default)]
+struct LatencyWindow {
+    samples: VecDeque<Duration>,
+}
+
+impl LatencyWindow {
+    fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(elapsed);
+    }
+
+    fn summary(&self) -> Option<LatencySummary> {
+        let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let (min, max) = (*sorted.first()?, *sorted.last()?);
+        let total: Duration = sorted.iter().sum();
+        // Nearest-rank percentile: the smallest sample at or above 95% of them.
+        let rank = (sorted.len() * 95).div_ceil(100).max(1);
+        Some(LatencySummary {
+            samples: sorted.len(),
+            min_ms: millis(min),
+            avg_ms: millis(total / sorted.len() as u32),
+            max_ms: millis(max),
+            p95_ms: millis(sorted[rank - 1]),
+        })
+    }
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+/// Aggregate of a window of round-trip times, in milliseconds.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Rolling round-trip times of FLRig calls and Wavelog uploads, for telling
+/// a slow rig link from a slow logbook server.  Only calls that got an
+/// answer are recorded; failures are counted elsewhere.
+#[derive(Default)]
+pub struct LatencyStats {
+    flrig: Mutex<LatencyWindow>,
+    wavelog: Mutex<LatencyWindow>,
+}
+
+/// One set of stats shared by every task that talks to FLRig or Wavelog.
+pub type SharedLatency = Arc<LatencyStats>;
+
+impl LatencyStats {
+    pub fn record_flrig(&self, elapsed: Duration) {
+        self.flrig.lock().unwrap().record(elapsed);
+    }
+
+    pub fn record_wavelog(&self, elapsed: Duration) {
+        self.wavelog.lock().unwrap().record(elapsed);
+    }
+
+    /// Summary of the FLRig window, or None before the first answer.
+    pub fn flrig(&self) -> Option<LatencySummary> {
+        self.flrig.lock().unwrap().summary()
+    }
+
+    /// Summary of the Wavelog window, or None before the first upload.
+    pub fn wavelog(&self) -> Option<LatencySummary> {
+        self.wavelog.lock().unwrap().summary()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn empty_window_has_no_summary() {
+        let stats = LatencyStats::default();
+        assert_eq!(stats.flrig(), None);
+        assert_eq!(stats.wavelog(), None);
+    }
+
+    #[test]
+    fn summary_of_recorded_latencies() {
+        let stats = LatencyStats::default();
+        for n in [40, 10, 30, 20, 100] {
+            stats.record_flrig(ms(n));
+        }
+        stats.record_wavelog(ms(250));
+        assert_eq!(
+            stats.flrig(),
+            Some(LatencySummary {
+                samples: 5,
+                min_ms: 10.0,
+                avg_ms: 40.0,
+                max_ms: 100.0,
+                p95_ms: 100.0,
+            })
+        );
+        assert_eq!(stats.wavelog().unwrap().p95_ms, 250.0);
+    }
+
+    #[test]
+    fn p95_is_nearest_rank() {
+        let stats = LatencyStats::default();
+        for n in 1..=100 {
+            stats.record_wavelog(ms(n));
+        }
+        let summary = stats.wavelog().unwrap();
+        assert_eq!(summary.p95_ms, 95.0);
+        assert_eq!(summary.avg_ms, 50.5);
+    }
+
+    #[test]
+    fn window_keeps_only_the_latest_samples() {
+        let stats = LatencyStats::default();
+        stats.record_flrig(ms(5000));
+        for _ in 0..WINDOW {
+            stats.record_flrig(ms(20));
+        }
+        let summary = stats.flrig().unwrap();
+        assert_eq!(summary.samples, WINDOW);
+        assert_eq!(summary.max_ms, 20.0);
+    }
+}
@@ -0,0 +1,234 @@
+use env_logger::filter::{Builder, Filter};
+use log::{Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use syslog::{BasicLogger, Facility, Formatter3164};
+
+/// Where log output goes.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    /// env_logger on stderr (the default).  systemd captures this in the
+    /// journal, but every line lands at the same priority.
+    #[default]
+    Stderr,
+    /// The local syslog socket, with log levels mapped to syslog severities.
+    Syslog,
+}
+
+// Settings from .toml file.  The [logging] section is optional.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LoggingSettings {
+    #[serde(default)]
+    pub target: LogTarget,
+    /// An error that keeps recurring is logged once, then at most once per
+    /// this many seconds with a count of the repeats in between.
+    pub repeat_window_secs: Option<u64>,
+}
+
+pub const DEFAULT_REPEAT_WINDOW_SECS: u64 = 60;
+
+// Set once by init_logging, like the logger itself.
+static REPEAT_WINDOW: OnceLock<Duration> = OnceLock::new();
+
+fn repeat_window() -> Duration {
+    *REPEAT_WINDOW
+        .get()
+        .unwrap_or(&Duration::from_secs(DEFAULT_REPEAT_WINDOW_SECS))
+}
+
+/// Holds back repeats of the same error message, so a loop that fails on
+/// every pass (FLRig or Wavelog down, a stray sender on the WSJT-X port)
+/// does not flood the log.  The first occurrence is logged straight away;
+/// later identical ones within the window are only counted, and the first
+/// one after the window is logged with that count.
+pub struct RepeatedErrors {
+    window: Duration,
+    seen: HashMap<String, Seen>,
+}
+
+struct Seen {
+    logged: Instant,
+    last: Instant,
+    // Occurrences held back since `logged`.
+    repeats: u64,
+}
+
+impl Default for RepeatedErrors {
+    fn default() -> Self {
+        Self::new(repeat_window())
+    }
+}
+
+impl RepeatedErrors {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// The line to log for `msg` at `now`, or None if it is a repeat to hold
+    /// back.
+    pub fn check(&mut self, msg: &str, now: Instant) -> Option<String> {
+        let window = self.window;
+        // Forget messages that have not recurred for a whole window, so the
+        // map stays small and a much later recurrence is logged afresh.
+        self.seen
+            .retain(|_, seen| now.duration_since(seen.last) < window);
+        let Some(seen) = self.seen.get_mut(msg) else {
+            self.seen.insert(
+                msg.to_string(),
+                Seen {
+                    logged: now,
+                    last: now,
+                    repeats: 0,
+                },
+            );
+            return Some(msg.to_string());
+        };
+        seen.last = now;
+        if now.duration_since(seen.logged) < window {
+            seen.repeats += 1;
+            return None;
+        }
+        let line = format!(
+            "{msg} (still failing, {} occurrences in the last {}s)",
+            seen.repeats + 1,
+            now.duration_since(seen.logged).as_secs()
+        );
+        seen.logged = now;
+        seen.repeats = 0;
+        Some(line)
+    }
+
+    /// Log `msg` at `level` unless it is a repeat to hold back.
+    pub fn log(&mut self, level: log::Level, msg: &str) {
+        if let Some(line) = self.check(msg, Instant::now()) {
+            log::log!(level, "{line}");
+        }
+    }
+}
+
+// syslog's BasicLogger has no notion of per-module filtering, so wrap it in
+// the same RUST_LOG filter env_logger uses.  RUST_LOG therefore behaves
+// identically whichever target is selected.
+struct SyslogLogger {
+    inner: BasicLogger,
+    filter: Filter,
+}
+
+impl Log for SyslogLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.filter.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+fn init_syslog() -> Result<(), String> {
+    let formatter = Formatter3164 {
+        facility: Facility::LOG_USER,
+        hostname: None,
+        process: env!("CARGO_PKG_NAME").to_string(),
+        pid: std::process::id(),
+    };
+    let logger = syslog::unix(formatter).map_err(|e| e.to_string())?;
+    let filter = Builder::from_env("RUST_LOG").build();
+    let max_level = filter.filter();
+
+    log::set_boxed_logger(Box::new(SyslogLogger {
+        inner: BasicLogger::new(logger),
+        filter,
+    }))
+    .map_err(|e| e.to_string())?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Install the global logger selected by `[logging] target`.  Falls back to
+/// env_logger if the syslog socket cannot be opened.
+pub fn init_logging(settings: &LoggingSettings) {
+    if let Some(secs) = settings.repeat_window_secs {
+        let _ = REPEAT_WINDOW.set(Duration::from_secs(secs));
+    }
+    match settings.target {
+        LogTarget::Stderr => env_logger::init(),
+        LogTarget::Syslog => {
+            if let Err(e) = init_syslog() {
+                env_logger::init();
+                log::warn!("Could not connect to syslog ({e}), logging to stderr");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeats_within_the_window_are_logged_once() {
+        let mut errors = RepeatedErrors::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let logged: Vec<String> = (0..10)
+            .filter_map(|i| errors.check("FLRig down", start + Duration::from_secs(i)))
+            .collect();
+        assert_eq!(logged, vec!["FLRig down".to_string()]);
+
+        // A different error is not held back by the first.
+        assert!(errors.check("Wavelog down", start).is_some());
+
+        // After the window, one summary line counts what was held back.
+        assert_eq!(
+            errors
+                .check("FLRig down", start + Duration::from_secs(61))
+                .as_deref(),
+            Some("FLRig down (still failing, 10 occurrences in the last 61s)")
+        );
+        assert!(errors
+            .check("FLRig down", start + Duration::from_secs(62))
+            .is_none());
+    }
+
+    #[test]
+    fn error_that_stops_recurring_is_forgotten() {
+        let mut errors = RepeatedErrors::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(errors.check("FLRig down", start).is_some());
+        // Seen once, then quiet for a whole window: logged afresh.
+        assert_eq!(
+            errors
+                .check("FLRig down", start + Duration::from_secs(120))
+                .as_deref(),
+            Some("FLRig down")
+        );
+    }
+
+    #[test]
+    fn error_that_recurs_much_later_is_logged_afresh() {
+        let mut errors = RepeatedErrors::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(errors.check("FLRig down", start).is_some());
+        assert!(errors
+            .check("FLRig down", start + Duration::from_secs(1))
+            .is_none());
+        // Held back once, then quiet for a day: not a summary of that day.
+        assert_eq!(
+            errors
+                .check("FLRig down", start + Duration::from_secs(86_400))
+                .as_deref(),
+            Some("FLRig down")
+        );
+    }
+}
@@ -1,22 +1,53 @@
+mod adif;
+mod allow;
+mod bandmem;
 mod cat;
 mod flrig;
+mod latency;
+mod logging;
+#[cfg(test)]
+mod mock_flrig;
+#[cfg(test)]
+mod mock_wavelog;
+mod mqtt;
+mod rigstate;
 mod settings;
+mod spool;
+mod statefile;
+mod supervise;
 mod wavelog;
 mod ws;
 mod wsjtx;
 
 use std::process;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
-use log::info;
-use tokio::sync::watch;
+use log::{error, info, warn};
+use tokio::sync::{broadcast, mpsc, watch};
 use tokio_util::sync::CancellationToken;
 
-use crate::cat::CAT_thread;
-use crate::settings::Settings;
-use crate::wavelog::wavelog_thread;
+use crate::bandmem::BandModeMemory;
+use crate::cat::{CAT_thread, CatHandles};
+use crate::latency::SharedLatency;
+use crate::logging::init_logging;
+use crate::mqtt::mqtt_thread;
+use crate::rigstate::RigStateFile;
+use crate::settings::{Settings, SharedLiveSettings};
+use crate::spool::{spool_thread, QsoSpool};
+use crate::supervise::join_with_timeout;
+use crate::wavelog::{wavelog_thread, RadioCache, RigPublishers};
 use crate::ws::ws_thread;
-use crate::wsjtx::wsjtx_thread;
+use crate::wsjtx::{wsjtx_thread, WsjtxLink};
+
+// How long the poll loop, WSJT-X listener and QSO spool get to finish what
+// they are doing (an upload, a QSO being logged, a final flush) once
+// shutdown starts.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+// How often restore_on_start asks FLRig again while it is not answering,
+// e.g. because it starts after wlrigctl at boot.
+const RESTORE_RETRY: Duration = Duration::from_secs(5);
 
 #[cfg(unix)]
 async fn shutdown_signal() {
@@ -33,48 +64,264 @@ async fn shutdown_signal() {
     tokio::signal::ctrl_c().await.ok();
 }
 
+// One QSY back to where the rig was before the last shutdown, made once
+// FLRig first answers a read, however long that takes.
+async fn restore_saved_state(rig: Arc<flrig::FLRig>, token: CancellationToken) {
+    let mut waiting_logged = false;
+    while let Err(e) = rig.fetch_radio_data().await {
+        if !waiting_logged {
+            info!("restore_on_start: waiting for FLRig to answer ({e})");
+            waiting_logged = true;
+        }
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = tokio::time::sleep(RESTORE_RETRY) => {}
+        }
+    }
+    match rig.restore_saved_state().await {
+        Ok(Some(saved)) => info!("Restored the rig to {} Hz {}", saved.frequency, saved.mode),
+        Ok(None) => info!("restore_on_start: no saved rig state yet"),
+        Err(e) => warn!("Could not restore the saved rig state: {e}"),
+    }
+}
+
+// Re-read the config file on SIGHUP and apply the hot-reloadable settings.
+// Anything that needs the listeners rebinding is only reported.
+#[cfg(unix)]
+async fn reload_on_sighup(
+    live: SharedLiveSettings,
+    rig: Arc<flrig::FLRig>,
+    mut listeners: Vec<(&'static str, String)>,
+    token: CancellationToken,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            error!("Could not register SIGHUP handler, config reload disabled: {e}");
+            return;
+        }
+    };
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = sighup.recv() => {}
+        }
+        match Settings::reload(&live) {
+            Ok(settings) => {
+                rig.reload_power_limits(&settings.flrig);
+                let now = settings.listener_addrs();
+                for ((name, old), (_, new)) in listeners.iter().zip(&now) {
+                    if old != new {
+                        warn!("{name} changed from {old} to {new}; restart wlrigctl to apply");
+                    }
+                }
+                listeners = now;
+                info!("Configuration reloaded");
+            }
+            Err(e) => error!("Config reload failed, keeping current settings: {e}"),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn reload_on_sighup(
+    _live: SharedLiveSettings,
+    _rig: Arc<flrig::FLRig>,
+    _listeners: Vec<(&'static str, String)>,
+    _token: CancellationToken,
+) {
+}
+
+// `wlrigctl --init`: write the commented example config for a first run.
+fn init_config() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = Settings::config_file()?;
+    if let Err(e) = Settings::write_template(&path) {
+        eprintln!("Could not write {}: {e}", path.display());
+        process::exit(1)
+    }
+    println!(
+        "Wrote example config to {}; edit it before starting wlrigctl.",
+        path.display()
+    );
+    Ok(())
+}
+
+// `wlrigctl --test-upload`: send made-up rig state to the live-radio URL, to
+// check the URL and API key without a rig, and report how Wavelog answered.
+async fn test_upload(settings: &Settings) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let url = &settings.wavelog.url;
+    match wavelog::test_upload(&settings.wavelog).await {
+        Ok(status) => {
+            println!("Test upload to {url} accepted: {status}");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Test upload to {url} failed: {e}");
+            process::exit(1)
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    env_logger::init();
+    if std::env::args().nth(1).as_deref() == Some("--init") {
+        return init_config();
+    }
+
+    // Settings are read before logging starts, because they choose where
+    // the log goes; failures here can only be reported on stderr.
+    let settings = Settings::new().unwrap_or_else(|err| {
+        eprintln!("Could not read settings: {err}");
+        process::exit(1)
+    });
+
+    if std::env::args().nth(1).as_deref() == Some("--test-upload") {
+        return test_upload(&settings).await;
+    }
+
+    init_logging(&settings.logging);
 
     let appname = env!("CARGO_PKG_NAME");
     let appver = env!("CARGO_PKG_VERSION");
 
     info!("{appname} v{appver} started.");
+    if let Ok(path) = Settings::discover_config_file() {
+        info!("Using config file {}", path.display());
+    }
 
-    let settings = Settings::new().unwrap_or_else(|err| {
-        eprintln!("Could not read settings: {err}");
-        process::exit(1)
-    });
+    // Captured before the settings are split up between the threads below.
+    let config_json = settings.redacted_json();
+    let live: SharedLiveSettings = Arc::new(RwLock::new(settings.live()));
+    let listeners = settings.listener_addrs();
 
     let radio_id: String = settings.wavelog.identifier.clone();
-    let rig = Arc::new(flrig::FLRig::new(settings.flrig, radio_id));
+    let restore_on_start = settings.flrig.restore_on_start.unwrap_or(false);
+    let state_file = settings.flrig.state_file.clone().map_or_else(
+        || Settings::config_dir().map(|dir| dir.join("rig-state.json")),
+        |path| Ok(path.into()),
+    );
+    let band_mode_file = settings.flrig.band_mode_file.clone();
+    // Round-trip times of FLRig calls and Wavelog uploads, for CAT /stats.
+    let latency = SharedLatency::default();
+    let mut rig = flrig::FLRig::new(settings.flrig, radio_id).with_latency(latency.clone());
+    if let Some(path) = &band_mode_file {
+        rig = rig.with_band_memory(BandModeMemory::load(path));
+    }
+    if restore_on_start {
+        match &state_file {
+            Ok(path) => rig = rig.with_state_file(RigStateFile::load(path)),
+            Err(e) => warn!("restore_on_start: no config directory for the state file: {e}"),
+        }
+    }
+    let rig = Arc::new(rig);
 
     let token = CancellationToken::new();
 
+    if restore_on_start {
+        tokio::spawn(restore_saved_state(rig.clone(), token.clone()));
+    }
+
     // Watch channel for streaming live radio state to WebSocket clients.
     // watch holds the latest value; new subscribers receive it immediately on connect.
     let (ws_tx, ws_rx) = watch::channel::<Option<Arc<wavelog::RadioData>>>(None);
 
+    // The same changes for CAT /ws subscribers, each of which holds its own
+    // receiver.
+    let (updates, _) = broadcast::channel(16);
+
+    // Requests to the poll loop: CAT /refresh and QSOs logged by WSJT-X.
+    let (refresh_tx, refresh_rx) = mpsc::channel(4);
+
+    // Latest rig state, shared so CAT /status can reuse the poll loop's read.
+    let radio_cache = RadioCache::default();
+
     // polling of FLRig frequency. Issue http requests to wavelog to update live frequency
-    wavelog_thread(settings.wavelog.clone(), rig.clone(), token.clone(), ws_tx);
+    let poll_task = wavelog_thread(
+        settings.wavelog.clone(),
+        rig.clone(),
+        live.clone(),
+        token.clone(),
+        RigPublishers {
+            ws_tx,
+            updates: updates.clone(),
+        },
+        refresh_rx,
+        radio_cache.clone(),
+        latency.clone(),
+    );
+
+    tokio::spawn(reload_on_sighup(
+        live.clone(),
+        rig.clone(),
+        listeners,
+        token.clone(),
+    ));
+
+    // WSJT-X QSOs Wavelog could not take, retried until it does.
+    let spool = settings.wavelog.qso_spool.as_ref().map(|path| {
+        info!("Keeping WSJT-X QSOs that fail to upload in: {path}");
+        Arc::new(QsoSpool::new(path))
+    });
+    let spool_task = spool.clone().map(|spool| {
+        spool_thread(
+            spool,
+            settings.wavelog.clone(),
+            latency.clone(),
+            token.clone(),
+        )
+    });
 
     // Separate thread for someone logging from WSJTX via UDP on port 2237
-    wsjtx_thread(settings.wsjtx, settings.wavelog, token.clone());
+    let wsjtx = WsjtxLink::default();
+    let wsjtx_task = wsjtx_thread(
+        settings.wsjtx,
+        settings.wavelog.clone(),
+        wsjtx.clone(),
+        refresh_tx.clone(),
+        latency.clone(),
+        spool,
+        token.clone(),
+    );
+
+    // Optional MQTT mirror of the same rig state changes.
+    mqtt_thread(settings.mqtt, ws_rx.clone(), token.clone());
 
     // WebSocket server: push live rig state to browser clients.
     // Always started; [websocket] section in config.toml is optional.
     let config_dir = Settings::config_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
     ws_thread(settings.websocket, config_dir, ws_rx, token.clone());
 
+    let handles = CatHandles {
+        rig: rig.clone(),
+        live,
+        wsjtx,
+        refresh: refresh_tx,
+        radio_cache,
+        updates,
+        latency,
+    };
+
     // Keep the current thread for CAT control requests from Wavelog
     // We gateway these requests back to FLRig after a little bit of massaging
-    tokio::select! {
-        result = CAT_thread(settings.cat, &rig, token.clone()) => result,
+    let result = tokio::select! {
+        result = CAT_thread(settings.cat, settings.wavelog, handles, config_json, token.clone()) => result,
         _ = shutdown_signal() => {
             info!("Shutdown signal received, stopping tasks");
-            token.cancel();
             Ok(())
         }
-    }
+    };
+
+    // Returning from main aborts whatever is still running, so let the tasks
+    // see the cancellation and finish first, then flush what they logged.
+    token.cancel();
+    let mut tasks = vec![
+        ("Wavelog poll loop", poll_task),
+        ("WSJT-X listener", wsjtx_task),
+    ];
+    tasks.extend(spool_task.map(|task| ("QSO spool", task)));
+    join_with_timeout(tasks, SHUTDOWN_GRACE).await;
+    rig.flush_state().await;
+    log::logger().flush();
+    result
 }
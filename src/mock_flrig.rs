@@ -0,0 +1,234 @@
+// A minimal FLRig stand-in for tests: a real XML-RPC server on an ephemeral
+// loopback port, so the production dxr client is exercised unchanged.
+//
+// Replies are canned per method name, as raw XML-RPC <value> contents, e.g.
+// ("rig.get_mode", "<string>USB</string>").  Methods without a canned reply
+// get an XML-RPC fault, like FLRig does for methods it does not know.
+// system.multicall is answered from the same table.  Every call is recorded
+// as (method, request body) for assertions.  drop_connections() makes the
+// next connections close without answering, like a stale keep-alive socket.
+// delay_replies() holds every answer back, like a slow serial link.
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::Response;
+use hyper_util::rt::TokioIo;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+use tokio::time::{sleep, Duration};
+
+use crate::flrig::FlrigSettings;
+
+pub struct MockFlrig {
+    pub port: u16,
+    calls: Arc<Mutex<Vec<(String, String)>>>,
+    drop_next: Arc<AtomicUsize>,
+    delay_ms: Arc<AtomicU64>,
+}
+
+// Text between the first `open` and the following `close` tag.
+fn between<'a>(text: &'a str, open: &str, close: &str) -> Option<&'a str> {
+    let start = text.find(open)? + open.len();
+    let end = text[start..].find(close)? + start;
+    Some(&text[start..end])
+}
+
+fn method_response(value: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><methodResponse><params><param>\
+         <value>{value}</value></param></params></methodResponse>"
+    )
+}
+
+fn fault_response(method: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><methodResponse><fault><value><struct>\
+         <member><name>faultCode</name><value><i4>-1</i4></value></member>\
+         <member><name>faultString</name><value><string>{method}: unknown method</string></value></member>\
+         </struct></value></fault></methodResponse>"
+    )
+}
+
+fn multicall_fault(method: &str) -> String {
+    format!(
+        "<struct><member><name>faultCode</name><value><i4>-1</i4></value></member>\
+         <member><name>faultString</name><value><string>{method}: unknown method</string></value></member></struct>"
+    )
+}
+
+impl MockFlrig {
+    pub async fn start(replies: &[(&str, &str)]) -> MockFlrig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let replies_srv: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(
+            replies
+                .iter()
+                .map(|(m, v)| (m.to_string(), v.to_string()))
+                .collect(),
+        ));
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let drop_next = Arc::new(AtomicUsize::new(0));
+        let delay_ms = Arc::new(AtomicU64::new(0));
+
+        let calls_srv = calls.clone();
+        let drop_srv = drop_next.clone();
+        let delay_srv = delay_ms.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let dropping = drop_srv
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok();
+                if dropping {
+                    // Swallow the request, then hang up without a response.
+                    let _ = stream.read(&mut [0u8; 4096]).await;
+                    continue;
+                }
+                let (replies, calls) = (replies_srv.clone(), calls_srv.clone());
+                let delay = delay_srv.clone();
+                tokio::spawn(async move {
+                    let svc = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let (replies, calls) = (replies.clone(), calls.clone());
+                        let delay = Duration::from_millis(delay.load(Ordering::SeqCst));
+                        async move {
+                            let body = req.into_body().collect().await.unwrap().to_bytes();
+                            let body = String::from_utf8_lossy(&body).to_string();
+                            let xml = MockFlrig::answer(&replies, &calls, &body);
+                            sleep(delay).await;
+                            Ok::<_, Infallible>(
+                                Response::builder()
+                                    .header("Content-Type", "text/xml")
+                                    .body(Full::new(Bytes::from(xml)))
+                                    .unwrap(),
+                            )
+                        }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), svc)
+                        .await;
+                });
+            }
+        });
+
+        MockFlrig {
+            port,
+            calls,
+            drop_next,
+            delay_ms,
+        }
+    }
+
+    fn answer(
+        replies: &Mutex<HashMap<String, String>>,
+        calls: &Mutex<Vec<(String, String)>>,
+        body: &str,
+    ) -> String {
+        let method = between(body, "<methodName>", "</methodName>")
+            .unwrap_or_default()
+            .to_string();
+        let replies = replies.lock().unwrap();
+
+        if method == "system.multicall" {
+            // Each inner call is a struct whose methodName member is a string.
+            let mut values = String::new();
+            for chunk in body.split("<name>methodName</name>").skip(1) {
+                let inner = between(chunk, "<string>", "</string>")
+                    .unwrap_or_default()
+                    .to_string();
+                calls.lock().unwrap().push((inner.clone(), String::new()));
+                match replies.get(&inner) {
+                    Some(v) => values.push_str(&format!(
+                        "<value><array><data><value>{v}</value></data></array></value>"
+                    )),
+                    None => values.push_str(&format!("<value>{}</value>", multicall_fault(&inner))),
+                }
+            }
+            return method_response(&format!("<array><data>{values}</data></array>"));
+        }
+
+        calls
+            .lock()
+            .unwrap()
+            .push((method.clone(), body.to_string()));
+        match replies.get(&method) {
+            Some(v) => method_response(v),
+            None => fault_response(&method),
+        }
+    }
+
+    /// Close the next `n` connections after reading the request, without
+    /// answering it.
+    pub fn drop_connections(&self, n: usize) {
+        self.drop_next.store(n, Ordering::SeqCst);
+    }
+
+    /// Wait `ms` milliseconds before sending each answer.  The call is
+    /// recorded on arrival, so `methods()` shows the order calls were made.
+    pub fn delay_replies(&self, ms: u64) {
+        self.delay_ms.store(ms, Ordering::SeqCst);
+    }
+
+    /// Settings pointing an FLRig client at this mock.
+    pub fn settings(&self) -> FlrigSettings {
+        FlrigSettings {
+            host: "http://127.0.0.1".to_string(),
+            port: self.port,
+            maxpower: 100,
+            cw_bw_index: None,
+            mode_bw_index: None,
+            narrow_delay_ms: None,
+            power_on: None,
+            power_off: None,
+            poll_interval_ms: None,
+            report_dsp: None,
+            report_passband: None,
+            report_smeter: None,
+            report_ptt: None,
+            smeter_calibration: None,
+            use_data_mode: None,
+            mode_power_limits: None,
+            enforce_mode_power: None,
+            cw_pitch_hz: None,
+            use_get_info: None,
+            backup_host: None,
+            backup_port: None,
+            failover_after: None,
+            verify_set: None,
+            log_vfob: None,
+            restore_on_start: None,
+            state_file: None,
+            power_is_watts: None,
+            band_mode_file: None,
+        }
+    }
+
+    /// Names of the methods called so far, in order.
+    pub fn methods(&self) -> Vec<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(m, _)| m.clone())
+            .collect()
+    }
+
+    /// Request body of the most recent call to `method`, if any.
+    pub fn last_call(&self, method: &str) -> Option<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(m, _)| m == method)
+            .map(|(_, body)| body.clone())
+    }
+}
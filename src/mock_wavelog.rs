@@ -0,0 +1,80 @@
+// A minimal Wavelog stand-in for tests: an HTTP server on an ephemeral
+// loopback port that answers every request with one status and forwards each
+// request body on a channel for assertions.  Live-radio and QSO posts can be
+// told apart by their JSON: only QSOs have "type": "adif".  mock_wavelog_requests
+// also reports each request's method, and mock_wavelog_replying answers with
+// a body.
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Serve `status` to every request and send each request body on `tx`.
+/// Returns a QSO API URL on the server; any path is answered the same way.
+pub async fn mock_wavelog(status: StatusCode, tx: UnboundedSender<String>) -> String {
+    mock_wavelog_replying(status, "", tx).await
+}
+
+/// As `mock_wavelog`, answering every request with `reply` as the body.
+pub async fn mock_wavelog_replying(
+    status: StatusCode,
+    reply: &'static str,
+    tx: UnboundedSender<String>,
+) -> String {
+    let (requests_tx, mut requests) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some((_, body)) = requests.recv().await {
+            if tx.send(body).is_err() {
+                return;
+            }
+        }
+    });
+    serve(status, reply, requests_tx).await
+}
+
+/// As `mock_wavelog`, sending each request's method with its body.
+pub async fn mock_wavelog_requests(
+    status: StatusCode,
+    tx: UnboundedSender<(Method, String)>,
+) -> String {
+    serve(status, "", tx).await
+}
+
+async fn serve(
+    status: StatusCode,
+    reply: &'static str,
+    tx: UnboundedSender<(Method, String)>,
+) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let tx = tx.clone();
+            tokio::spawn(http1::Builder::new().serve_connection(
+                TokioIo::new(stream),
+                service_fn(move |req: Request<hyper::body::Incoming>| {
+                    let tx = tx.clone();
+                    async move {
+                        let method = req.method().clone();
+                        let body = req.into_body().collect().await.unwrap().to_bytes();
+                        let _ = tx.send((method, String::from_utf8_lossy(&body).into_owned()));
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Full::new(Bytes::from_static(reply.as_bytes())))
+                                .unwrap(),
+                        )
+                    }
+                }),
+            ));
+        }
+    });
+    format!("http://{addr}/index.php/api/qso")
+}
@@ -0,0 +1,199 @@
+use crate::wavelog::RadioData;
+use log::{debug, info, warn};
+use rumqttc::{AsyncClient, MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Pause before polling the broker connection again after it fails; rumqttc
+// reconnects on the next poll.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Optional `[mqtt]` section: mirror each live rig state change to an MQTT
+/// broker, for shack displays and home automation.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MqttSettings {
+    /// Broker host name or address.
+    pub broker: String,
+    /// Broker port.  Defaults to 1883.
+    pub port: Option<u16>,
+    /// Topic the rig state is published on, e.g. "shack/rig".
+    pub topic: String,
+    /// MQTT client id.  Defaults to "wlrigctl".
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Publish with the retain flag, so a subscriber that connects later
+    /// gets the current state straight away.  Defaults to true.
+    pub retain: Option<bool>,
+}
+
+// The JSON published for a rig state: no Wavelog API key, and numbers where
+// the rig gave them.
+fn payload(data: &RadioData) -> Value {
+    json!({
+        "radio": data.radio,
+        "frequency": data.frequency.parse::<u64>().ok(),
+        "mode": data.mode,
+        "rig_mode": data.rig_mode,
+        "power": data.power.parse::<f64>().ok(),
+    })
+}
+
+// Somewhere to send rig state payloads; a broker connection in production.
+trait Publish {
+    async fn publish(&self, payload: Vec<u8>) -> Result<(), String>;
+}
+
+struct Broker {
+    client: AsyncClient,
+    topic: String,
+    retain: bool,
+}
+
+impl Publish for Broker {
+    async fn publish(&self, payload: Vec<u8>) -> Result<(), String> {
+        self.client
+            .publish(&self.topic, QoS::AtMostOnce, self.retain, payload)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+// Publish every state the poll loop announces on `rx` until cancelled.
+async fn mirror_loop<P: Publish>(
+    publisher: P,
+    mut rx: watch::Receiver<Option<Arc<RadioData>>>,
+    token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            changed = rx.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+        let Some(data) = rx.borrow_and_update().clone() else {
+            continue;
+        };
+        if let Err(e) = publisher
+            .publish(payload(&data).to_string().into_bytes())
+            .await
+        {
+            debug!("MQTT publish failed: {e}");
+        }
+    }
+}
+
+/// Mirror the rig state on `rx` (the poll loop's WebSocket channel) to the
+/// broker in `settings`.  Does nothing when there is no `[mqtt]` section.
+pub fn mqtt_thread(
+    settings: Option<MqttSettings>,
+    rx: watch::Receiver<Option<Arc<RadioData>>>,
+    token: CancellationToken,
+) {
+    let Some(settings) = settings else {
+        return;
+    };
+    let port = settings.port.unwrap_or(1883);
+    let client_id = settings.client_id.as_deref().unwrap_or("wlrigctl");
+    let mut options = MqttOptions::new(client_id, &settings.broker, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &settings.username {
+        options.set_credentials(username, settings.password.as_deref().unwrap_or(""));
+    }
+    let (client, mut eventloop) = AsyncClient::new(options, 16);
+    info!(
+        "Mirroring rig state to MQTT {}:{port} topic {}",
+        settings.broker, settings.topic
+    );
+
+    // The event loop does the network I/O; published messages only leave
+    // while it is being polled.
+    let io_token = token.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = io_token.cancelled() => return,
+                event = eventloop.poll() => {
+                    if let Err(e) = event {
+                        warn!("MQTT connection to broker failed: {e}");
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        }
+    });
+
+    let broker = Broker {
+        client,
+        topic: settings.topic,
+        retain: settings.retain.unwrap_or(true),
+    };
+    tokio::spawn(mirror_loop(broker, rx, token));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct MockPublisher(Arc<Mutex<Vec<Value>>>);
+
+    impl Publish for MockPublisher {
+        async fn publish(&self, payload: Vec<u8>) -> Result<(), String> {
+            let value = serde_json::from_slice(&payload).unwrap();
+            self.0.lock().unwrap().push(value);
+            Ok(())
+        }
+    }
+
+    fn radio_data(frequency: &str) -> Arc<RadioData> {
+        Arc::new(RadioData {
+            key: "secret".to_string(),
+            radio: "IC-703".to_string(),
+            frequency: frequency.to_string(),
+            mode: "DIGI".to_string(),
+            rig_mode: "D-USB".to_string(),
+            power: "10".to_string(),
+            cat_url: None,
+            my_grid: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn rig_change_is_published_without_the_api_key() {
+        let (tx, rx) = watch::channel(None);
+        let publisher = MockPublisher::default();
+        let token = CancellationToken::new();
+        let task = tokio::spawn(mirror_loop(publisher.clone(), rx, token.clone()));
+
+        tx.send(Some(radio_data("14074000"))).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while publisher.0.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("nothing published");
+        token.cancel();
+        task.await.unwrap();
+
+        assert_eq!(
+            *publisher.0.lock().unwrap(),
+            vec![json!({
+                "radio": "IC-703",
+                "frequency": 14074000,
+                "mode": "DIGI",
+                "rig_mode": "D-USB",
+                "power": 10.0,
+            })]
+        );
+    }
+}
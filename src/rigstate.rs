@@ -0,0 +1,123 @@
+use crate::statefile::StateFileWriter;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// The rig's frequency and FLRig mode, as saved for `[flrig]
+/// restore_on_start`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SavedRigState {
+    pub frequency: u64,
+    pub mode: String,
+}
+
+// Remembers the last frequency and mode the rig was seen on, persisted to a
+// small JSON file ({"frequency": 7074000, "mode": "USB"}) so an unattended
+// station can be put back there after a power cut.
+pub struct RigStateFile {
+    writer: StateFileWriter<SavedRigState>,
+    // What the file held at startup, for the restore.
+    loaded: Option<SavedRigState>,
+    // The latest state seen, so the file is only rewritten on a change.
+    current: Mutex<Option<SavedRigState>>,
+}
+
+impl RigStateFile {
+    /// Load the state file at `path`.  A missing file means nothing to
+    /// restore; an unreadable or corrupt one is logged and treated the same.
+    /// Must be called inside the Tokio runtime, which runs the writes.
+    pub fn load(path: impl AsRef<Path>) -> RigStateFile {
+        let path = path.as_ref().to_path_buf();
+        let loaded = match std::fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text)
+                .inspect_err(|e| warn!("Ignoring corrupt rig state file {}: {e}", path.display()))
+                .ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!("Could not read rig state file {}: {e}", path.display());
+                None
+            }
+        };
+        info!("Rig state file: {} ({loaded:?})", path.display());
+        RigStateFile {
+            writer: StateFileWriter::spawn(path),
+            current: Mutex::new(loaded.clone()),
+            loaded,
+        }
+    }
+
+    /// The state saved before this run started, if any.
+    pub fn loaded(&self) -> Option<&SavedRigState> {
+        self.loaded.as_ref()
+    }
+
+    /// Record that the rig is on `frequency` in FLRig mode `mode`.  The file
+    /// is rewritten in the background, shortly after either has changed.
+    pub fn observe(&self, frequency: u64, mode: &str) {
+        let state = SavedRigState {
+            frequency,
+            mode: mode.to_string(),
+        };
+        let mut current = self.current.lock().unwrap();
+        if current.as_ref() == Some(&state) {
+            return;
+        }
+        debug!("Saving rig state {state:?}");
+        self.writer.set(state.clone());
+        *current = Some(state);
+    }
+
+    /// Write a change still waiting to be saved, e.g. on shutdown.
+    pub async fn flush(&self) {
+        self.writer.flush().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("rig-state.json")
+    }
+
+    #[tokio::test]
+    async fn missing_file_has_nothing_to_restore() {
+        let state = RigStateFile::load(temp_path("wlrigctl-rigstate-missing"));
+        assert_eq!(state.loaded(), None);
+    }
+
+    #[tokio::test]
+    async fn save_load_round_trip() {
+        let path = temp_path("wlrigctl-rigstate-roundtrip");
+        let state = RigStateFile::load(&path);
+        state.observe(7_074_000, "D-USB");
+        state.observe(14_074_000, "USB");
+        state.flush().await;
+        // Nothing saved before this run, whatever has been seen since.
+        assert_eq!(state.loaded(), None);
+        drop(state);
+
+        let state = RigStateFile::load(&path);
+        assert_eq!(
+            state.loaded(),
+            Some(&SavedRigState {
+                frequency: 14_074_000,
+                mode: "USB".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn corrupt_file_has_nothing_to_restore() {
+        let path = temp_path("wlrigctl-rigstate-corrupt");
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(RigStateFile::load(&path).loaded(), None);
+    }
+}
@@ -1,16 +1,57 @@
-use config::{Config, ConfigError, File};
+use config::{Config, ConfigError, Environment, File, Source};
 use home::home_dir;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
-use crate::cat::CatSettings;
-use crate::flrig::FlrigSettings;
+use crate::cat::{self, CatSettings};
+use crate::flrig::{self, FlrigSettings, ModeMap};
+use crate::logging::LoggingSettings;
+use crate::mqtt::MqttSettings;
 use crate::wavelog::WavelogSettings;
 use crate::ws::WsSettings;
 use crate::wsjtx::WsjtxSettings;
 
-#[derive(Debug, Deserialize)]
+// The commented example config shipped in the .deb, written out by --init.
+const CONFIG_TEMPLATE: &str = include_str!("../packaging/example.toml");
+
+#[derive(Debug)]
+pub enum SettingsError {
+    /// No config file at the expected path -- typically a first run.
+    Missing(PathBuf),
+    /// The file exists but could not be parsed or is missing settings.
+    Invalid(ConfigError),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Missing(path) => write!(
+                f,
+                "no config file found at {0}\n\
+                 Create one from the example with:\n    \
+                 cp /usr/share/wlrigctl/example.toml {0}\n\
+                 or run `wlrigctl --init` to write a commented template there, \
+                 then edit it for your Wavelog, FLRig and WSJT-X setup.",
+                path.display()
+            ),
+            SettingsError::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<ConfigError> for SettingsError {
+    fn from(error: ConfigError) -> Self {
+        SettingsError::Invalid(error)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Settings {
     pub wavelog: WavelogSettings,
     pub flrig: FlrigSettings,
@@ -22,6 +63,58 @@ pub struct Settings {
     /// absent all defaults apply (127.0.0.1:54323, self-signed TLS cert).
     #[serde(default)]
     pub websocket: WsSettings,
+    /// Log destination.  The [logging] section is optional; when absent
+    /// env_logger writes to stderr as before.
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    /// MQTT mirror of the live rig state; off when there is no [mqtt]
+    /// section.
+    pub mqtt: Option<MqttSettings>,
+}
+
+/// The settings a SIGHUP reload can change without restarting anything:
+/// poll and upload intervals, the mode names used for a QSY, the FT8
+/// frequency list and the CW pitch offset.  Tasks read them afresh for every poll or request.
+/// (Per-mode power limits are reloaded into the FLRig client directly.)
+#[derive(Debug, Clone)]
+pub struct LiveSettings {
+    pub poll_interval_ms: u64,
+    pub jitter: f64,
+    pub min_upload_interval_ms: u64,
+    pub keepalive_secs: u64,
+    pub mode_map: ModeMap,
+    pub ft8_freqs: Arc<[f64]>,
+    pub ft8_automode: bool,
+    pub cw_pitch_hz: u32,
+}
+
+pub type SharedLiveSettings = Arc<RwLock<LiveSettings>>;
+
+impl LiveSettings {
+    pub fn new(wavelog: &WavelogSettings, flrig: &FlrigSettings, cat: &CatSettings) -> Self {
+        // Mode names default to ICOM/generic if absent.
+        let mode_map = flrig::build_mode_map(
+            cat.cw_mode.as_deref(),
+            cat.rtty_mode.as_deref(),
+            cat.digital_mode.as_deref(),
+        );
+        let mode_map = if flrig.use_data_mode.unwrap_or(true) {
+            mode_map
+        } else {
+            mode_map.without_data_mode()
+        };
+        let mode_map = mode_map.with_digi_fallback(cat.default_digi_mode.as_deref());
+        LiveSettings {
+            poll_interval_ms: flrig.poll_interval_ms.unwrap_or(wavelog.interval),
+            jitter: wavelog.jitter.unwrap_or(0.0),
+            min_upload_interval_ms: wavelog.min_upload_interval_ms.unwrap_or(wavelog.interval),
+            keepalive_secs: wavelog.keepalive_secs.unwrap_or(0),
+            mode_map,
+            ft8_freqs: cat::ft8_freqs(cat.ft8_frequencies.as_deref()),
+            ft8_automode: cat.ft8_automode.unwrap_or(true),
+            cw_pitch_hz: flrig.cw_pitch_hz.unwrap_or(0),
+        }
+    }
 }
 
 impl Settings {
@@ -39,17 +132,473 @@ impl Settings {
         Ok(base_dir.join(app_name))
     }
 
-    pub fn new() -> Result<Self, ConfigError> {
-        let config_file = Self::config_dir()?.join("config.toml");
+    /// Path of the config file: `config.toml` in `config_dir()`.
+    pub fn config_file() -> Result<PathBuf, ConfigError> {
+        Ok(Self::config_dir()?.join("config.toml"))
+    }
+
+    /// The config file to load: `config_file()` if it exists, otherwise a
+    /// `config.toml` next to the executable (for portable installs, e.g. on a
+    /// USB stick).  When neither exists this is `config_file()`, so the
+    /// "missing" error points at the usual place.
+    pub fn discover_config_file() -> Result<PathBuf, ConfigError> {
+        let exe_dir = env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf));
+        Ok(resolve_config_file(Self::config_file()?, exe_dir))
+    }
+
+    pub fn new() -> Result<Self, SettingsError> {
+        Self::load(&Self::discover_config_file()?, Self::environment())
+    }
+
+    /// The hot-reloadable part of these settings.
+    pub fn live(&self) -> LiveSettings {
+        LiveSettings::new(&self.wavelog, &self.flrig, &self.cat)
+    }
+
+    /// Re-read the config file (on SIGHUP) and swap its hot-reloadable part
+    /// into `live`.  The full new settings are returned so the caller can
+    /// apply the rest of the reloadable subset and report what needs a
+    /// restart.  On error `live` is left as it was.
+    pub fn reload(live: &RwLock<LiveSettings>) -> Result<Self, SettingsError> {
+        Self::reload_from(&Self::discover_config_file()?, Self::environment(), live)
+    }
+
+    fn reload_from(
+        config_file: &Path,
+        env: Environment,
+        live: &RwLock<LiveSettings>,
+    ) -> Result<Self, SettingsError> {
+        let settings = Self::load(config_file, env)?;
+        *live.write().unwrap() = settings.live();
+        Ok(settings)
+    }
+
+    /// Addresses of the network listeners and the FLRig server.  These only
+    /// take effect at startup, so a reload that changes one of them is
+    /// reported as needing a restart.
+    pub fn listener_addrs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "[flrig] host/port",
+                format!("{}:{}", self.flrig.host, self.flrig.port),
+            ),
+            (
+                "[flrig] backup_host/backup_port",
+                match &self.flrig.backup_host {
+                    Some(host) => {
+                        let port = self.flrig.backup_port.unwrap_or(self.flrig.port);
+                        format!("{host}:{port}")
+                    }
+                    None => "none".to_string(),
+                },
+            ),
+            ("[CAT] host/port", self.cat.bind_addr().to_string()),
+            (
+                "[WSJTX] host/port",
+                format!("{}:{}", self.wsjtx.host, self.wsjtx.port),
+            ),
+            (
+                "[websocket] host/port",
+                self.websocket.bind_addr().to_string(),
+            ),
+        ]
+    }
+
+    /// Write the commented example config to `path` for a first run,
+    /// creating its directory.  Never overwrites an existing file.
+    pub fn write_template(path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .and_then(|mut f| std::io::Write::write_all(&mut f, CONFIG_TEMPLATE.as_bytes()))
+    }
 
+    /// Environment variables that override config file values.
+    ///
+    /// `WLRIGCTL_<SECTION>__<KEY>` overrides `key` in `[section]`, e.g.
+    /// `WLRIGCTL_WAVELOG__KEY` replaces `[wavelog] key` and
+    /// `WLRIGCTL_CAT__PORT` replaces `[CAT] port`.  Only scalar values can be
+    /// set this way; lists must stay in the file.
+    fn environment() -> Environment {
+        Environment::with_prefix("WLRIGCTL")
+            .prefix_separator("_")
+            .separator("__")
+    }
+
+    fn load(config_file: &Path, env: Environment) -> Result<Self, SettingsError> {
+        if !config_file.exists() {
+            return Err(SettingsError::Missing(config_file.to_path_buf()));
+        }
         let config_path = config_file
             .to_str()
             .ok_or_else(|| ConfigError::Message("Config path not valid UTF-8".into()))?;
 
-        let settings = Config::builder()
-            .add_source(File::with_name(config_path))
-            .build()?;
+        let mut builder = Config::builder().add_source(File::with_name(config_path));
+
+        // The config crate lowercases environment variable names, but the
+        // [CAT] and [WSJTX] sections are uppercase (see CLAUDE.md), so map
+        // those section names back before applying the overrides.
+        for (key, value) in env.collect()? {
+            let key = match key.split_once('.') {
+                Some((section, rest)) if UPPERCASE_SECTIONS.contains(&section) => {
+                    format!("{}.{rest}", section.to_uppercase())
+                }
+                _ => key,
+            };
+            builder = builder.set_override(key, value)?;
+        }
+
+        let mut settings: Settings = builder.build()?.try_deserialize()?;
+        settings
+            .wavelog
+            .resolve_endpoints()
+            .map_err(ConfigError::Message)?;
+        settings.flrig.validate().map_err(ConfigError::Message)?;
+        settings.wsjtx.validate().map_err(ConfigError::Message)?;
+        Ok(settings)
+    }
+
+    /// The effective configuration as JSON, safe to paste into a bug report.
+    /// Every value not listed in `PUBLIC_FIELDS` is replaced by "***", so a
+    /// newly added secret is hidden until someone decides it is safe to show.
+    pub fn redacted_json(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or(Value::Null);
+        redact(&mut value, "");
+        value
+    }
+}
+
+// Dotted paths of settings that are safe to show in /config.  Anything not
+// listed here -- in particular [wavelog] key -- is redacted.
+const PUBLIC_FIELDS: &[&str] = &[
+    "wavelog.url",
+    "wavelog.qso_url",
+    "wavelog.identifier",
+    "wavelog.station_profile_id",
+    "wavelog.interval",
+    "wavelog.cat_url",
+    "wavelog.my_grid",
+    "wavelog.jitter",
+    "wavelog.min_upload_interval_ms",
+    "wavelog.keepalive_secs",
+    "wavelog.compress",
+    "wavelog.adif_inject",
+    "wavelog.mode_remap",
+    "wavelog.local_adif",
+    "wavelog.qso_spool",
+    "wavelog.radio_method",
+    "wavelog.qso_method",
+    "wavelog.base_url",
+    "flrig.host",
+    "flrig.port",
+    "flrig.maxpower",
+    "flrig.cw_bw_index",
+    "flrig.mode_bw_index",
+    "flrig.narrow_delay_ms",
+    "flrig.power_on",
+    "flrig.power_off",
+    "flrig.poll_interval_ms",
+    "flrig.report_dsp",
+    "flrig.report_passband",
+    "flrig.report_smeter",
+    "flrig.report_ptt",
+    "flrig.smeter_calibration",
+    "flrig.use_data_mode",
+    "flrig.mode_power_limits",
+    "flrig.enforce_mode_power",
+    "flrig.cw_pitch_hz",
+    "flrig.use_get_info",
+    "flrig.backup_host",
+    "flrig.backup_port",
+    "flrig.failover_after",
+    "flrig.verify_set",
+    "flrig.log_vfob",
+    "flrig.restore_on_start",
+    "flrig.state_file",
+    "flrig.power_is_watts",
+    "flrig.band_mode_file",
+    "CAT.host",
+    "CAT.port",
+    "CAT.cw_mode",
+    "CAT.rtty_mode",
+    "CAT.digital_mode",
+    "CAT.default_digi_mode",
+    "CAT.ft8_frequencies",
+    "CAT.ft8_automode",
+    "CAT.wavelog_origin",
+    "CAT.band_homes",
+    "CAT.band_wrap",
+    "CAT.respond_early",
+    "CAT.mode_aliases",
+    "CAT.cache_ms",
+    "CAT.health_failures",
+    "CAT.wsjtx_health_secs",
+    "CAT.tls_cert",
+    "CAT.tls_key",
+    "CAT.connection_timeout_secs",
+    "CAT.max_connections",
+    "CAT.max_body_bytes",
+    "CAT.body_timeout_ms",
+    "CAT.freq_step_hz",
+    "CAT.power_step",
+    "CAT.allow",
+    "WSJTX.host",
+    "WSJTX.port",
+    "WSJTX.err_timeout",
+    "WSJTX.forward_to",
+    "WSJTX.rxbuf_bytes",
+    "WSJTX.max_tx_secs",
+    "WSJTX.dual_stack",
+    "WSJTX.magic",
+    "WSJTX.schema",
+    "websocket.host",
+    "websocket.port",
+    "websocket.tls_cert",
+    "websocket.tls_key",
+    "logging.target",
+    "logging.repeat_window_secs",
+    "mqtt.broker",
+    "mqtt.port",
+    "mqtt.topic",
+    "mqtt.client_id",
+    "mqtt.username",
+    "mqtt.retain",
+];
+
+// Replace every non-public, non-null leaf under `value` with "***".  Unset
+// options stay null: they reveal nothing and help show what is configured.
+fn redact(value: &mut Value, path: &str) {
+    if PUBLIC_FIELDS.contains(&path) || value.is_null() {
+        return;
+    }
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                let child_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                redact(child, &child_path);
+            }
+        }
+        _ => *value = Value::String("***".to_string()),
+    }
+}
+
+// First existing file of: the XDG/home config file, then `config.toml` in
+// the executable's directory.  Falls back to the primary path.
+fn resolve_config_file(primary: PathBuf, exe_dir: Option<PathBuf>) -> PathBuf {
+    if primary.exists() {
+        return primary;
+    }
+    match exe_dir.map(|dir| dir.join("config.toml")) {
+        Some(portable) if portable.exists() => portable,
+        _ => primary,
+    }
+}
+
+// Lowercased names of the config sections whose TOML names are uppercase.
+const UPPERCASE_SECTIONS: &[&str] = &["cat", "wsjtx"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::LogTarget;
+    use std::collections::HashMap;
+
+    const MINIMAL_CONFIG: &str = r#"
+[wavelog]
+url = "http://localhost/index.php/api/radio"
+qso_url = "http://localhost/index.php/api/qso"
+key = "from-file"
+identifier = "IC-703"
+station_profile_id = 1
+interval = 200
+
+[flrig]
+host = "http://127.0.0.1"
+port = 12345
+maxpower = 10
+
+[CAT]
+port = 54321
+
+[WSJTX]
+host = "127.0.0.1"
+port = 2237
+err_timeout = 3
+"#;
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        let dir = env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    // An Environment fed from a fixed map, so tests never read or mutate the
+    // real process environment.
+    fn fake_env(vars: &[(&str, &str)]) -> Environment {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::environment().source(Some(map))
+    }
+
+    #[test]
+    fn file_values_used_without_env() {
+        let path = write_config("wlrigctl-settings-file", MINIMAL_CONFIG);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        assert_eq!(settings.wavelog.key, "from-file");
+        assert_eq!(settings.cat.port, 54321);
+    }
+
+    #[test]
+    fn missing_file_is_reported_specifically() {
+        let dir = env::temp_dir().join("wlrigctl-settings-missing");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        match Settings::load(&path, fake_env(&[])) {
+            Err(SettingsError::Missing(p)) => assert_eq!(p, path),
+            other => panic!("expected Missing, got {other:?}"),
+        }
+        let msg = SettingsError::Missing(path.clone()).to_string();
+        assert!(msg.contains(&path.display().to_string()));
+        assert!(msg.contains("--init"));
+    }
+
+    #[test]
+    fn invalid_file_is_not_reported_as_missing() {
+        let path = write_config("wlrigctl-settings-invalid", "[wavelog]\nurl = 1\n");
+        assert!(matches!(
+            Settings::load(&path, fake_env(&[])),
+            Err(SettingsError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn write_template_creates_loadable_file_once() {
+        let dir = env::temp_dir().join("wlrigctl-settings-init");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("config.toml");
+        Settings::write_template(&path).unwrap();
+        assert!(Settings::load(&path, fake_env(&[])).is_ok());
+        // A second --init must not clobber the user's edits.
+        assert!(Settings::write_template(&path).is_err());
+    }
+
+    #[test]
+    fn config_discovery_prefers_home_then_exe_dir() {
+        let home = write_config("wlrigctl-settings-disc-home", MINIMAL_CONFIG);
+        let exe = write_config("wlrigctl-settings-disc-exe", MINIMAL_CONFIG);
+        let exe_dir = exe.parent().map(Path::to_path_buf);
+        assert_eq!(resolve_config_file(home.clone(), exe_dir.clone()), home);
+
+        std::fs::remove_file(&home).unwrap();
+        assert_eq!(resolve_config_file(home.clone(), exe_dir.clone()), exe);
+
+        // Neither present: report the primary location, not the fallback.
+        std::fs::remove_file(&exe).unwrap();
+        assert_eq!(resolve_config_file(home.clone(), exe_dir), home);
+        assert_eq!(resolve_config_file(home.clone(), None), home);
+    }
+
+    #[test]
+    fn reload_updates_poll_interval() {
+        let path = write_config("wlrigctl-settings-reload", MINIMAL_CONFIG);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        let live = RwLock::new(settings.live());
+        assert_eq!(live.read().unwrap().poll_interval_ms, 200);
+
+        let edited = MINIMAL_CONFIG.replace("interval = 200", "interval = 500");
+        std::fs::write(&path, edited).unwrap();
+        let reloaded = Settings::reload_from(&path, fake_env(&[]), &live).unwrap();
+        assert_eq!(live.read().unwrap().poll_interval_ms, 500);
+        assert_eq!(live.read().unwrap().min_upload_interval_ms, 500);
+        assert_eq!(reloaded.listener_addrs(), settings.listener_addrs());
+    }
+
+    #[test]
+    fn failed_reload_keeps_live_settings() {
+        let path = write_config("wlrigctl-settings-reload-bad", MINIMAL_CONFIG);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        let live = RwLock::new(settings.live());
+
+        std::fs::write(&path, "[wavelog]\nurl = 1\n").unwrap();
+        assert!(Settings::reload_from(&path, fake_env(&[]), &live).is_err());
+        assert_eq!(live.read().unwrap().poll_interval_ms, 200);
+    }
+
+    #[test]
+    fn env_overrides_nested_field() {
+        let path = write_config("wlrigctl-settings-env", MINIMAL_CONFIG);
+        let settings =
+            Settings::load(&path, fake_env(&[("WLRIGCTL_WAVELOG__KEY", "from-env")])).unwrap();
+        assert_eq!(settings.wavelog.key, "from-env");
+        // Untouched values still come from the file.
+        assert_eq!(settings.wavelog.identifier, "IC-703");
+    }
+
+    #[test]
+    fn env_overrides_uppercase_sections() {
+        let path = write_config("wlrigctl-settings-env-upper", MINIMAL_CONFIG);
+        let settings = Settings::load(
+            &path,
+            fake_env(&[
+                ("WLRIGCTL_CAT__PORT", "60000"),
+                ("WLRIGCTL_WSJTX__PORT", "2238"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(settings.cat.port, 60000);
+        assert_eq!(settings.wsjtx.port, 2238);
+    }
+
+    #[test]
+    fn redacted_json_hides_key() {
+        let path = write_config("wlrigctl-settings-redact", MINIMAL_CONFIG);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        let json = settings.redacted_json();
+        assert_eq!(json["wavelog"]["key"], "***");
+        assert_eq!(json["wavelog"]["identifier"], "IC-703");
+        assert_eq!(json["CAT"]["port"], 54321);
+        assert!(json["CAT"]["wavelog_origin"].is_null());
+    }
+
+    #[test]
+    fn redact_hides_fields_not_on_allow_list() {
+        let mut value = serde_json::json!({
+            "wavelog": { "url": "http://x", "new_token": "secret" },
+            "mqtt": { "password": "hunter2" },
+        });
+        redact(&mut value, "");
+        assert_eq!(value["wavelog"]["url"], "http://x");
+        assert_eq!(value["wavelog"]["new_token"], "***");
+        assert_eq!(value["mqtt"]["password"], "***");
+    }
+
+    #[test]
+    fn logging_defaults_to_stderr() {
+        let path = write_config("wlrigctl-settings-log-default", MINIMAL_CONFIG);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        assert_eq!(settings.logging.target, LogTarget::Stderr);
+    }
 
-        settings.try_deserialize()
+    #[test]
+    fn logging_target_syslog() {
+        let config = format!("{MINIMAL_CONFIG}\n[logging]\ntarget = \"syslog\"\n");
+        let path = write_config("wlrigctl-settings-log-syslog", &config);
+        let settings = Settings::load(&path, fake_env(&[])).unwrap();
+        assert_eq!(settings.logging.target, LogTarget::Syslog);
     }
 }
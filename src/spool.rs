@@ -0,0 +1,310 @@
+use crate::latency::SharedLatency;
+use crate::wavelog::{upload_wsjtx_qso_data, WavelogSettings};
+use log::{error, info, warn};
+use reqwest::Client;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// How often QSOs waiting in the spool are offered to Wavelog again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+// The final flush on shutdown must finish inside main's SHUTDOWN_GRACE, so
+// that it can still report what it left behind.
+const SHUTDOWN_FLUSH: Duration = Duration::from_secs(4);
+
+/// QSOs that could not be uploaded to Wavelog because it was unreachable or
+/// failing, kept in a file (`[wavelog] qso_spool`) until an upload succeeds,
+/// so neither an outage nor a restart loses them.  One JSON string of ADIF
+/// per line.
+pub struct QsoSpool {
+    path: PathBuf,
+    // Held for every read or rewrite of the file.
+    file: tokio::sync::Mutex<()>,
+    // Held for a whole flush, so two cannot upload the same QSO.
+    flushing: tokio::sync::Mutex<()>,
+}
+
+/// What one pass over the spool achieved.
+#[derive(Debug, Default, PartialEq)]
+pub struct FlushReport {
+    pub sent: usize,
+    /// Rejected by Wavelog, so dropped rather than retried forever.
+    pub rejected: usize,
+    pub left: usize,
+}
+
+impl QsoSpool {
+    pub fn new(path: impl Into<PathBuf>) -> QsoSpool {
+        QsoSpool {
+            path: path.into(),
+            file: tokio::sync::Mutex::new(()),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Add a QSO to the end of the spool.
+    pub async fn push(&self, adif_text: &str) -> io::Result<()> {
+        let line = serde_json::to_string(adif_text)?;
+        let _file = self.file.lock().await;
+        let path = self.path.clone();
+        blocking(move || {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{line}")
+        })
+        .await
+    }
+
+    /// The QSOs waiting to be uploaded, oldest first.
+    pub async fn pending(&self) -> io::Result<Vec<String>> {
+        let _file = self.file.lock().await;
+        let path = self.path.clone();
+        blocking(move || read_entries(&path)).await
+    }
+
+    /// Offer each waiting QSO to Wavelog in turn, oldest first, until one
+    /// fails in a way worth retrying or `deadline` passes.  Sent and
+    /// rejected QSOs are removed; QSOs pushed meanwhile are kept.
+    pub async fn flush(
+        &self,
+        client: &Client,
+        settings: &WavelogSettings,
+        latency: &SharedLatency,
+        deadline: Instant,
+    ) -> io::Result<FlushReport> {
+        let _flushing = self.flushing.lock().await;
+        let entries = self.pending().await?;
+        let mut report = FlushReport::default();
+        let mut done = 0;
+        for adif_text in &entries {
+            let upload = upload_wsjtx_qso_data(client, settings, adif_text.clone(), latency);
+            match tokio::time::timeout_at(deadline, upload).await {
+                Ok(Ok(_)) => report.sent += 1,
+                Ok(Err(e)) if !e.is_retryable() => {
+                    error!("Wavelog rejected a spooled QSO, dropping it: {e}\n{adif_text}");
+                    report.rejected += 1;
+                }
+                Ok(Err(e)) => {
+                    warn!("Spooled QSO upload failed, will retry: {e}");
+                    break;
+                }
+                Err(_) => break,
+            }
+            done += 1;
+        }
+        report.left = entries.len() - done;
+        if done > 0 {
+            self.remove_first(done).await?;
+        }
+        Ok(report)
+    }
+
+    // Drop the first `count` entries, keeping any pushed since they were read.
+    async fn remove_first(&self, count: usize) -> io::Result<()> {
+        let _file = self.file.lock().await;
+        let path = self.path.clone();
+        blocking(move || {
+            let entries = read_entries(&path)?;
+            let kept = entries.get(count..).unwrap_or_default();
+            let tmp = path.with_extension("tmp");
+            let mut file = fs::File::create(&tmp)?;
+            for entry in kept {
+                writeln!(file, "{}", serde_json::to_string(entry)?)?;
+            }
+            file.sync_all()?;
+            fs::rename(tmp, path)
+        })
+        .await
+    }
+}
+
+// Run file I/O off the async worker threads.
+async fn blocking<T, F>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+}
+
+// Every entry in the spool file; a missing file is an empty spool.  A line
+// that does not parse (a write cut off by a crash) is skipped.
+fn read_entries(path: &Path) -> io::Result<Vec<String>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Retry the spool every minute, and once more, for at most a few seconds,
+/// when `token` is cancelled, so a clean stop does not strand QSOs Wavelog
+/// could have taken.
+pub fn spool_thread(
+    spool: Arc<QsoSpool>,
+    settings: WavelogSettings,
+    latency: SharedLatency,
+    token: CancellationToken,
+) -> JoinHandle<()> {
+    spool_thread_with_interval(spool, settings, latency, token, RETRY_INTERVAL)
+}
+
+fn spool_thread_with_interval(
+    spool: Arc<QsoSpool>,
+    settings: WavelogSettings,
+    latency: SharedLatency,
+    token: CancellationToken,
+    interval: Duration,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = Client::new();
+        loop {
+            tokio::select! {
+                _ = token.cancelled() => break,
+                _ = tokio::time::sleep(interval) => {}
+            }
+            let deadline = Instant::now() + interval;
+            match spool.flush(&client, &settings, &latency, deadline).await {
+                Ok(report) if report.sent > 0 || report.rejected > 0 => info!(
+                    "QSO spool: {} sent, {} rejected, {} left",
+                    report.sent, report.rejected, report.left
+                ),
+                Ok(_) => {}
+                Err(e) => error!("Could not read the QSO spool: {e}"),
+            }
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_FLUSH;
+        match spool.flush(&client, &settings, &latency, deadline).await {
+            Ok(FlushReport {
+                left: 0, sent: 0, ..
+            }) => {}
+            Ok(report) => info!(
+                "Final QSO spool flush: {} sent, {} left for the next start",
+                report.sent, report.left
+            ),
+            Err(e) => error!("Could not flush the QSO spool: {e}"),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_wavelog::mock_wavelog;
+    use reqwest::StatusCode;
+
+    const QSO: &str = "<call:5>G4ABC <mode:3>FT8 <eor>";
+
+    fn spool_at(name: &str) -> QsoSpool {
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        QsoSpool::new(path)
+    }
+
+    fn settings(qso_url: String) -> WavelogSettings {
+        serde_json::from_value(serde_json::json!({
+            "url": "http://127.0.0.1:19999/index.php/api/radio",
+            "qso_url": qso_url,
+            "key": "wl123",
+            "identifier": "rig",
+            "station_profile_id": 1,
+            "interval": 1000,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn pending_qso_is_sent_during_shutdown() {
+        let spool = Arc::new(spool_at("wlrigctl-spool-shutdown.jsonl"));
+        spool.push(QSO).await.unwrap();
+        let (tx, mut uploads) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_wavelog(StatusCode::OK, tx).await;
+
+        // Too long an interval for a regular retry to happen first.
+        let token = CancellationToken::new();
+        let task = spool_thread_with_interval(
+            spool.clone(),
+            settings(url),
+            SharedLatency::default(),
+            token.clone(),
+            Duration::from_secs(3600),
+        );
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("final flush did not finish")
+            .unwrap();
+
+        let body: serde_json::Value = serde_json::from_str(&uploads.recv().await.unwrap()).unwrap();
+        assert!(body["string"].as_str().unwrap().contains("G4ABC"));
+        assert!(spool.pending().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn qsos_stay_spooled_while_wavelog_fails() {
+        let spool = spool_at("wlrigctl-spool-failing.jsonl");
+        spool.push(QSO).await.unwrap();
+        spool.push("<call:5>M0XYZ <eor>").await.unwrap();
+        let (tx, _uploads) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_wavelog(StatusCode::SERVICE_UNAVAILABLE, tx).await;
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let report = spool
+            .flush(
+                &Client::new(),
+                &settings(url),
+                &SharedLatency::default(),
+                deadline,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            FlushReport {
+                sent: 0,
+                rejected: 0,
+                left: 2
+            }
+        );
+        assert_eq!(spool.pending().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rejected_qsos_are_dropped() {
+        let spool = spool_at("wlrigctl-spool-rejected.jsonl");
+        spool.push(QSO).await.unwrap();
+        let (tx, _uploads) = tokio::sync::mpsc::unbounded_channel();
+        let url = mock_wavelog(StatusCode::BAD_REQUEST, tx).await;
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let report = spool
+            .flush(
+                &Client::new(),
+                &settings(url),
+                &SharedLatency::default(),
+                deadline,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.rejected, 1);
+        assert!(spool.pending().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn cut_off_line_is_skipped() {
+        let path = std::env::temp_dir().join("wlrigctl-spool-cut-off.jsonl");
+        fs::write(&path, "\"<call:5>G4ABC <eor>\"\n\"<call:5>M0X").unwrap();
+        assert_eq!(read_entries(&path).unwrap(), vec!["<call:5>G4ABC <eor>"]);
+    }
+}
@@ -0,0 +1,114 @@
+use log::warn;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::time::Duration;
+
+// How long a state file waits after a change before it is written, so a
+// VFO knob being turned costs one write rather than one per poll.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Keeps a small JSON state file up to date without blocking the caller:
+/// [`StateFileWriter::set`] only records the newest value, and a writer task
+/// writes it once changes have settled for a moment.  Writes go through a
+/// temporary file and a rename, so a power cut mid-write never leaves a
+/// truncated file behind.
+pub struct StateFileWriter<T> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    path: PathBuf,
+    // The newest value not yet written.
+    pending: Mutex<Option<T>>,
+    changed: Notify,
+    // Held while writing, so writes land in the order values were set.
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl<T: Serialize + Send + 'static> StateFileWriter<T> {
+    /// Start the writer task for the file at `path`.  Must be called inside
+    /// the Tokio runtime.
+    pub fn spawn(path: PathBuf) -> StateFileWriter<T> {
+        StateFileWriter::with_debounce(path, DEBOUNCE)
+    }
+
+    fn with_debounce(path: PathBuf, debounce: Duration) -> StateFileWriter<T> {
+        let shared = Arc::new(Shared {
+            path,
+            pending: Mutex::new(None),
+            changed: Notify::new(),
+            writing: tokio::sync::Mutex::new(()),
+        });
+        let task = shared.clone();
+        tokio::spawn(async move {
+            loop {
+                task.changed.notified().await;
+                tokio::time::sleep(debounce).await;
+                write_pending(&task).await;
+            }
+        });
+        StateFileWriter { shared }
+    }
+
+    /// Have `value` written shortly, replacing any value still waiting.
+    pub fn set(&self, value: T) {
+        *self.shared.pending.lock().unwrap() = Some(value);
+        self.shared.changed.notify_one();
+    }
+
+    /// Write any value still waiting now, e.g. on shutdown.
+    pub async fn flush(&self) {
+        write_pending(&self.shared).await;
+    }
+}
+
+async fn write_pending<T: Serialize>(shared: &Shared<T>) {
+    let _writing = shared.writing.lock().await;
+    let Some(value) = shared.pending.lock().unwrap().take() else {
+        return;
+    };
+    let text = match serde_json::to_string_pretty(&value) {
+        Ok(text) => text,
+        Err(e) => return warn!("Could not encode {}: {e}", shared.path.display()),
+    };
+    let path = shared.path.clone();
+    let written = tokio::task::spawn_blocking(move || {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text)?;
+        std::fs::rename(&tmp, &path)
+    })
+    .await
+    .map_err(std::io::Error::other)
+    .and_then(|result| result);
+    if let Err(e) = written {
+        warn!("Could not write {}: {e}", shared.path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_the_settled_value_is_written() {
+        let dir = std::env::temp_dir().join("wlrigctl-statefile-debounce");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        let debounce = Duration::from_millis(200);
+        let writer = StateFileWriter::with_debounce(path.clone(), debounce);
+        writer.set(1);
+        writer.set(2);
+        assert!(!path.exists());
+
+        tokio::time::sleep(debounce * 3).await;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "2");
+
+        writer.set(3);
+        writer.flush().await;
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3");
+    }
+}
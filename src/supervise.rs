@@ -0,0 +1,154 @@
+use log::{error, warn};
+use std::any::Any;
+use std::future::Future;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+// Pause before restarting a task that panicked, so one that panics straight
+// away does not spin.
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+/// Run the task that `start` builds, building and running it again whenever
+/// it panics, until it returns normally or `token` is cancelled.  Without
+/// this a panic would silently end the task while the process carried on
+/// looking healthy.  Each restart begins with whatever state `start` gives
+/// it, so anything that must survive a panic belongs outside the task.
+pub fn supervise<F, Fut>(name: &'static str, token: CancellationToken, start: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    supervise_with_delay(name, token, RESTART_DELAY, start)
+}
+
+fn supervise_with_delay<F, Fut>(
+    name: &'static str,
+    token: CancellationToken,
+    delay: Duration,
+    mut start: F,
+) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            match tokio::spawn(start()).await {
+                Err(e) if e.is_panic() => error!(
+                    "{name} panicked ({}); restarting in {}s",
+                    panic_message(e.into_panic()),
+                    delay.as_secs_f32()
+                ),
+                _ => return,
+            }
+            tokio::select! {
+                _ = token.cancelled() => return,
+                _ = tokio::time::sleep(delay) => {}
+            }
+        }
+    })
+}
+
+/// Wait, after shutdown has been signalled, for each of `tasks` to see it and
+/// finish, so work in flight (a Wavelog upload, a QSO being logged) is not
+/// cut off.  Any still running when `grace` is up is aborted; their names are
+/// returned.
+pub async fn join_with_timeout(
+    tasks: Vec<(&'static str, JoinHandle<()>)>,
+    grace: Duration,
+) -> Vec<&'static str> {
+    let deadline = tokio::time::Instant::now() + grace;
+    let mut aborted = Vec::new();
+    for (name, mut task) in tasks {
+        if tokio::time::timeout_at(deadline, &mut task).await.is_err() {
+            warn!(
+                "{name} did not stop within {}s; aborting it",
+                grace.as_secs_f32()
+            );
+            task.abort();
+            aborted.push(name);
+        }
+    }
+    aborted
+}
+
+// The text given to panic!(), which is a &str or a String in practice.
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map_or_else(|| "no message".to_string(), |msg| msg.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn panicking_task_is_restarted() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_task = runs.clone();
+        let supervisor = supervise_with_delay(
+            "test task",
+            CancellationToken::new(),
+            Duration::from_millis(10),
+            move || {
+                let runs = runs_task.clone();
+                async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first run fails");
+                    }
+                }
+            },
+        );
+
+        tokio::time::timeout(Duration::from_secs(5), supervisor)
+            .await
+            .expect("supervisor did not finish")
+            .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn cancelled_supervisor_does_not_restart() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_task = runs.clone();
+        let supervisor = supervise_with_delay("test task", token, Duration::ZERO, move || {
+            runs_task.fetch_add(1, Ordering::SeqCst);
+            async { panic!("always fails") }
+        });
+
+        supervisor.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn tasks_that_see_shutdown_are_joined_and_the_rest_aborted() {
+        let token = CancellationToken::new();
+        let task_token = token.clone();
+        let polite = tokio::spawn(async move { task_token.cancelled().await });
+        let stubborn = tokio::spawn(std::future::pending::<()>());
+
+        token.cancel();
+        let aborted = join_with_timeout(
+            vec![("polite", polite), ("stubborn", stubborn)],
+            Duration::from_millis(100),
+        )
+        .await;
+        assert_eq!(aborted, vec!["stubborn"]);
+    }
+
+    #[test]
+    fn panic_message_from_str_and_string() {
+        assert_eq!(panic_message(Box::new("boom")), "boom");
+        assert_eq!(panic_message(Box::new("bang".to_string())), "bang");
+        assert_eq!(panic_message(Box::new(42)), "no message");
+    }
+}
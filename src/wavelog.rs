@@ -1,17 +1,35 @@
+use crate::adif;
 use crate::flrig;
-use log::{debug, info};
-use reqwest::{Client, Error};
+use crate::latency::{LatencyStats, SharedLatency};
+use crate::logging::RepeatedErrors;
+use crate::settings::SharedLiveSettings;
+use crate::supervise::supervise;
+use dxr_client::ClientError;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use std::sync::Arc;
-use tokio::sync::watch;
-use tokio::time::Duration;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
 // settings from .toml file
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct WavelogSettings {
+    /// Live-radio API URL.  May be left out when `base_url` is set, which it
+    /// is then built from; when given it overrides that.
+    #[serde(default)]
     pub url: String,
+    /// QSO API URL.  As `url`, may be left out when `base_url` is set.
+    #[serde(default)]
     pub qso_url: String,
     pub key: String,
     pub identifier: String,
@@ -21,6 +39,95 @@ pub struct WavelogSettings {
     /// live-radio POST so Wavelog can auto-register the CAT callback and show a
     /// "QSY" button in the bandmap without any manual configuration.
     pub cat_url: Option<String>,
+    /// This station's Maidenhead locator, e.g. "IO91wm", sent with every
+    /// live-radio upload for logbooks that show it.  Left out when absent.
+    pub my_grid: Option<String>,
+    /// Random extra delay added to each poll, as a fraction of the poll
+    /// interval (e.g. 0.1 for up to 10%).  Stops several FLRig clients
+    /// polling in lockstep.  The delay is never shorter than the interval.
+    pub jitter: Option<f64>,
+    /// Minimum time between live-radio uploads to Wavelog, in milliseconds.
+    /// Changes seen in between are coalesced into the next upload.  Falls
+    /// back to `interval` when absent.
+    pub min_upload_interval_ms: Option<u64>,
+    /// Re-send the current rig state after this many seconds without an
+    /// upload, so Wavelog keeps showing the radio as online while it sits on
+    /// one frequency.  0 (the default) only uploads on a change.
+    pub keepalive_secs: Option<u64>,
+    /// Gzip QSO upload bodies (`Content-Encoding: gzip`).  Only enable this
+    /// if the web server in front of Wavelog decompresses request bodies.
+    pub compress: Option<bool>,
+    /// ADIF fields added to every QSO before it is uploaded, e.g.
+    /// `{ MY_GRIDSQUARE = "IO91wm" }`.  A field the record already has is
+    /// left as it is.
+    pub adif_inject: Option<HashMap<String, String>>,
+    /// Replacements for the ADIF `MODE` and `SUBMODE` values of uploaded
+    /// QSOs, for modes Wavelog does not accept as a logging program spells
+    /// them, e.g. `{ "FST4W" = "MFSK" }`.  Matched case-insensitively.
+    /// Absent passes every mode through.
+    pub mode_remap: Option<HashMap<String, String>>,
+    /// Path of an ADIF file every WSJT-X QSO is also appended to, whether or
+    /// not the Wavelog upload succeeds.
+    pub local_adif: Option<String>,
+    /// Path of a file holding WSJT-X QSOs whose upload failed because
+    /// Wavelog was unreachable or erroring, retried every minute and once
+    /// more at shutdown.  Without it such QSOs are only logged as failed.
+    pub qso_spool: Option<String>,
+    /// HTTP method for live-radio uploads to `url`.  Defaults to POST.
+    pub radio_method: Option<HttpMethod>,
+    /// HTTP method for QSO uploads to `qso_url`.  Defaults to POST.
+    pub qso_method: Option<HttpMethod>,
+    /// Address of the Wavelog install, e.g. "https://log.example.org", from
+    /// which `url` and `qso_url` are built when they are left out.
+    pub base_url: Option<String>,
+}
+
+impl WavelogSettings {
+    /// Fill in `url` and `qso_url` from `base_url` where the config left
+    /// them out.  An explicit URL is kept as it is.
+    pub fn resolve_endpoints(&mut self) -> Result<(), String> {
+        if !self.url.is_empty() && !self.qso_url.is_empty() {
+            return Ok(());
+        }
+        let Some(base) = self.base_url.as_deref() else {
+            return Err("[wavelog] needs url and qso_url, or base_url".to_string());
+        };
+        if self.url.is_empty() {
+            self.url = endpoint(base, "radio");
+        }
+        if self.qso_url.is_empty() {
+            self.qso_url = endpoint(base, "qso");
+        }
+        Ok(())
+    }
+}
+
+// Wavelog serves its API from index.php, which works with or without URL
+// rewriting on the web server.
+fn endpoint(base: &str, name: &str) -> String {
+    format!("{}/index.php/api/{name}", base.trim_end_matches('/'))
+}
+
+/// HTTP method for a Wavelog endpoint, for proxies and API variants that do
+/// not take POST.  Written in upper case in the config; anything else fails
+/// to load at startup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    #[default]
+    Post,
+    Put,
+    Patch,
+}
+
+impl From<HttpMethod> for reqwest::Method {
+    fn from(method: HttpMethod) -> Self {
+        match method {
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Patch => reqwest::Method::PATCH,
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -29,28 +136,291 @@ pub struct RadioData {
     pub radio: String,
     pub frequency: String,
     pub mode: String,
+    /// The rig's own name for the mode, as FLRig reported it (e.g. "D-USB"),
+    /// where `mode` is Wavelog's.  Not sent to Wavelog.
+    #[serde(skip)]
+    pub rig_mode: String,
     pub power: String,
     /// Omitted from JSON when absent so existing Wavelog installs that don't
     /// know about the field are not confused.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cat_url: Option<String>,
+    /// `[wavelog] my_grid`, omitted from JSON when absent like `cat_url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub my_grid: Option<String>,
+}
+
+/// The rig state most recently read by the poll loop or the CAT server, and
+/// when it was read, so CAT read routes can answer without asking FLRig.
+///
+/// Each invalidation starts a new generation.  A read is stored only if no
+/// invalidation happened while it was in flight, so a poll that began before
+/// a QSY cannot put the old frequency back once the QSY has cleared it.
+#[derive(Clone, Default)]
+pub struct RadioCache(Arc<Mutex<CacheSlot>>);
+
+#[derive(Default)]
+struct CacheSlot {
+    generation: u64,
+    entry: Option<(Instant, RadioData)>,
+}
+
+impl RadioCache {
+    /// Take before starting a read, to pass to [`RadioCache::store`].
+    pub fn generation(&self) -> u64 {
+        self.0.lock().unwrap().generation
+    }
+
+    /// Cache `data`, read just now, unless the cache has been invalidated
+    /// since `generation` was taken.
+    pub fn store(&self, generation: u64, data: &RadioData) {
+        let mut slot = self.0.lock().unwrap();
+        if slot.generation == generation {
+            slot.entry = Some((Instant::now(), data.clone()));
+        }
+    }
+
+    /// Forget the cached state, and any read still in flight, after the rig
+    /// has been told to change.
+    pub fn invalidate(&self) {
+        let mut slot = self.0.lock().unwrap();
+        slot.generation += 1;
+        slot.entry = None;
+    }
+
+    /// The cached state if it was read less than `ttl` ago.
+    pub fn fresh(&self, ttl: Duration) -> Option<RadioData> {
+        match &self.0.lock().unwrap().entry {
+            Some((read_at, data)) if read_at.elapsed() < ttl => Some(data.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Every rig state change the poll loop sees, for the CAT server's `/ws`
+/// subscribers.  Sending with no subscribers is not an error.
+pub type RigUpdates = broadcast::Sender<Arc<RadioData>>;
+
+/// Where the poll loop announces a rig state change: the WebSocket server's
+/// watch channel, which also feeds the MQTT mirror, and CAT `/ws`.
+#[derive(Clone)]
+pub struct RigPublishers {
+    pub ws_tx: watch::Sender<Option<Arc<RadioData>>>,
+    pub updates: RigUpdates,
+}
+
+impl RigPublishers {
+    // Local clients are not rate-limited, so this is every change.
+    fn publish(&self, data: &RadioData) {
+        let data = Arc::new(data.clone());
+        let _ = self.ws_tx.send(Some(data.clone()));
+        let _ = self.updates.send(data);
+    }
+}
+
+/// What the poll loop read and sent for a CAT `/refresh` request.
+pub struct Refreshed {
+    pub data: RadioData,
+    /// Whether Wavelog accepted the upload.
+    pub uploaded: bool,
+}
+
+/// Something another task wants the poll loop to do.  The poll loop is the
+/// only task that uploads live-radio data, so its change detection and its
+/// record of what Wavelog was sent stay consistent.
+pub enum PollRequest {
+    /// Read the rig and upload immediately.  The reply is sent on the
+    /// enclosed oneshot once the upload has been attempted.
+    Refresh(oneshot::Sender<Result<Refreshed, ClientError>>),
+    /// WSJT-X logged a QSO on `frequency` (Hz) at `logged_at`, which the
+    /// poll may have missed if the rig moved between polls.
+    QsoLogged {
+        frequency: String,
+        logged_at: Instant,
+    },
+}
+
+pub type RefreshSender = mpsc::Sender<PollRequest>;
+pub type RefreshReceiver = mpsc::Receiver<PollRequest>;
+
+/// Why an upload to Wavelog failed.
+#[derive(Debug)]
+pub enum WavelogError {
+    /// The request could not be sent or the response not read.
+    Transport(String),
+    /// Wavelog did not answer in time.
+    Timeout(String),
+    /// Wavelog answered with a non-success status.  `body` is the start of
+    /// its reply, which usually says what was wrong.
+    HttpStatus { code: StatusCode, body: String },
+    /// The request body could not be encoded.
+    Serialize(String),
+}
+
+impl fmt::Display for WavelogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WavelogError::Transport(msg) => write!(f, "Transport error: {}", msg),
+            WavelogError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            WavelogError::HttpStatus { code, body } if body.is_empty() => {
+                write!(f, "Wavelog returned {}", code)
+            }
+            WavelogError::HttpStatus { code, body } => {
+                write!(f, "Wavelog returned {}: {}", code, body)
+            }
+            WavelogError::Serialize(msg) => write!(f, "Serialize error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WavelogError {}
+
+impl WavelogError {
+    /// Whether the same request might succeed later: Wavelog could not be
+    /// reached, was too slow, or failed on its side.  A request it refused
+    /// (4xx other than 429) will be refused again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            WavelogError::Transport(_) | WavelogError::Timeout(_) => true,
+            WavelogError::HttpStatus { code, .. } => {
+                code.is_server_error() || *code == StatusCode::TOO_MANY_REQUESTS
+            }
+            WavelogError::Serialize(_) => false,
+        }
+    }
 }
 
-async fn upload_live_radio_data(
+impl From<reqwest::Error> for WavelogError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            WavelogError::Timeout(error.to_string())
+        } else {
+            WavelogError::Transport(error.to_string())
+        }
+    }
+}
+
+impl From<serde_json::Error> for WavelogError {
+    fn from(error: serde_json::Error) -> Self {
+        WavelogError::Serialize(error.to_string())
+    }
+}
+
+// Longest stretch of an error reply kept in WavelogError::HttpStatus; enough
+// for Wavelog's JSON error messages without logging a whole HTML page.
+const MAX_ERROR_BODY_CHARS: usize = 200;
+
+// Pass a success response through; turn anything else into HttpStatus with
+// the start of the body.
+async fn check_status(response: Response) -> Result<Response, WavelogError> {
+    let code = response.status();
+    if code.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(WavelogError::HttpStatus {
+        code,
+        body: body.trim().chars().take(MAX_ERROR_BODY_CHARS).collect(),
+    })
+}
+
+// Send a request to Wavelog and check its status, recording the round trip
+// in `latency` if Wavelog answered at all.
+async fn send_timed(
+    request: RequestBuilder,
+    latency: &LatencyStats,
+) -> Result<Response, WavelogError> {
+    let started = Instant::now();
+    let response = request.send().await?;
+    latency.record_wavelog(started.elapsed());
+    check_status(response).await
+}
+
+pub(crate) async fn upload_live_radio_data(
     client: &Client,
     settings: &WavelogSettings,
     radio_data: &RadioData,
-) -> Result<(), Error> {
-    client.post(&settings.url).json(radio_data).send().await?;
+    latency: &LatencyStats,
+) -> Result<StatusCode, WavelogError> {
+    let body = serde_json::to_vec(radio_data)?;
+    let method = settings.radio_method.unwrap_or_default();
+    let request = client
+        .request(method.into(), &settings.url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body);
+    let response = send_timed(request, latency).await?;
 
-    Ok(())
+    Ok(response.status())
 }
 
+/// The made-up rig state `wlrigctl --test-upload` sends: 14.074 MHz USB at
+/// 5 W, under this station's key and identifier.
+pub fn test_radio_data(settings: &WavelogSettings) -> RadioData {
+    RadioData {
+        key: settings.key.clone(),
+        radio: settings.identifier.clone(),
+        frequency: "14074000".to_string(),
+        mode: "USB".to_string(),
+        rig_mode: "USB".to_string(),
+        power: "5".to_string(),
+        cat_url: settings.cat_url.clone(),
+        my_grid: settings.my_grid.clone(),
+    }
+}
+
+/// Send `test_radio_data` to the live-radio URL, to check the URL and API
+/// key without a rig.  Returns Wavelog's success status.
+pub async fn test_upload(settings: &WavelogSettings) -> Result<StatusCode, WavelogError> {
+    upload_live_radio_data(
+        &Client::new(),
+        settings,
+        &test_radio_data(settings),
+        &LatencyStats::default(),
+    )
+    .await
+}
+
+/// A logged QSO's `FREQ` (MHz in ADIF) in whole Hz, as live-radio data
+/// carries it.  None if the record has no usable frequency.
+pub fn qso_frequency(adif_text: &str) -> Option<String> {
+    let mhz: f64 = adif::field(adif_text, "FREQ")?.trim().parse().ok()?;
+    if !mhz.is_finite() || mhz <= 0.0 {
+        return None;
+    }
+    Some(format!("{}", (mhz * 1_000_000.0).round() as u64))
+}
+
+// Application-defined ADIF field naming the rig (`[wavelog] identifier`) that
+// logged a QSO, for logbooks fed by several wlrigctl instances.
+const RIG_ADIF_FIELD: &str = "APP_WLRIGCTL_RIG";
+
+// The record as sent to Wavelog: its mode remapped by mode_remap, and with
+// the configured adif_inject fields and the rig identifier added, none of
+// them replacing a field already there.
+fn qso_adif(settings: &WavelogSettings, adif_text: String) -> String {
+    let adif_text = match &settings.mode_remap {
+        Some(map) => adif::remap(&adif_text, &["MODE", "SUBMODE"], map),
+        None => adif_text,
+    };
+    let adif_text = match &settings.adif_inject {
+        Some(fields) => adif::inject(&adif_text, fields),
+        None => adif_text,
+    };
+    let rig = HashMap::from([(RIG_ADIF_FIELD.to_string(), settings.identifier.clone())]);
+    adif::inject(&adif_text, &rig)
+}
+
+/// Upload one ADIF QSO record to Wavelog's QSO API.
+///
+/// Returns Wavelog's success status; a QSO Wavelog rejected is reported as
+/// `WavelogError::HttpStatus`.
 pub async fn upload_wsjtx_qso_data(
     client: &Client,
     settings: &WavelogSettings,
     adif_text: String,
-) -> Result<(), Error> {
+    latency: &LatencyStats,
+) -> Result<StatusCode, WavelogError> {
+    let adif_text = qso_adif(settings, adif_text);
     let qso_data: Value = json!({
         "key": &settings.key,
         "station_profile_id": settings.station_profile_id,
@@ -58,63 +428,857 @@ pub async fn upload_wsjtx_qso_data(
         "string": adif_text
     });
 
-    client
-        .post(&settings.qso_url)
-        .json(&qso_data)
-        .send()
-        .await?;
+    let method = settings.qso_method.unwrap_or_default();
+    let request = client.request(method.into(), &settings.qso_url);
+    let request = if settings.compress.unwrap_or(false) {
+        request
+            .header(CONTENT_TYPE, "application/json")
+            .header(CONTENT_ENCODING, "gzip")
+            .body(gzip(qso_data.to_string().as_bytes()))
+    } else {
+        request.json(&qso_data)
+    };
+    let response = send_timed(request, latency).await?;
+
+    Ok(response.status())
+}
+
+// Gzip a request body.  Writing to a Vec cannot fail.
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|()| encoder.finish())
+        .expect("gzip into memory cannot fail")
+}
+
+// Poll interval in ms with jitter applied.  `r` is a uniform random number in
+// [0, 1); the result lies in [interval_ms, interval_ms * (1 + jitter)], with
+// jitter clamped to 0..=1 so a bad setting cannot stall or speed up polling.
+fn jittered_interval(interval_ms: u64, jitter: f64, r: f64) -> u64 {
+    let jitter = if jitter.is_finite() {
+        jitter.clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    interval_ms + (interval_ms as f64 * jitter * r).round() as u64
+}
+
+// Rate-limits live-radio uploads independently of the FLRig poll rate.  A
+// change that arrives too soon after the last upload is held as pending and
+// sent once the interval has elapsed, so the final state always reaches
+// Wavelog even if the rig then stops changing.
+struct UploadThrottle {
+    min_interval: Duration,
+    // Longest gap between uploads once there has been one; zero for none.
+    keepalive: Duration,
+    last_upload: Option<Instant>,
+    pending: bool,
+}
+
+impl UploadThrottle {
+    fn new(min_interval: Duration) -> UploadThrottle {
+        UploadThrottle {
+            min_interval,
+            keepalive: Duration::ZERO,
+            last_upload: None,
+            pending: false,
+        }
+    }
+
+    fn changed(&mut self) {
+        self.pending = true;
+    }
 
-    Ok(())
+    // Record an upload made outside the throttle, e.g. a forced refresh.
+    fn uploaded(&mut self, now: Instant) {
+        self.pending = false;
+        self.last_upload = Some(now);
+    }
+
+    // True if a pending change should be uploaded now, or the keepalive has
+    // run out; records the upload.
+    fn due(&mut self, now: Instant) -> bool {
+        let ready = self
+            .last_upload
+            .is_none_or(|last| now.duration_since(last) >= self.min_interval);
+        let stale = !self.keepalive.is_zero()
+            && self
+                .last_upload
+                .is_some_and(|last| now.duration_since(last) >= self.keepalive);
+        if (self.pending && ready) || stale {
+            self.pending = false;
+            self.last_upload = Some(now);
+            return true;
+        }
+        false
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn wavelog_thread(
     settings: WavelogSettings,
     rig_poll: Arc<flrig::FLRig>,
+    live: SharedLiveSettings,
     token: CancellationToken,
-    ws_tx: watch::Sender<Option<Arc<RadioData>>>,
-) {
-    let mut radio_data_current = RadioData {
+    publishers: RigPublishers,
+    refresh_rx: RefreshReceiver,
+    cache: RadioCache,
+    latency: SharedLatency,
+) -> JoinHandle<()> {
+    // A panic restarts the loop from scratch, so what must outlive a run is
+    // kept outside it for the next run to pick up.
+    let carried = Arc::new(Carried {
+        refresh_rx: tokio::sync::Mutex::new(refresh_rx),
+        last_uploaded: Mutex::new(None),
+        latency,
+    });
+    supervise("Wavelog poll loop", token.clone(), move || {
+        poll_loop(
+            settings.clone(),
+            rig_poll.clone(),
+            live.clone(),
+            token.clone(),
+            publishers.clone(),
+            carried.clone(),
+            cache.clone(),
+        )
+    })
+}
+
+// Poll loop state that survives a restart after a panic.
+struct Carried {
+    refresh_rx: tokio::sync::Mutex<RefreshReceiver>,
+    // What Wavelog was last sent, to seed the next run's change detection.
+    last_uploaded: Mutex<Option<RadioData>>,
+    // Round-trip stats the uploads are recorded into.
+    latency: SharedLatency,
+}
+
+// The state a poll loop run starts from: what was last uploaded, so a
+// restart does not re-send a rig that has not moved, or else nothing, so the
+// first reading is always sent.
+fn seed_radio_data(settings: &WavelogSettings, last_uploaded: Option<RadioData>) -> RadioData {
+    last_uploaded.unwrap_or_else(|| RadioData {
         key: settings.key.clone(),
         radio: settings.identifier.clone(),
         frequency: String::from(""),
         mode: String::from(""),
+        rig_mode: String::new(),
         power: String::from("0"),
         cat_url: settings.cat_url.clone(),
-    };
+        my_grid: settings.my_grid.clone(),
+    })
+}
 
-    tokio::task::spawn(async move {
-        let client = Client::new();
-        loop {
-            match rig_poll.get_radio_data().await {
-                Ok(Some(radio_data_new)) => {
-                    if radio_data_current.frequency != radio_data_new.frequency
-                        || radio_data_current.mode != radio_data_new.mode
-                        || radio_data_current.power != radio_data_new.power
-                    {
-                        radio_data_current.frequency = radio_data_new.frequency;
-                        radio_data_current.mode = radio_data_new.mode;
-                        radio_data_current.power = radio_data_new.power;
-
-                        if let Err(e) =
-                            upload_live_radio_data(&client, &settings, &radio_data_current).await
-                        {
-                            debug!("Wavelog upload failed (may be transient): {e}");
-                        }
-                        // Publish new state to WebSocket clients via watch channel.
-                        let _ = ws_tx.send(Some(Arc::new(radio_data_current.clone())));
-                    }
+// Take the frequency of a QSO logged at `logged_at` into `current`, keeping
+// the rig's mode and power; true if that changed it.  Ignored if the rig has
+// been read since (`last_read`), as that reading is newer, or if no mode has
+// been read yet to go with it.
+fn apply_logged_qso(
+    current: &mut RadioData,
+    frequency: String,
+    logged_at: Instant,
+    last_read: Option<Instant>,
+) -> bool {
+    match last_read {
+        Some(read_at)
+            if read_at <= logged_at
+                && !current.mode.is_empty()
+                && current.frequency != frequency =>
+        {
+            current.frequency = frequency;
+            true
+        }
+        _ => false,
+    }
+}
+
+// Take a new reading into `current`; true if it changed anything Wavelog sees.
+fn apply_reading(current: &mut RadioData, new: RadioData) -> bool {
+    if current.frequency == new.frequency && current.mode == new.mode && current.power == new.power
+    {
+        return false;
+    }
+    current.frequency = new.frequency;
+    current.mode = new.mode;
+    current.rig_mode = new.rig_mode;
+    current.power = new.power;
+    true
+}
+
+async fn poll_loop(
+    settings: WavelogSettings,
+    rig_poll: Arc<flrig::FLRig>,
+    live: SharedLiveSettings,
+    token: CancellationToken,
+    publishers: RigPublishers,
+    carried: Arc<Carried>,
+    cache: RadioCache,
+) {
+    let mut refresh_rx = carried.refresh_rx.lock().await;
+    let last_uploaded = carried.last_uploaded.lock().unwrap().clone();
+    let mut radio_data_current = seed_radio_data(&settings, last_uploaded);
+
+    let mut throttle = UploadThrottle::new(Duration::ZERO);
+    let mut errors = RepeatedErrors::default();
+    // When FLRig last told us the rig's state, changed or not.
+    let mut last_read: Option<Instant> = None;
+
+    let client = Client::new();
+    loop {
+        // Intervals are re-read every poll so a config reload applies.
+        let (poll_interval, jitter, min_upload_interval_ms, keepalive_secs) = {
+            let live = live.read().unwrap();
+            (
+                live.poll_interval_ms,
+                live.jitter,
+                live.min_upload_interval_ms,
+                live.keepalive_secs,
+            )
+        };
+        throttle.min_interval = Duration::from_millis(min_upload_interval_ms);
+        throttle.keepalive = Duration::from_secs(keepalive_secs);
+
+        let generation = cache.generation();
+        match rig_poll.get_radio_data().await {
+            Ok(Some(radio_data_new)) => {
+                last_read = Some(Instant::now());
+                if apply_reading(&mut radio_data_current, radio_data_new) {
+                    throttle.changed();
+                    // Publish new state to WebSocket and CAT /ws clients.
+                    publishers.publish(&radio_data_current);
                 }
-                Ok(None) => {} // FLRig reports nothing changed; skip this cycle
-                Err(e) => info!("Got err:{:#?}", e),
+                cache.store(generation, &radio_data_current);
+                rig_poll.log_vfo_b().await;
             }
+            // FLRig reports nothing changed; skip this cycle.  No reading
+            // was taken, so the cache is left to age.
+            Ok(None) => last_read = Some(Instant::now()),
+            Err(e) => errors.log(log::Level::Info, &format!("Could not read the rig: {e}")),
+        }
 
-            tokio::select! {
-                _ = token.cancelled() => {
-                    info!("wavelog thread shutting down");
-                    return;
+        if throttle.due(Instant::now()) {
+            match upload_live_radio_data(&client, &settings, &radio_data_current, &carried.latency)
+                .await
+            {
+                Ok(_) => *carried.last_uploaded.lock().unwrap() = Some(radio_data_current.clone()),
+                Err(e) => errors.log(
+                    log::Level::Debug,
+                    &format!("Wavelog upload failed (may be transient): {e}"),
+                ),
+            }
+        }
+
+        tokio::select! {
+            _ = token.cancelled() => {
+                info!("wavelog thread shutting down");
+                return;
+            }
+            _ = tokio::time::sleep(Duration::from_millis(jittered_interval(
+                poll_interval,
+                jitter,
+                fastrand::f64(),
+            ))) => {}
+            Some(request) = refresh_rx.recv() => match request {
+                PollRequest::QsoLogged { frequency, logged_at } => {
+                    // Sent with the next upload the throttle allows, unless the
+                    // poll that follows straight away finds the rig elsewhere.
+                    let current = &mut radio_data_current;
+                    if apply_logged_qso(current, frequency, logged_at, last_read) {
+                        throttle.changed();
+                        publishers.publish(&radio_data_current);
+                    }
+                }
+                PollRequest::Refresh(reply) => {
+                    // A forced read and upload, done here so it cannot
+                    // interleave with a regular poll.
+                    let generation = cache.generation();
+                    let result = match rig_poll.fetch_radio_data().await {
+                        Ok(fresh) => {
+                            last_read = Some(Instant::now());
+                            radio_data_current.frequency = fresh.frequency;
+                            radio_data_current.mode = fresh.mode;
+                            radio_data_current.rig_mode = fresh.rig_mode;
+                            radio_data_current.power = fresh.power;
+                            publishers.publish(&radio_data_current);
+                            cache.store(generation, &radio_data_current);
+                            let uploaded = upload_live_radio_data(
+                                &client,
+                                &settings,
+                                &radio_data_current,
+                                &carried.latency,
+                            )
+                            .await;
+                            match &uploaded {
+                                Ok(_) => {
+                                    *carried.last_uploaded.lock().unwrap() =
+                                        Some(radio_data_current.clone())
+                                }
+                                Err(e) => warn!("Wavelog upload for refresh failed: {e}"),
+                            }
+                            throttle.uploaded(Instant::now());
+                            Ok(Refreshed {
+                                data: radio_data_current.clone(),
+                                uploaded: uploaded.is_ok(),
+                            })
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let _ = reply.send(result);
                 }
-                _ = tokio::time::sleep(Duration::from_millis(settings.interval)) => {}
             }
         }
-    });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_settings() -> WavelogSettings {
+        WavelogSettings {
+            url: "http://localhost/index.php/api/radio".to_string(),
+            qso_url: "http://localhost/index.php/api/qso".to_string(),
+            key: "wl123".to_string(),
+            identifier: "rig".to_string(),
+            station_profile_id: 2,
+            interval: 200,
+            cat_url: None,
+            my_grid: None,
+            jitter: None,
+            min_upload_interval_ms: None,
+            keepalive_secs: None,
+            compress: None,
+            adif_inject: None,
+            mode_remap: None,
+            local_adif: None,
+            qso_spool: None,
+            radio_method: None,
+            qso_method: None,
+            base_url: None,
+        }
+    }
+
+    #[test]
+    fn qso_adif_names_the_rig() {
+        let mut settings = test_settings();
+        settings.adif_inject = Some(HashMap::from([(
+            "MY_GRIDSQUARE".to_string(),
+            "IO91wm".to_string(),
+        )]));
+        let adif = qso_adif(&settings, "<call:5>G4ABC <eor>".to_string());
+        assert_eq!(
+            adif,
+            "<call:5>G4ABC <MY_GRIDSQUARE:6>IO91wm <APP_WLRIGCTL_RIG:3>rig <eor>"
+        );
+
+        // A record that already names a rig keeps it.
+        let adif = "<call:5>G4ABC <app_wlrigctl_rig:4>Rig2 <eor>";
+        assert_eq!(qso_adif(&test_settings(), adif.to_string()), adif);
+    }
+
+    #[tokio::test]
+    async fn mode_remap_rewrites_uploaded_mode() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut settings = test_settings();
+        settings.qso_url = crate::mock_wavelog::mock_wavelog(StatusCode::OK, tx).await;
+        settings.mode_remap = Some(HashMap::from([("FST4W".to_string(), "MFSK".to_string())]));
+
+        upload_wsjtx_qso_data(
+            &Client::new(),
+            &settings,
+            "<call:5>G4ABC <mode:5>FST4W <eor>".to_string(),
+            &LatencyStats::default(),
+        )
+        .await
+        .unwrap();
+        let body: Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(
+            body["string"],
+            "<call:5>G4ABC <mode:4>MFSK <APP_WLRIGCTL_RIG:3>rig <eor>"
+        );
+
+        // Absent, the mode passes through.
+        assert_eq!(
+            qso_adif(
+                &test_settings(),
+                "<call:5>G4ABC <mode:5>FST4W <eor>".to_string()
+            ),
+            "<call:5>G4ABC <mode:5>FST4W <APP_WLRIGCTL_RIG:3>rig <eor>"
+        );
+    }
+
+    #[tokio::test]
+    async fn configured_methods_are_used() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let url = crate::mock_wavelog::mock_wavelog_requests(StatusCode::OK, tx).await;
+        let mut settings = test_settings();
+        settings.url = url.clone();
+        settings.qso_url = url;
+        settings.radio_method = Some(HttpMethod::Put);
+        let client = Client::new();
+        let latency = LatencyStats::default();
+
+        upload_live_radio_data(
+            &client,
+            &settings,
+            &seed_radio_data(&settings, None),
+            &latency,
+        )
+        .await
+        .unwrap();
+        assert_eq!(rx.recv().await.unwrap().0, reqwest::Method::PUT);
+
+        // QSOs keep the default.
+        upload_wsjtx_qso_data(
+            &client,
+            &settings,
+            "<call:5>G4ABC <eor>".to_string(),
+            &latency,
+        )
+        .await
+        .unwrap();
+        assert_eq!(rx.recv().await.unwrap().0, reqwest::Method::POST);
+        assert_eq!(latency.wavelog().unwrap().samples, 2);
+    }
+
+    fn base_url_settings() -> WavelogSettings {
+        let mut settings = test_settings();
+        settings.url = String::new();
+        settings.qso_url = String::new();
+        settings.base_url = Some("https://log.example.org/".to_string());
+        settings
+    }
+
+    #[test]
+    fn base_url_builds_both_endpoints() {
+        let mut settings = base_url_settings();
+        settings.resolve_endpoints().unwrap();
+        assert_eq!(settings.url, "https://log.example.org/index.php/api/radio");
+        assert_eq!(
+            settings.qso_url,
+            "https://log.example.org/index.php/api/qso"
+        );
+    }
+
+    #[test]
+    fn explicit_urls_override_the_built_ones() {
+        let mut settings = base_url_settings();
+        settings.qso_url = "http://proxy.lan/qso".to_string();
+        settings.resolve_endpoints().unwrap();
+        assert_eq!(settings.url, "https://log.example.org/index.php/api/radio");
+        assert_eq!(settings.qso_url, "http://proxy.lan/qso");
+
+        // With both given, base_url is not needed.
+        let mut settings = test_settings();
+        settings.resolve_endpoints().unwrap();
+        assert_eq!(settings.url, "http://localhost/index.php/api/radio");
+    }
+
+    #[test]
+    fn missing_urls_without_base_url_are_an_error() {
+        let mut settings = base_url_settings();
+        settings.base_url = None;
+        assert!(settings.resolve_endpoints().is_err());
+    }
+
+    #[test]
+    fn wavelog_radio_payload_includes_cat_url() {
+        let mut settings = test_settings();
+        settings.cat_url = Some("http://127.0.0.1:54321".to_string());
+        let mut data = seed_radio_data(&settings, None);
+        data.frequency = "14074000".to_string();
+        data.mode = "USB".to_string();
+        data.power = "25".to_string();
+
+        assert_eq!(
+            json!(data),
+            json!({
+                "key": "wl123",
+                "radio": "rig",
+                "frequency": "14074000",
+                "mode": "USB",
+                "power": "25",
+                "cat_url": "http://127.0.0.1:54321",
+            })
+        );
+    }
+
+    #[test]
+    fn my_grid_is_sent_only_when_configured() {
+        let mut settings = test_settings();
+        let payload = json!(seed_radio_data(&settings, None));
+        assert!(payload.get("my_grid").is_none(), "{payload}");
+
+        settings.my_grid = Some("IO91wm".to_string());
+        let payload = json!(seed_radio_data(&settings, None));
+        assert_eq!(payload["my_grid"], "IO91wm");
+        // Readings keep it, as they keep cat_url.
+        let mut data = seed_radio_data(&settings, None);
+        assert!(apply_reading(&mut data, test_radio_data(&test_settings())));
+        assert_eq!(data.my_grid.as_deref(), Some("IO91wm"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_sends_synthetic_rig_state() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut settings = test_settings();
+        settings.url = crate::mock_wavelog::mock_wavelog(StatusCode::OK, tx).await;
+
+        assert_eq!(test_upload(&settings).await.unwrap(), StatusCode::OK);
+        let body: Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "key": "wl123",
+                "radio": "rig",
+                "frequency": "14074000",
+                "mode": "USB",
+                "power": "5",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_reports_rejection_with_body() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut settings = test_settings();
+        settings.url = crate::mock_wavelog::mock_wavelog_replying(
+            StatusCode::UNAUTHORIZED,
+            "Invalid API key",
+            tx,
+        )
+        .await;
+
+        let err = test_upload(&settings).await.unwrap_err();
+        assert!(
+            matches!(&err, WavelogError::HttpStatus { code, body }
+                if *code == StatusCode::UNAUTHORIZED && body == "Invalid API key"),
+            "{err}"
+        );
+        assert!(err.to_string().contains("Invalid API key"), "{err}");
+    }
+
+    #[test]
+    fn http_method_must_be_known() {
+        let parse = |s: &str| serde_json::from_value::<HttpMethod>(json!(s));
+        assert_eq!(parse("PUT").unwrap(), HttpMethod::Put);
+        assert!(parse("GET").is_err());
+        assert!(parse("put").is_err());
+    }
+
+    #[test]
+    fn wavelog_error_display() {
+        let cases = [
+            (
+                WavelogError::Transport("connection refused".to_string()),
+                "Transport error: connection refused",
+            ),
+            (
+                WavelogError::Timeout("operation timed out".to_string()),
+                "Timeout: operation timed out",
+            ),
+            (
+                WavelogError::HttpStatus {
+                    code: StatusCode::UNAUTHORIZED,
+                    body: r#"{"status":"failed","reason":"missing api key"}"#.to_string(),
+                },
+                r#"Wavelog returned 401 Unauthorized: {"status":"failed","reason":"missing api key"}"#,
+            ),
+            (
+                WavelogError::HttpStatus {
+                    code: StatusCode::BAD_GATEWAY,
+                    body: String::new(),
+                },
+                "Wavelog returned 502 Bad Gateway",
+            ),
+            (
+                WavelogError::Serialize("key must be a string".to_string()),
+                "Serialize error: key must be a string",
+            ),
+        ];
+        for (err, expected) in cases {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn serde_error_converts_to_serialize() {
+        let err = serde_json::from_str::<Value>("{").unwrap_err();
+        assert!(matches!(
+            WavelogError::from(err),
+            WavelogError::Serialize(_)
+        ));
+    }
+
+    #[test]
+    fn gzip_body_round_trips() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let body = json!({
+            "key": "wl123",
+            "type": "adif",
+            "string": "<call:5>G4ABC <band:3>20m <mode:3>FT8 <eor>".repeat(50),
+        })
+        .to_string();
+        let compressed = gzip(body.as_bytes());
+        assert!(compressed.len() < body.len());
+
+        let mut decoded = String::new();
+        GzDecoder::new(compressed.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn upload_throttle_limits_rate_while_polling_continues() {
+        let mut throttle = UploadThrottle::new(Duration::from_millis(1000));
+        let t0 = Instant::now();
+        let mut uploads = Vec::new();
+        // Poll every 100ms for 2.5s with the rig changing on every poll.
+        for tick in 0..25 {
+            let now = t0 + Duration::from_millis(tick * 100);
+            throttle.changed();
+            if throttle.due(now) {
+                uploads.push(tick * 100);
+            }
+        }
+        assert_eq!(uploads, vec![0, 1000, 2000]);
+    }
+
+    #[test]
+    fn upload_throttle_flushes_pending_change_later() {
+        let mut throttle = UploadThrottle::new(Duration::from_millis(1000));
+        let t0 = Instant::now();
+        throttle.changed();
+        assert!(throttle.due(t0));
+        throttle.changed();
+        assert!(!throttle.due(t0 + Duration::from_millis(500)));
+        // No further change, but the held one is still sent.
+        assert!(throttle.due(t0 + Duration::from_millis(1000)));
+        assert!(!throttle.due(t0 + Duration::from_millis(3000)));
+    }
+
+    #[test]
+    fn upload_throttle_keepalive_resends_unchanged_state() {
+        let mut throttle = UploadThrottle::new(Duration::from_millis(1000));
+        throttle.keepalive = Duration::from_secs(60);
+        let t0 = Instant::now();
+        // Nothing to keep alive before the first upload.
+        assert!(!throttle.due(t0 + Duration::from_secs(120)));
+
+        throttle.changed();
+        assert!(throttle.due(t0));
+        assert!(!throttle.due(t0 + Duration::from_secs(59)));
+        // No change for a minute: sent anyway, then not again for a minute.
+        assert!(throttle.due(t0 + Duration::from_secs(60)));
+        assert!(!throttle.due(t0 + Duration::from_secs(90)));
+        assert!(throttle.due(t0 + Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn upload_throttle_without_keepalive_waits_for_change() {
+        let mut throttle = UploadThrottle::new(Duration::from_millis(1000));
+        let t0 = Instant::now();
+        throttle.changed();
+        assert!(throttle.due(t0));
+        assert!(!throttle.due(t0 + Duration::from_secs(3600)));
+    }
+
+    fn reading(frequency: &str, mode: &str) -> RadioData {
+        RadioData {
+            key: String::new(),
+            radio: String::new(),
+            frequency: frequency.to_string(),
+            mode: mode.to_string(),
+            rig_mode: mode.to_string(),
+            power: "10".to_string(),
+            cat_url: None,
+            my_grid: None,
+        }
+    }
+
+    #[test]
+    fn seeded_restart_uploads_only_a_real_change() {
+        let settings = test_settings();
+        let t0 = Instant::now();
+
+        // A restart seeded with what was last uploaded: an unmoved rig is
+        // not sent again...
+        let mut current = seed_radio_data(&settings, Some(reading("14074000", "USB")));
+        let mut throttle = UploadThrottle::new(Duration::ZERO);
+        if apply_reading(&mut current, reading("14074000", "USB")) {
+            throttle.changed();
+        }
+        assert!(!throttle.due(t0));
+
+        // ...but the first real change is.
+        if apply_reading(&mut current, reading("7074000", "USB")) {
+            throttle.changed();
+        }
+        assert!(throttle.due(t0));
+        assert_eq!(current.frequency, "7074000");
+
+        // With nothing uploaded before, the first reading is always sent.
+        let mut current = seed_radio_data(&settings, None);
+        assert!(apply_reading(&mut current, reading("14074000", "USB")));
+        assert_eq!(current.key, settings.key);
+    }
+
+    #[test]
+    fn jittered_interval_stays_within_bounds() {
+        for _ in 0..1000 {
+            let ms = jittered_interval(200, 0.1, fastrand::f64());
+            assert!((200..=220).contains(&ms), "{ms} out of bounds");
+        }
+    }
+
+    #[test]
+    fn jittered_interval_extremes() {
+        assert_eq!(jittered_interval(200, 0.0, 0.999), 200);
+        assert_eq!(jittered_interval(200, 0.5, 0.0), 200);
+        assert_eq!(jittered_interval(200, 0.5, 1.0), 300);
+    }
+
+    #[test]
+    fn jittered_interval_clamps_bad_settings() {
+        assert_eq!(jittered_interval(200, -1.0, 0.5), 200);
+        assert_eq!(jittered_interval(200, 5.0, 1.0), 400);
+        assert_eq!(jittered_interval(200, f64::NAN, 0.5), 200);
+    }
+
+    #[test]
+    fn frequency_of_logged_qso() {
+        let adif = "<call:5>G4ABC <freq:9>14.075500 <mode:4>MFSK <submode:3>FT4 <eor>";
+        assert_eq!(qso_frequency(adif).as_deref(), Some("14075500"));
+        assert_eq!(qso_frequency("<call:5>G4ABC <mode:3>FT8 <eor>"), None);
+        assert_eq!(qso_frequency("<freq:1>0 <eor>"), None);
+    }
+
+    #[test]
+    fn logged_qso_moves_frequency_but_keeps_rig_mode() {
+        let mut current = seed_radio_data(&test_settings(), None);
+        current.frequency = "14074000".to_string();
+        current.mode = "DIGI".to_string();
+        let read_at = Instant::now();
+        let logged_at = read_at + Duration::from_secs(1);
+
+        assert!(apply_logged_qso(
+            &mut current,
+            "10136000".to_string(),
+            logged_at,
+            Some(read_at)
+        ));
+        assert_eq!(current.frequency, "10136000");
+        assert_eq!(current.mode, "DIGI");
+        // Already there: nothing to send.
+        assert!(!apply_logged_qso(
+            &mut current,
+            "10136000".to_string(),
+            logged_at,
+            Some(read_at)
+        ));
+    }
+
+    #[test]
+    fn read_begun_before_invalidation_is_not_cached() {
+        let cache = RadioCache::default();
+        let generation = cache.generation();
+        cache.store(generation, &reading("7074000", "USB"));
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.fresh(ttl).unwrap().frequency, "7074000");
+
+        // A poll starts, a QSY clears the cache, then the poll finishes.
+        let generation = cache.generation();
+        cache.invalidate();
+        cache.store(generation, &reading("7074000", "USB"));
+        assert!(cache.fresh(ttl).is_none());
+
+        cache.store(cache.generation(), &reading("14074000", "USB"));
+        assert_eq!(cache.fresh(ttl).unwrap().frequency, "14074000");
+        assert!(cache.fresh(Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn logged_qso_does_not_override_newer_rig_reading() {
+        let mut current = seed_radio_data(&test_settings(), None);
+        current.frequency = "14074000".to_string();
+        let logged_at = Instant::now();
+        let read_at = logged_at + Duration::from_secs(1);
+
+        assert!(!apply_logged_qso(
+            &mut current,
+            "10136000".to_string(),
+            logged_at,
+            Some(read_at)
+        ));
+        // Nor with no reading at all, when there is no mode to send.
+        assert!(!apply_logged_qso(
+            &mut current,
+            "10136000".to_string(),
+            logged_at,
+            None
+        ));
+        assert!(!apply_logged_qso(
+            &mut current,
+            "10136000".to_string(),
+            logged_at,
+            Some(logged_at - Duration::from_secs(1))
+        ));
+        assert_eq!(current.frequency, "14074000");
+    }
+
+    #[tokio::test]
+    async fn poll_loop_stops_on_shutdown() {
+        let flrig = crate::mock_flrig::MockFlrig::start(&[
+            ("rig.get_update", "<string>vfoA</string>"),
+            ("rig.get_vfo", "<string>7074000</string>"),
+            ("rig.get_mode", "<string>D-USB</string>"),
+            ("rig.get_maxpwr", "<i4>100</i4>"),
+            ("rig.get_power", "<i4>25</i4>"),
+        ])
+        .await;
+        let (tx, mut uploads) = tokio::sync::mpsc::unbounded_channel();
+        let token = CancellationToken::new();
+
+        let mut settings = test_settings();
+        settings.url = crate::mock_wavelog::mock_wavelog(StatusCode::OK, tx).await;
+        let cat: crate::cat::CatSettings = serde_json::from_value(json!({"port": 0})).unwrap();
+        let live = Arc::new(std::sync::RwLock::new(crate::settings::LiveSettings::new(
+            &settings,
+            &flrig.settings(),
+            &cat,
+        )));
+        let rig = Arc::new(flrig::FLRig::new(flrig.settings(), "test-rig".to_string()));
+        let poll_task = wavelog_thread(
+            settings,
+            rig,
+            live,
+            token.clone(),
+            RigPublishers {
+                ws_tx: watch::channel(None).0,
+                updates: broadcast::channel(16).0,
+            },
+            mpsc::channel(1).1,
+            RadioCache::default(),
+            SharedLatency::default(),
+        );
+
+        // Running: the first reading is uploaded.
+        tokio::time::timeout(Duration::from_secs(5), uploads.recv())
+            .await
+            .expect("the poll loop never uploaded")
+            .unwrap();
+
+        token.cancel();
+        let aborted = crate::supervise::join_with_timeout(
+            vec![("Wavelog poll loop", poll_task)],
+            Duration::from_secs(5),
+        )
+        .await;
+        assert!(aborted.is_empty(), "the poll loop ignored the shutdown");
+    }
 }
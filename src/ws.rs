@@ -5,7 +5,7 @@ use rcgen::generate_simple_self_signed;
 use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
 use rustls::ServerConfig;
 use rustls_pki_types::pem::PemObject;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::fs;
 use std::io;
@@ -23,7 +23,7 @@ use tokio_util::sync::CancellationToken;
 /// The entire `[websocket]` section is optional in `config.toml`.  When absent,
 /// all fields take their defaults and the server starts automatically on
 /// `127.0.0.1:54323` using a persistent self-signed TLS certificate.
-#[derive(Debug, Deserialize, Clone, Default)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct WsSettings {
     /// Interface to bind on.  Defaults to `127.0.0.1`.
     pub host: Option<String>,
@@ -53,7 +53,7 @@ impl WsSettings {
 // ── TLS helpers ──────────────────────────────────────────────────────────────
 
 /// Build a [`TlsAcceptor`] from PEM files provided by the user.
-fn load_tls_acceptor(cert_path: &str, key_path: &str) -> io::Result<TlsAcceptor> {
+pub(crate) fn load_tls_acceptor(cert_path: &str, key_path: &str) -> io::Result<TlsAcceptor> {
     let cert_chain: Vec<CertificateDer<'static>> = CertificateDer::pem_file_iter(cert_path)
         .map_err(io::Error::other)?
         .collect::<Result<Vec<_>, _>>()
@@ -146,19 +146,38 @@ fn persistent_self_signed_acceptor(config_dir: &Path, addr: SocketAddr) -> io::R
 
 // ── Per-client handler ────────────────────────────────────────────────────────
 
-fn radio_status_msg(data: &RadioData) -> Message {
-    let msg = json!({
-        "type":      "radio_status",
+/// The fields of a "radio_status" message, less the "type" and "timestamp"
+/// that [`ws_message`] adds.  Shared with the CAT server's `/ws` stream, so
+/// one client can read either.
+pub(crate) fn radio_status_fields(data: &RadioData) -> serde_json::Map<String, serde_json::Value> {
+    let serde_json::Value::Object(fields) = json!({
         "frequency": data.frequency.parse::<u64>().unwrap_or(0),
         "mode":      data.mode,
         "power":     data.power.parse::<f32>().unwrap_or(0.0),
         "radio":     data.radio,
-        "timestamp": std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map(|d| d.as_millis())
-                        .unwrap_or(0),
-    });
-    Message::Text(msg.to_string().into())
+    }) else {
+        unreachable!("json! of an object literal")
+    };
+    fields
+}
+
+/// A text message of type `kind` carrying `fields`, stamped with the time
+/// it was sent in milliseconds since the epoch.
+pub(crate) fn ws_message(
+    kind: &str,
+    mut fields: serde_json::Map<String, serde_json::Value>,
+) -> Message {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    fields.insert("type".to_string(), json!(kind));
+    fields.insert("timestamp".to_string(), json!(timestamp));
+    Message::Text(serde_json::Value::Object(fields).to_string().into())
+}
+
+fn radio_status_msg(data: &RadioData) -> Message {
+    ws_message("radio_status", radio_status_fields(data))
 }
 
 async fn handle_client(
@@ -395,8 +414,10 @@ mod tests {
             radio: "IC-703".to_string(),
             frequency: "14074000".to_string(),
             mode: "USB".to_string(),
+            rig_mode: "USB".to_string(),
             power: "10".to_string(),
             cat_url: None,
+            my_grid: None,
         };
         let msg = radio_status_msg(&data);
         let json: serde_json::Value = match msg {
@@ -418,8 +439,10 @@ mod tests {
             radio: "test".to_string(),
             frequency: "not-a-number".to_string(),
             mode: "USB".to_string(),
+            rig_mode: "USB".to_string(),
             power: "??".to_string(),
             cat_url: None,
+            my_grid: None,
         };
         let msg = radio_status_msg(&data);
         let json: serde_json::Value = match msg {
@@ -439,8 +462,10 @@ mod tests {
             radio: "IC-703".to_string(),
             frequency: "14074000".to_string(),
             mode: "USB".to_string(),
+            rig_mode: "USB".to_string(),
             power: "5".to_string(),
             cat_url: None,
+            my_grid: None,
         });
         let (tx, mut rx) = watch::channel::<Option<Arc<RadioData>>>(Some(data.clone()));
         let initial = rx.borrow_and_update().clone();
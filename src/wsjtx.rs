@@ -1,21 +1,149 @@
-use crate::wavelog::{upload_wsjtx_qso_data, WavelogSettings};
+use crate::adif::AdifLog;
+use crate::latency::SharedLatency;
+use crate::logging::RepeatedErrors;
+use crate::spool::QsoSpool;
+use crate::supervise::supervise;
+use crate::wavelog::{
+    qso_frequency, upload_wsjtx_qso_data, PollRequest, RefreshSender, WavelogSettings,
+};
 use bincode2::LengthOption::U32;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Display;
-use std::net::SocketAddr;
+use std::net::{Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::{Arc, Mutex};
 use tokio::net::UdpSocket;
-use tokio::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
 // Settings from config file
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct WsjtxSettings {
     pub host: String,
     pub port: u16,
     pub err_timeout: u64,
+    /// Extra `host:port` UDP destinations that receive a verbatim copy of every
+    /// WSJT-X datagram, so GridTracker/JTAlert keep working without multicast.
+    /// Example: forward_to = ["127.0.0.1:2238", "192.168.1.20:2237"]
+    pub forward_to: Option<Vec<String>>,
+    /// UDP receive buffer size in bytes.  Defaults to 1500; raise it if
+    /// WSJT-X logs QSOs with long comments or contest exchanges and the log
+    /// reports truncated datagrams.  At most 65507, the largest UDP payload.
+    pub rxbuf_bytes: Option<usize>,
+    /// Software transmit watchdog: if WSJT-X reports transmitting for longer
+    /// than this many seconds without a break, send it HaltTx.  Off if absent.
+    pub max_tx_secs: Option<u64>,
+    /// Listen on `[::]:port` with IPV6_V6ONLY off, so one socket receives
+    /// both IPv4 and IPv6 datagrams.  `host` is then ignored and the listener
+    /// is reachable on every interface.  Off by default.
+    pub dual_stack: Option<bool>,
+    /// Magic numbers a datagram may start with, as hex ("0xadbccbda") or
+    /// decimal strings, for forks of WSJT-X that frame the protocol with
+    /// their own.  Defaults to WSJT-X's only.
+    pub magic: Option<Vec<String>>,
+    /// Schema versions accepted in the datagram header.  Defaults to [2],
+    /// WSJT-X's.
+    pub schema: Option<Vec<u32>>,
+}
+
+impl WsjtxSettings {
+    /// Check that `magic` and `schema` make a usable header check, so a
+    /// typo stops wlrigctl at startup rather than silently dropping every
+    /// datagram.
+    pub fn validate(&self) -> Result<(), String> {
+        AcceptedHeader::new(self).map(|_| ())
+    }
+}
+
+// The magic numbers and schema versions a received datagram may carry.
+#[derive(Clone, Debug)]
+struct AcceptedHeader {
+    magics: Vec<u32>,
+    schemas: Vec<u32>,
+}
+
+impl Default for AcceptedHeader {
+    fn default() -> AcceptedHeader {
+        AcceptedHeader {
+            magics: vec![WSJTX_MAGIC],
+            schemas: vec![WSJTX_SCHEMA],
+        }
+    }
+}
+
+impl AcceptedHeader {
+    fn new(settings: &WsjtxSettings) -> Result<AcceptedHeader, String> {
+        let mut accepted = AcceptedHeader::default();
+        if let Some(magic) = &settings.magic {
+            accepted.magics = magic
+                .iter()
+                .map(|m| parse_magic(m))
+                .collect::<Result<_, _>>()?;
+        }
+        if let Some(schema) = &settings.schema {
+            accepted.schemas = schema.clone();
+        }
+        if accepted.magics.is_empty() || accepted.schemas.is_empty() {
+            return Err("[WSJTX] magic and schema must not be empty lists".to_string());
+        }
+        Ok(accepted)
+    }
+}
+
+// A configured magic number: hex with a "0x" prefix, or decimal.
+fn parse_magic(text: &str) -> Result<u32, String> {
+    let trimmed = text.trim();
+    let parsed = match trimmed
+        .strip_prefix("0x")
+        .or_else(|| trimmed.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => trimmed.parse::<u32>(),
+    };
+    parsed.map_err(|e| format!("[WSJTX] magic '{text}' is not a 32-bit number: {e}"))
+}
+
+// Resolve the forward_to list once at startup; entries that are not a valid
+// ip:port are reported and skipped rather than failing the whole listener.
+fn parse_forward_peers(entries: &[String]) -> Vec<SocketAddr> {
+    entries
+        .iter()
+        .filter_map(|entry| match entry.parse::<SocketAddr>() {
+            Ok(addr) => Some(addr),
+            Err(e) => {
+                warn!("Ignoring WSJT-X forward_to entry '{entry}': {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+// A UDP socket on [::]:port that also receives IPv4, which arrives with
+// v4-mapped source addresses (::ffff:a.b.c.d).  IPV6_V6ONLY has to be cleared
+// before bind, which tokio cannot do, hence socket2.
+fn bind_dual_stack(port: u16) -> std::io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_only_v6(false)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    UdpSocket::from_std(socket.into())
+}
+
+// An IPv6 socket cannot send to an IPv4 address as such; it has to be given
+// the v4-mapped form.  Used for forward_to peers on a dual-stack listener.
+fn v4_mapped(addr: SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(v4) => {
+            SocketAddr::V6(SocketAddrV6::new(v4.ip().to_ipv6_mapped(), v4.port(), 0, 0))
+        }
+        v6 => v6,
+    }
 }
 
 // Maximum unfragmented UDP payload over Ethernet is 1472 bytes
@@ -23,8 +151,18 @@ pub struct WsjtxSettings {
 // 1500 comfortably exceeds that.  WSJT-X LoggedADIF packets are well under
 // 1000 bytes in practice, so this is ample.
 const SZ_RXBUF: usize = 1500;
+// Largest possible UDP payload over IPv4 (65535 - 20-byte IP - 8-byte UDP).
+const MAX_UDP_PAYLOAD: usize = 65507;
 const WSJTX_MAGIC: u32 = 0xadbccbda;
-const SZ_HDR: usize = 12; // bytes of initial header
+const WSJTX_SCHEMA: u32 = 2;
+// Bytes of initial header.
+const SZ_HDR: usize = 12;
+// Longest text WSJT-X accepts in its Tx message fields.
+const MAX_FREE_TEXT_CHARS: usize = 37;
+// WSJT-X sends a Status on every change and a Heartbeat every 15 s, so a
+// transmitting flag holds for as long as WSJT-X is heard from.  Three missed
+// heartbeats mean it has gone without a Close, and its last flag is stale.
+const STATUS_FRESH: Duration = Duration::from_secs(45);
 
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct WsjtxHeartbeat {
@@ -70,6 +208,52 @@ pub struct WsjtxLoggedAdif {
     adif_text: String,
 }
 
+// Close (type 6) is sent by WSJT-X as it exits.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxClose {
+    id: String,
+}
+
+// Replay (type 7) is sent *to* WSJT-X, which answers by sending again the
+// Decode messages for the decodes in its Band Activity window.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxReplay {
+    id: String,
+}
+
+// HaltTx (type 8) is sent *to* WSJT-X.  auto_tx_only = 0 stops transmission
+// immediately; 1 would only disable Auto Tx at the end of the current period.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxHaltTx {
+    id: String,
+    auto_tx_only: u8,
+}
+
+// FreeText (type 9) is sent *to* WSJT-X and replaces its Tx5 free text
+// message.  send = 1 also starts transmitting it at the next period.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxFreeText {
+    id: String,
+    text: String,
+    send: u8,
+}
+
+// SwitchConfiguration (type 14) is sent *to* WSJT-X and makes the named
+// configuration (as in its Configurations menu) the current one.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxSwitchConfiguration {
+    id: String,
+    configuration_name: String,
+}
+
+// Location (type 11) is sent *to* WSJT-X and sets the grid it reports as
+// the station's own, e.g. when roving.
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct WsjtxLocation {
+    id: String,
+    location: String,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub enum WsjtxMsg {
     Heartbeat(WsjtxHeartbeat),
@@ -78,15 +262,15 @@ pub enum WsjtxMsg {
     Clear,
     Reply,
     QSOLogged,
-    Close,
-    Replay,
-    HaltTx,
-    FreeText,
+    Close(WsjtxClose),
+    Replay(WsjtxReplay),
+    HaltTx(WsjtxHaltTx),
+    FreeText(WsjtxFreeText),
     WSPRDecode,
-    Location,
+    Location(WsjtxLocation),
     LoggedADIF(WsjtxLoggedAdif),
     HighlightCallsign,
-    SwitchConfiguration,
+    SwitchConfiguration(WsjtxSwitchConfiguration),
     Configure,
 }
 
@@ -161,20 +345,45 @@ impl Display for WsjtxMsg {
             WsjtxMsg::Clear => write!(f, "Clear"),
             WsjtxMsg::Reply => write!(f, "Reply"),
             WsjtxMsg::QSOLogged => write!(f, "QSO Logged"),
-            WsjtxMsg::Close => write!(f, "Close"),
-            WsjtxMsg::Replay => write!(f, "Replay"),
-            WsjtxMsg::HaltTx => write!(f, "Halt Tx"),
-            WsjtxMsg::FreeText => write!(f, "Free Text"),
+            WsjtxMsg::Close(msg) => write!(f, "Close id: {}", msg.id),
+            WsjtxMsg::Replay(msg) => write!(f, "Replay id: {}", msg.id),
+            WsjtxMsg::HaltTx(msg) => write!(f, "Halt Tx id: {}", msg.id),
+            WsjtxMsg::FreeText(msg) => write!(f, "Free Text id: {} text: {}", msg.id, msg.text),
             WsjtxMsg::WSPRDecode => write!(f, "WSPR Decode"),
-            WsjtxMsg::Location => write!(f, "Location"),
+            WsjtxMsg::Location(msg) => {
+                write!(f, "Location id: {} location: {}", msg.id, msg.location)
+            }
             WsjtxMsg::LoggedADIF(msg) => write!(f, "{}", msg),
             WsjtxMsg::HighlightCallsign => write!(f, "Highlight Callsign"),
-            WsjtxMsg::SwitchConfiguration => write!(f, "Switch Configuration"),
+            WsjtxMsg::SwitchConfiguration(msg) => write!(
+                f,
+                "Switch Configuration id: {} name: {}",
+                msg.id, msg.configuration_name
+            ),
             WsjtxMsg::Configure => write!(f, "Configure"),
         }
     }
 }
 
+impl WsjtxMsg {
+    // The id of the WSJT-X instance a message came from or is meant for.
+    fn id(&self) -> Option<&str> {
+        match self {
+            WsjtxMsg::Heartbeat(msg) => Some(&msg.id),
+            WsjtxMsg::Status(msg) => Some(&msg.id),
+            WsjtxMsg::Decode(msg) => Some(&msg.id),
+            WsjtxMsg::LoggedADIF(msg) => Some(&msg.id),
+            WsjtxMsg::Close(msg) => Some(&msg.id),
+            WsjtxMsg::Replay(msg) => Some(&msg.id),
+            WsjtxMsg::HaltTx(msg) => Some(&msg.id),
+            WsjtxMsg::FreeText(msg) => Some(&msg.id),
+            WsjtxMsg::Location(msg) => Some(&msg.id),
+            WsjtxMsg::SwitchConfiguration(msg) => Some(&msg.id),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug)]
 pub struct WsjtxData {
     magic: u32,
@@ -185,80 +394,84 @@ pub struct WsjtxData {
 #[derive(Debug)]
 pub enum WsjtxError {
     DatagramTooShort(String),
+    DatagramTruncated(String),
     DeserializationFailure(String),
     BadMajick(String),
     UnsupportedSchema(String),
     QSOUploadFailed(String),
+    InvalidCommand(String),
+    NotConnected(String),
+    SendFailed(String),
 }
 
 impl Display for WsjtxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             WsjtxError::DatagramTooShort(msg) => write!(f, "DatagramTooShort: {}", msg),
+            WsjtxError::DatagramTruncated(msg) => write!(f, "DatagramTruncated: {}", msg),
             WsjtxError::DeserializationFailure(msg) => write!(f, "DeserializationFailure: {}", msg),
             WsjtxError::BadMajick(msg) => write!(f, "BadMajick: {}", msg),
             WsjtxError::UnsupportedSchema(msg) => write!(f, "UnsupportedSchema: {}", msg),
             WsjtxError::QSOUploadFailed(msg) => write!(f, "QSOUploadFailed: {}", msg),
+            WsjtxError::InvalidCommand(msg) => write!(f, "InvalidCommand: {}", msg),
+            WsjtxError::NotConnected(msg) => write!(f, "NotConnected: {}", msg),
+            WsjtxError::SendFailed(msg) => write!(f, "SendFailed: {}", msg),
         }
     }
 }
 
 impl std::error::Error for WsjtxError {}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::wavelog::WavelogSettings;
-    use reqwest::Client;
-
-    fn dummy_settings() -> WavelogSettings {
-        WavelogSettings {
-            url: "http://localhost/api/radio".to_string(),
-            qso_url: "http://localhost/api/qso".to_string(),
-            key: "test".to_string(),
-            identifier: "test-rig".to_string(),
-            station_profile_id: 1,
-            interval: 1000,
-            cat_url: None,
-        }
-    }
-
-    // Serialise a WsjtxData packet using the same bincode2 config as the live code.
-    fn make_packet(magic: u32, schema: u32, msg: WsjtxMsg) -> Vec<u8> {
-        bincode2::config()
-            .big_endian()
-            .string_length(U32)
-            .array_length(U32)
-            .serialize(&WsjtxData { magic, schema, msg })
-            .unwrap()
-    }
-
-    #[tokio::test]
-    async fn decode_hdr_too_short() {
-        let result = decode_hdr(&Client::new(), dummy_settings(), &[0u8; 4]).await;
-        assert!(matches!(result, Err(WsjtxError::DatagramTooShort(_))));
-    }
+// Build the BadMajick message from the raw datagram.  The first four bytes
+// are shown in hex, and a magic that only matches when byte-swapped gets a
+// specific hint: that is a sender framing the header little-endian rather
+// than a stray packet from some other application.
+fn describe_bad_magic(buf: &[u8], accepted: &AcceptedHeader) -> String {
+    let head: [u8; 4] = [buf[0], buf[1], buf[2], buf[3]];
+    let hex = head
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ");
 
-    #[tokio::test]
-    async fn decode_hdr_bad_magic() {
-        let buf = make_packet(0xDEAD_BEEF, 2, WsjtxMsg::Clear);
-        let result = decode_hdr(&Client::new(), dummy_settings(), &buf).await;
-        assert!(matches!(result, Err(WsjtxError::BadMajick(_))));
+    if accepted.magics.contains(&u32::from_le_bytes(head)) {
+        format!(
+            "Bad majick: first bytes {hex} are the WSJT-X magic in little-endian order; \
+             the sender is not using WSJT-X's big-endian framing (protocol mismatch, \
+             not a stray packet)"
+        )
+    } else {
+        let expected = accepted
+            .magics
+            .iter()
+            .map(|m| format!("{m:08x}"))
+            .collect::<Vec<_>>()
+            .join(" or ");
+        format!(
+            "Bad majick: first bytes {hex} (expected {expected} big-endian); \
+             probably not a WSJT-X datagram"
+        )
     }
+}
 
-    #[tokio::test]
-    async fn decode_hdr_unsupported_schema() {
-        let buf = make_packet(WSJTX_MAGIC, 3, WsjtxMsg::Clear);
-        let result = decode_hdr(&Client::new(), dummy_settings(), &buf).await;
-        assert!(matches!(result, Err(WsjtxError::UnsupportedSchema(_))));
-    }
+// Frame a message for sending to WSJT-X, using the same schema 2 encoding
+// that incoming datagrams are decoded with.  Always WSJT-X's own magic and
+// schema, whatever [WSJTX] magic and schema accept.
+fn encode_msg(msg: WsjtxMsg) -> Vec<u8> {
+    bincode2::config()
+        .big_endian()
+        .string_length(U32)
+        .array_length(U32)
+        .serialize(&WsjtxData {
+            magic: WSJTX_MAGIC,
+            schema: WSJTX_SCHEMA,
+            msg,
+        })
+        .expect("WSJT-X message serialisation cannot fail")
 }
 
-pub async fn decode_hdr(
-    client: &Client,
-    wavelog_settings: WavelogSettings,
-    buf: &[u8],
-) -> Result<(), WsjtxError> {
+// Check the header of a received datagram and decode its message.
+fn parse_datagram(buf: &[u8], accepted: &AcceptedHeader) -> Result<WsjtxMsg, WsjtxError> {
     if buf.len() < SZ_HDR {
         let errmsg = "Datagram too short for WSJTX header".to_string();
         return Err(WsjtxError::DatagramTooShort(errmsg));
@@ -271,54 +484,496 @@ pub async fn decode_hdr(
         .deserialize::<WsjtxData>(buf)
     {
         Ok(wsjtx) => {
-            if wsjtx.magic != WSJTX_MAGIC {
-                let errmsg = format!("Bad majick: {}", wsjtx.magic);
-                return Err(WsjtxError::BadMajick(errmsg));
+            if !accepted.magics.contains(&wsjtx.magic) {
+                return Err(WsjtxError::BadMajick(describe_bad_magic(buf, accepted)));
             }
-            if wsjtx.schema != 2 {
-                let errmsg = format!("Schema: {}; only schema 2 so far", wsjtx.schema);
+            if !accepted.schemas.contains(&wsjtx.schema) {
+                let errmsg = format!(
+                    "Schema: {}; accepting only {:?} (see [WSJTX] schema)",
+                    wsjtx.schema, accepted.schemas
+                );
                 return Err(WsjtxError::UnsupportedSchema(errmsg));
             }
-            match wsjtx.msg {
-                WsjtxMsg::LoggedADIF(msg) => {
-                    match upload_wsjtx_qso_data(client, &wavelog_settings, msg.adif_text).await {
-                        Ok(_) => Ok(()),
-                        Err(_) => Err(WsjtxError::QSOUploadFailed("upload failure".to_string())),
-                    }
+            Ok(wsjtx.msg)
+        }
+        Err(_) => {
+            let errmsg = "Couldn't deserialize datagram into WSJTX header".to_string();
+            Err(WsjtxError::DeserializationFailure(errmsg))
+        }
+    }
+}
+
+// Where a logged QSO goes: always Wavelog, and first the local ADIF file
+// when one is configured.  A QSO Wavelog could not take for now goes to the
+// spool, when there is one, to be retried.  `poll` reaches the Wavelog
+// poll loop, which owns the live-radio state.
+struct QsoLogger {
+    client: Client,
+    wavelog_settings: WavelogSettings,
+    local_adif: Option<Arc<AdifLog>>,
+    spool: Option<Arc<QsoSpool>>,
+    poll: RefreshSender,
+    latency: SharedLatency,
+}
+
+impl QsoLogger {
+    // Tell the poll loop the frequency of a just-logged QSO, which it may
+    // not have caught if the rig moved between polls.  It decides whether
+    // that is news to Wavelog's live view: not if it has read FLRig since.
+    fn update_live(&self, adif_text: &str, logged_at: Instant) {
+        let Some(frequency) = qso_frequency(adif_text) else {
+            debug!("Logged QSO has no usable FREQ; live data not updated");
+            return;
+        };
+        // A full queue means the poll loop is busy reading the rig anyway.
+        let request = PollRequest::QsoLogged {
+            frequency,
+            logged_at,
+        };
+        if self.poll.try_send(request).is_err() {
+            debug!("Poll loop busy; live data not updated for logged QSO");
+        }
+    }
+}
+
+async fn handle_msg(logger: &QsoLogger, msg: WsjtxMsg) -> Result<(), WsjtxError> {
+    match msg {
+        WsjtxMsg::LoggedADIF(msg) => {
+            // Written whether or not the upload succeeds: it is the local record.
+            if let Some(log) = &logger.local_adif {
+                if let Err(e) = log.append(&msg.adif_text).await {
+                    error!("Failed to write QSO to the local ADIF file: {e}");
                 }
-                msg => {
-                    debug!("{}", msg);
+            }
+            let logged_at = Instant::now();
+            match upload_wsjtx_qso_data(
+                &logger.client,
+                &logger.wavelog_settings,
+                msg.adif_text.clone(),
+                &logger.latency,
+            )
+            .await
+            {
+                Ok(_) => {
+                    logger.update_live(&msg.adif_text, logged_at);
                     Ok(())
                 }
+                Err(e) => match &logger.spool {
+                    Some(spool) if e.is_retryable() => match spool.push(&msg.adif_text).await {
+                        Ok(()) => Err(WsjtxError::QSOUploadFailed(format!(
+                            "{e}; kept in the QSO spool for retry"
+                        ))),
+                        Err(spool_err) => Err(WsjtxError::QSOUploadFailed(format!(
+                            "{e}; could not spool it either: {spool_err}"
+                        ))),
+                    },
+                    _ => Err(WsjtxError::QSOUploadFailed(e.to_string())),
+                },
             }
         }
-        Err(_) => {
-            let errmsg = "Couldn't deserialize datagram into WSJTX header".to_string();
-            Err(WsjtxError::DeserializationFailure(errmsg))
+        msg => {
+            debug!("{}", msg);
+            Ok(())
+        }
+    }
+}
+
+// recv_from silently discards whatever does not fit in the buffer, so a
+// datagram that fills it exactly was almost certainly longer than that.
+// Reject it here with a clear message rather than letting bincode fail on
+// the cut-off record with a confusing deserialisation error.
+fn check_datagram_len(amt: usize, bufsize: usize) -> Result<(), WsjtxError> {
+    if amt >= bufsize {
+        let errmsg = format!(
+            "received {amt} bytes, filling the {bufsize}-byte receive buffer; \
+             datagram probably truncated and has been dropped"
+        );
+        return Err(WsjtxError::DatagramTruncated(errmsg));
+    }
+    Ok(())
+}
+
+// Receive buffer size from the rxbuf_bytes setting.  Values that could not
+// hold a header, or that exceed the UDP payload limit, are reported and
+// clamped rather than failing the whole listener.
+fn rxbuf_size(configured: Option<usize>) -> usize {
+    let Some(size) = configured else {
+        return SZ_RXBUF;
+    };
+    let clamped = size.clamp(SZ_HDR + 1, MAX_UDP_PAYLOAD);
+    if clamped != size {
+        warn!(
+            "WSJT-X rxbuf_bytes = {size} is outside {}..={MAX_UDP_PAYLOAD}, using {clamped}",
+            SZ_HDR + 1
+        );
+    }
+    clamped
+}
+
+// Tracks how long each WSJT-X instance (by id) has been transmitting
+// without a break, across consecutive Status messages.
+struct TxWatchdog {
+    max_tx: Duration,
+    tx_since: HashMap<String, Instant>,
+}
+
+impl TxWatchdog {
+    fn new(max_tx: Duration) -> TxWatchdog {
+        TxWatchdog {
+            max_tx,
+            tx_since: HashMap::new(),
+        }
+    }
+
+    // Feed one Status; returns true when `id` has now been transmitting for
+    // at least max_tx and should be halted.  The timer restarts when it
+    // fires, so if WSJT-X ignores the HaltTx it is sent again after another
+    // max_tx rather than on every subsequent Status.
+    fn observe(&mut self, id: &str, transmitting: bool, now: Instant) -> bool {
+        if !transmitting {
+            self.tx_since.remove(id);
+            return false;
+        }
+        let since = *self.tx_since.entry(id.to_string()).or_insert(now);
+        if now.duration_since(since) >= self.max_tx {
+            self.tx_since.insert(id.to_string(), now);
+            return true;
+        }
+        false
+    }
+
+    // Drop any transmit timer for an instance that has gone away.
+    fn forget(&mut self, id: &str) {
+        self.tx_since.remove(id);
+    }
+}
+
+/// Route for sending commands to the WSJT-X instances the listener has heard
+/// from.  Cloned into the CAT server so HTTP requests can drive WSJT-X.
+/// Commands go out from the listening socket to the address each instance
+/// last sent from, which is where WSJT-X expects replies.
+#[derive(Clone)]
+pub struct WsjtxLink {
+    inner: Arc<Mutex<LinkState>>,
+    // Every Status received, for the CAT /ws stream.
+    statuses: broadcast::Sender<WsjtxStatusUpdate>,
+}
+
+impl Default for WsjtxLink {
+    fn default() -> WsjtxLink {
+        WsjtxLink {
+            inner: Arc::default(),
+            statuses: broadcast::channel(16).0,
+        }
+    }
+}
+
+/// The parts of a WSJT-X Status worth showing on a dashboard.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WsjtxStatusUpdate {
+    pub id: String,
+    pub dial_frequency: u64,
+    pub mode: String,
+    pub tx_mode: String,
+    pub dx_call: String,
+    pub report: String,
+    pub tx_enabled: bool,
+    pub transmitting: bool,
+    pub decoding: bool,
+}
+
+impl From<&WsjtxStatus> for WsjtxStatusUpdate {
+    fn from(status: &WsjtxStatus) -> WsjtxStatusUpdate {
+        WsjtxStatusUpdate {
+            id: status.id.clone(),
+            dial_frequency: status.dial_frequency_hz,
+            mode: status.mode.clone(),
+            tx_mode: status.tx_mode.clone(),
+            dx_call: status.dx_call.clone(),
+            report: status.report.clone(),
+            tx_enabled: status.tx_enabled != 0,
+            transmitting: status.transmitting != 0,
+            decoding: status.decoding != 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct LinkState {
+    socket: Option<Arc<UdpSocket>>,
+    instances: HashMap<String, SocketAddr>,
+    // The transmitting flag of each instance's latest Status, and when it
+    // arrived.
+    transmitting: HashMap<String, (Instant, bool)>,
+    // When the last datagram that parsed arrived, from any instance.
+    last_received: Option<Instant>,
+}
+
+impl WsjtxLink {
+    fn attach(&self, socket: Arc<UdpSocket>) {
+        self.inner.lock().unwrap().socket = Some(socket);
+    }
+
+    fn heard(&self, id: &str, src: SocketAddr) {
+        let mut state = self.inner.lock().unwrap();
+        if state.instances.get(id) != Some(&src) {
+            info!("WSJT-X instance '{id}' at {src}");
+            state.instances.insert(id.to_string(), src);
+        }
+    }
+
+    // Forget an instance that has sent Close, so nothing more is sent to it.
+    fn closed(&self, id: &str) {
+        let mut state = self.inner.lock().unwrap();
+        state.instances.remove(id);
+        state.transmitting.remove(id);
+    }
+
+    fn received(&self) {
+        self.inner.lock().unwrap().last_received = Some(Instant::now());
+    }
+
+    /// When the listener last received a WSJT-X datagram, or None if it has
+    /// not received one since starting.
+    pub fn last_received(&self) -> Option<Instant> {
+        self.inner.lock().unwrap().last_received
+    }
+
+    fn status_seen(&self, id: &str, transmitting: bool) {
+        self.inner
+            .lock()
+            .unwrap()
+            .transmitting
+            .insert(id.to_string(), (Instant::now(), transmitting));
+    }
+
+    // Pass a Status on to /ws subscribers; with none, it is dropped.
+    pub(crate) fn publish_status(&self, status: WsjtxStatusUpdate) {
+        let _ = self.statuses.send(status);
+    }
+
+    /// Every Status received from now on, from any instance.
+    pub fn subscribe_status(&self) -> broadcast::Receiver<WsjtxStatusUpdate> {
+        self.statuses.subscribe()
+    }
+
+    /// The transmitting flag of the most recent Status from any open WSJT-X
+    /// instance, or None if none has sent one or WSJT-X has gone quiet.
+    pub fn transmitting(&self) -> Option<bool> {
+        self.transmitting_at(Instant::now())
+    }
+
+    fn transmitting_at(&self, now: Instant) -> Option<bool> {
+        let state = self.inner.lock().unwrap();
+        let heard = state.last_received?;
+        if now.saturating_duration_since(heard) >= STATUS_FRESH {
+            return None;
+        }
+        state
+            .transmitting
+            .values()
+            .max_by_key(|(at, _)| *at)
+            .map(|&(_, transmitting)| transmitting)
+    }
+
+    // Send the message built for each known instance id to that instance.
+    // Returns how many instances it was sent to.
+    async fn send_to_all(&self, build: impl Fn(String) -> WsjtxMsg) -> Result<usize, WsjtxError> {
+        let (socket, instances) = {
+            let state = self.inner.lock().unwrap();
+            (state.socket.clone(), state.instances.clone())
+        };
+        let socket = socket
+            .ok_or_else(|| WsjtxError::NotConnected("WSJT-X listener is not running".into()))?;
+        if instances.is_empty() {
+            return Err(WsjtxError::NotConnected(
+                "no WSJT-X instance heard from yet".into(),
+            ));
+        }
+        for (id, addr) in &instances {
+            let packet = encode_msg(build(id.clone()));
+            socket
+                .send_to(&packet, addr)
+                .await
+                .map_err(|e| WsjtxError::SendFailed(format!("to '{id}' at {addr}: {e}")))?;
+        }
+        Ok(instances.len())
+    }
+
+    /// Ask every known WSJT-X instance to send its current decodes again.
+    /// Returns how many instances were asked.
+    pub async fn send_replay(&self) -> Result<usize, WsjtxError> {
+        self.send_to_all(|id| WsjtxMsg::Replay(WsjtxReplay { id }))
+            .await
+    }
+
+    /// Set the free text message of every known WSJT-X instance, and with
+    /// `send` also transmit it.  Returns how many instances were sent to.
+    pub async fn send_free_text(&self, text: &str, send: bool) -> Result<usize, WsjtxError> {
+        let len = text.chars().count();
+        if len > MAX_FREE_TEXT_CHARS {
+            return Err(WsjtxError::InvalidCommand(format!(
+                "free text is {len} characters; WSJT-X allows at most {MAX_FREE_TEXT_CHARS}"
+            )));
+        }
+        self.send_to_all(|id| {
+            WsjtxMsg::FreeText(WsjtxFreeText {
+                id,
+                text: text.to_string(),
+                send: send as u8,
+            })
+        })
+        .await
+    }
+
+    /// Make the named configuration the current one in every known WSJT-X
+    /// instance.  Returns how many instances were sent to.
+    pub async fn send_switch_configuration(&self, name: &str) -> Result<usize, WsjtxError> {
+        if name.trim().is_empty() {
+            return Err(WsjtxError::InvalidCommand(
+                "configuration name must not be empty".into(),
+            ));
         }
+        self.send_to_all(|id| {
+            WsjtxMsg::SwitchConfiguration(WsjtxSwitchConfiguration {
+                id,
+                configuration_name: name.to_string(),
+            })
+        })
+        .await
+    }
+
+    /// Set the station's grid in every known WSJT-X instance.  Returns how
+    /// many instances were sent to.
+    pub async fn send_location(&self, grid: &str) -> Result<usize, WsjtxError> {
+        if !is_maidenhead(grid) {
+            return Err(WsjtxError::InvalidCommand(format!(
+                "'{grid}' is not a Maidenhead locator, e.g. IO91 or IO91wm"
+            )));
+        }
+        self.send_to_all(|id| {
+            WsjtxMsg::Location(WsjtxLocation {
+                id,
+                location: grid.to_string(),
+            })
+        })
+        .await
     }
 }
 
+// A 4, 6 or 8 character Maidenhead locator: field letters A-R, square
+// digits, subsquare letters A-X, extended square digits.  Either case.
+fn is_maidenhead(grid: &str) -> bool {
+    let bytes = grid.as_bytes();
+    if !matches!(bytes.len(), 4 | 6 | 8) {
+        return false;
+    }
+    bytes.iter().enumerate().all(|(i, b)| match i {
+        0 | 1 => (b'A'..=b'R').contains(&b.to_ascii_uppercase()),
+        4 | 5 => (b'A'..=b'X').contains(&b.to_ascii_uppercase()),
+        _ => b.is_ascii_digit(),
+    })
+}
+
 async fn rxhandler(
-    client: &Client,
-    wavelog_settings: WavelogSettings,
-    rxdata: &[u8],
-    _src: SocketAddr,
+    logger: &QsoLogger,
+    msg: WsjtxMsg,
+    socket: &UdpSocket,
+    src: SocketAddr,
+    watchdog: Option<&mut TxWatchdog>,
+    link: &WsjtxLink,
 ) {
-    match decode_hdr(client, wavelog_settings, rxdata).await {
-        Ok(_) => (),
-        Err(e) => error!("{}", e),
+    link.received();
+
+    let mut watchdog = watchdog;
+    match (&msg, msg.id()) {
+        (WsjtxMsg::Close(close), _) => {
+            info!("WSJT-X instance '{}' at {src} has closed", close.id);
+            link.closed(&close.id);
+            if let Some(watchdog) = watchdog.as_deref_mut() {
+                watchdog.forget(&close.id);
+            }
+        }
+        (_, Some(id)) => link.heard(id, src),
+        (_, None) => {}
+    }
+
+    if let WsjtxMsg::Status(status) = &msg {
+        link.status_seen(&status.id, status.transmitting != 0);
+        link.publish_status(status.into());
+    }
+
+    if let (WsjtxMsg::Status(status), Some(watchdog)) = (&msg, watchdog) {
+        if watchdog.observe(&status.id, status.transmitting != 0, Instant::now()) {
+            error!(
+                "WSJT-X '{}' has been transmitting for over {}s; sending HaltTx",
+                status.id,
+                watchdog.max_tx.as_secs()
+            );
+            let halt = encode_msg(WsjtxMsg::HaltTx(WsjtxHaltTx {
+                id: status.id.clone(),
+                auto_tx_only: 0,
+            }));
+            if let Err(e) = socket.send_to(&halt, src).await {
+                error!("Failed to send HaltTx to WSJT-X at {src}: {e}");
+            }
+        }
+    }
+
+    if let Err(e) = handle_msg(logger, msg).await {
+        error!("{}", e);
     }
 }
 
+// Re-send a datagram unchanged to every forward_to peer.  A failure to reach
+// one peer is logged, with repeats held back as a down peer fails on every
+// datagram, and never prevents local processing of the datagram.
+async fn forward_datagram(
+    socket: &UdpSocket,
+    peers: &[SocketAddr],
+    data: &[u8],
+    errors: &mut RepeatedErrors,
+) {
+    for peer in peers {
+        if let Err(e) = socket.send_to(data, peer).await {
+            let msg = format!("Failed to forward WSJT-X datagram to {peer}: {e}");
+            errors.log(log::Level::Warn, &msg);
+        }
+    }
+}
+
+// Listener options resolved from WsjtxSettings at startup.
+#[derive(Clone)]
+struct RxOptions {
+    err_timeout: u64,
+    forward_to: Vec<SocketAddr>,
+    rxbuf_bytes: usize,
+    max_tx: Option<Duration>,
+    local_adif: Option<Arc<AdifLog>>,
+    spool: Option<Arc<QsoSpool>>,
+    poll: RefreshSender,
+    latency: SharedLatency,
+    accepted: AcceptedHeader,
+}
+
 async fn wsjtx_rxloop(
     wavelog_settings: WavelogSettings,
-    socket: UdpSocket,
-    err_timeout: u64,
+    socket: Arc<UdpSocket>,
+    link: WsjtxLink,
+    opts: RxOptions,
     token: CancellationToken,
 ) {
-    let client = Client::new();
-    let mut buf = vec![0u8; SZ_RXBUF];
+    let logger = QsoLogger {
+        client: Client::new(),
+        wavelog_settings,
+        local_adif: opts.local_adif,
+        spool: opts.spool,
+        poll: opts.poll,
+        latency: opts.latency,
+    };
+    let mut watchdog = opts.max_tx.map(TxWatchdog::new);
+    let mut buf = vec![0u8; opts.rxbuf_bytes];
+    let mut errors = RepeatedErrors::default();
     loop {
         tokio::select! {
             _ = token.cancelled() => {
@@ -327,12 +982,27 @@ async fn wsjtx_rxloop(
             }
             result = socket.recv_from(&mut buf) => {
                 match result {
-                    Ok((amt, src)) => rxhandler(&client, wavelog_settings.clone(), &buf[0..amt], src).await,
+                    Ok((amt, src)) => {
+                        if let Err(e) = check_datagram_len(amt, buf.len()) {
+                            errors.log(log::Level::Error, &e.to_string());
+                            continue;
+                        }
+                        let datagram = &buf[0..amt];
+                        forward_datagram(&socket, &opts.forward_to, datagram, &mut errors)
+                            .await;
+                        match parse_datagram(datagram, &opts.accepted) {
+                            Ok(msg) => {
+                                rxhandler(&logger, msg, &socket, src, watchdog.as_mut(), &link)
+                                    .await
+                            }
+                            Err(e) => errors.log(log::Level::Error, &e.to_string()),
+                        }
+                    }
                     Err(e) => {
-                        error!("UDP receive error: {}", e);
+                        errors.log(log::Level::Error, &format!("UDP receive error: {e}"));
                         tokio::select! {
                             _ = token.cancelled() => return,
-                            _ = tokio::time::sleep(Duration::from_secs(err_timeout)) => {}
+                            _ = tokio::time::sleep(Duration::from_secs(opts.err_timeout)) => {}
                         }
                     }
                 }
@@ -344,16 +1014,711 @@ async fn wsjtx_rxloop(
 pub fn wsjtx_thread(
     wsjtx_settings: WsjtxSettings,
     wavelog_settings: WavelogSettings,
+    link: WsjtxLink,
+    poll: RefreshSender,
+    latency: SharedLatency,
+    spool: Option<Arc<QsoSpool>>,
     token: CancellationToken,
-) {
-    let url = format!("{0}:{1}", wsjtx_settings.host, wsjtx_settings.port);
+) -> JoinHandle<()> {
+    let dual_stack = wsjtx_settings.dual_stack.unwrap_or(false);
+    let url = if dual_stack {
+        format!("[::]:{} (dual-stack)", wsjtx_settings.port)
+    } else {
+        format!("{0}:{1}", wsjtx_settings.host, wsjtx_settings.port)
+    };
     info!("Listening for WSJT-X QSO logs on: {url}");
+    let mut forward_to = parse_forward_peers(wsjtx_settings.forward_to.as_deref().unwrap_or(&[]));
+    if dual_stack {
+        forward_to = forward_to.into_iter().map(v4_mapped).collect();
+    }
+    for peer in &forward_to {
+        info!("Forwarding WSJT-X datagrams to: {peer}");
+    }
+    let rxbuf_bytes = rxbuf_size(wsjtx_settings.rxbuf_bytes);
+    // Checked by WsjtxSettings::validate when the config was loaded.
+    let accepted = AcceptedHeader::new(&wsjtx_settings).unwrap_or_else(|e| {
+        error!("{e}; accepting only WSJT-X's own header");
+        AcceptedHeader::default()
+    });
+    let max_tx = wsjtx_settings.max_tx_secs.map(Duration::from_secs);
+    if let Some(max_tx) = max_tx {
+        info!(
+            "WSJT-X transmit watchdog: HaltTx after {}s",
+            max_tx.as_secs()
+        );
+    }
+    // Opened here, outside the supervised loop, so a restart reuses it.
+    let local_adif =
+        wavelog_settings
+            .local_adif
+            .as_ref()
+            .and_then(|path| match AdifLog::open(path) {
+                Ok(log) => {
+                    info!("Logging WSJT-X QSOs locally to: {path}");
+                    Some(Arc::new(log))
+                }
+                Err(e) => {
+                    error!("Could not open local ADIF file {path}: {e}");
+                    None
+                }
+            });
     tokio::task::spawn(async move {
-        match UdpSocket::bind(&url).await {
+        let bound = if dual_stack {
+            bind_dual_stack(wsjtx_settings.port)
+        } else {
+            UdpSocket::bind(&url).await
+        };
+        match bound {
             Err(e) => error!("couldn't create socket for WSJTX QSO logging: {e}"),
             Ok(socket) => {
-                wsjtx_rxloop(wavelog_settings, socket, wsjtx_settings.err_timeout, token).await
+                let socket = Arc::new(socket);
+                link.attach(socket.clone());
+                let opts = RxOptions {
+                    err_timeout: wsjtx_settings.err_timeout,
+                    forward_to,
+                    rxbuf_bytes,
+                    max_tx,
+                    local_adif,
+                    spool,
+                    poll,
+                    latency,
+                    accepted,
+                };
+                // A panic restarts the loop on the same socket.
+                let supervisor = supervise("WSJT-X listener", token.clone(), move || {
+                    wsjtx_rxloop(
+                        wavelog_settings.clone(),
+                        socket.clone(),
+                        link.clone(),
+                        opts.clone(),
+                        token.clone(),
+                    )
+                });
+                let _ = supervisor.await;
             }
         }
-    });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wavelog::WavelogSettings;
+    use reqwest::Client;
+
+    fn dummy_settings() -> WavelogSettings {
+        WavelogSettings {
+            url: "http://localhost/api/radio".to_string(),
+            qso_url: "http://localhost/api/qso".to_string(),
+            key: "test".to_string(),
+            identifier: "test-rig".to_string(),
+            station_profile_id: 1,
+            interval: 1000,
+            cat_url: None,
+            my_grid: None,
+            jitter: None,
+            min_upload_interval_ms: None,
+            keepalive_secs: None,
+            compress: None,
+            adif_inject: None,
+            mode_remap: None,
+            local_adif: None,
+            qso_spool: None,
+            radio_method: None,
+            qso_method: None,
+            base_url: None,
+        }
+    }
+
+    // Parse and handle a datagram in one step, as rxhandler does.
+    async fn decode_hdr(
+        client: &Client,
+        wavelog_settings: WavelogSettings,
+        buf: &[u8],
+    ) -> Result<(), WsjtxError> {
+        let logger = QsoLogger {
+            client: client.clone(),
+            wavelog_settings,
+            local_adif: None,
+            spool: None,
+            poll: tokio::sync::mpsc::channel(1).0,
+            latency: SharedLatency::default(),
+        };
+        handle_msg(&logger, parse_datagram(buf, &AcceptedHeader::default())?).await
+    }
+
+    // Serialise a WsjtxData packet using the same bincode2 config as the live code.
+    fn make_packet(magic: u32, schema: u32, msg: WsjtxMsg) -> Vec<u8> {
+        bincode2::config()
+            .big_endian()
+            .string_length(U32)
+            .array_length(U32)
+            .serialize(&WsjtxData { magic, schema, msg })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn decode_hdr_too_short() {
+        let result = decode_hdr(&Client::new(), dummy_settings(), &[0u8; 4]).await;
+        assert!(matches!(result, Err(WsjtxError::DatagramTooShort(_))));
+    }
+
+    #[tokio::test]
+    async fn decode_hdr_bad_magic() {
+        let buf = make_packet(0xDEAD_BEEF, 2, WsjtxMsg::Clear);
+        let result = decode_hdr(&Client::new(), dummy_settings(), &buf).await;
+        assert!(matches!(result, Err(WsjtxError::BadMajick(_))));
+    }
+
+    #[tokio::test]
+    async fn decode_hdr_byte_swapped_magic_gets_hint() {
+        let mut buf = make_packet(WSJTX_MAGIC, 2, WsjtxMsg::Clear);
+        buf[0..4].copy_from_slice(&WSJTX_MAGIC.to_le_bytes());
+        let result = decode_hdr(&Client::new(), dummy_settings(), &buf).await;
+        let Err(WsjtxError::BadMajick(msg)) = result else {
+            panic!("expected BadMajick, got {result:?}");
+        };
+        assert!(msg.contains("da cb bc ad"), "{msg}");
+        assert!(msg.contains("little-endian"), "{msg}");
+    }
+
+    #[test]
+    fn bad_magic_stray_packet_shows_hex_without_endian_hint() {
+        let buf = make_packet(0xDEAD_BEEF, 2, WsjtxMsg::Clear);
+        let msg = describe_bad_magic(&buf, &AcceptedHeader::default());
+        assert!(msg.contains("de ad be ef"), "{msg}");
+        assert!(!msg.contains("little-endian"), "{msg}");
+    }
+
+    #[test]
+    fn forward_peers_skip_invalid_entries() {
+        let peers = parse_forward_peers(&[
+            "127.0.0.1:2238".to_string(),
+            "not-an-address".to_string(),
+            "[::1]:2239".to_string(),
+        ]);
+        assert_eq!(
+            peers,
+            vec![
+                "127.0.0.1:2238".parse::<SocketAddr>().unwrap(),
+                "[::1]:2239".parse::<SocketAddr>().unwrap(),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn received_datagram_is_forwarded_verbatim() {
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listener = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let listen_addr = listener.local_addr().unwrap();
+        let token = CancellationToken::new();
+        tokio::spawn(wsjtx_rxloop(
+            dummy_settings(),
+            Arc::new(listener),
+            WsjtxLink::default(),
+            RxOptions {
+                err_timeout: 1,
+                forward_to: vec![peer.local_addr().unwrap()],
+                rxbuf_bytes: SZ_RXBUF,
+                max_tx: None,
+                local_adif: None,
+                spool: None,
+                poll: tokio::sync::mpsc::channel(1).0,
+                latency: SharedLatency::default(),
+                accepted: AcceptedHeader::default(),
+            },
+            token.clone(),
+        ));
+
+        let packet = make_packet(WSJTX_MAGIC, 2, WsjtxMsg::Clear);
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        sender.send_to(&packet, listen_addr).await.unwrap();
+
+        let mut buf = [0u8; SZ_RXBUF];
+        let (amt, _) = tokio::time::timeout(Duration::from_secs(2), peer.recv_from(&mut buf))
+            .await
+            .expect("forwarded datagram not received")
+            .unwrap();
+        assert_eq!(&buf[..amt], packet.as_slice());
+        token.cancel();
+    }
+
+    #[tokio::test]
+    async fn dual_stack_socket_receives_both_families() {
+        let rx = bind_dual_stack(0).unwrap();
+        let port = rx.local_addr().unwrap().port();
+
+        let v4 = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        v4.send_to(b"v4", ("127.0.0.1", port)).await.unwrap();
+        let mut buf = [0u8; 16];
+        let (amt, src) = rx.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..amt], b"v4");
+        assert_eq!(src, v4_mapped(v4.local_addr().unwrap()));
+
+        // Hosts without IPv6 loopback can still run the IPv4 half.
+        let Ok(v6) = UdpSocket::bind("[::1]:0").await else {
+            return;
+        };
+        v6.send_to(b"v6", ("::1", port)).await.unwrap();
+        let (amt, _) = rx.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..amt], b"v6");
+
+        // Replies and forwards to an IPv4 peer go out in v4-mapped form.
+        rx.send_to(b"back", v4_mapped(v4.local_addr().unwrap()))
+            .await
+            .unwrap();
+        let (amt, _) = v4.recv_from(&mut buf).await.unwrap();
+        assert_eq!(&buf[..amt], b"back");
+    }
+
+    #[tokio::test]
+    async fn oversized_datagram_is_flagged_as_truncated() {
+        let rx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let oversized = vec![0xAAu8; SZ_RXBUF + 500];
+        tx.send_to(&oversized, rx.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let mut buf = vec![0u8; SZ_RXBUF];
+        let (amt, _) = rx.recv_from(&mut buf).await.unwrap();
+        assert!(matches!(
+            check_datagram_len(amt, buf.len()),
+            Err(WsjtxError::DatagramTruncated(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_datagram_fits_enlarged_buffer() {
+        let rx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let tx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let large = vec![0xAAu8; SZ_RXBUF + 500];
+        tx.send_to(&large, rx.local_addr().unwrap()).await.unwrap();
+
+        let mut buf = vec![0u8; rxbuf_size(Some(8192))];
+        let (amt, _) = rx.recv_from(&mut buf).await.unwrap();
+        assert_eq!(amt, large.len());
+        assert!(check_datagram_len(amt, buf.len()).is_ok());
+    }
+
+    #[test]
+    fn tx_watchdog_fires_after_continuous_transmit() {
+        let mut wd = TxWatchdog::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        // Status messages every 15s while transmitting.
+        for secs in [0, 15, 30, 45] {
+            assert!(!wd.observe("WSJT-X", true, t0 + Duration::from_secs(secs)));
+        }
+        assert!(wd.observe("WSJT-X", true, t0 + Duration::from_secs(60)));
+        // Timer restarted: no repeat on the very next Status.
+        assert!(!wd.observe("WSJT-X", true, t0 + Duration::from_secs(75)));
+    }
+
+    #[test]
+    fn tx_watchdog_resets_when_transmission_stops() {
+        let mut wd = TxWatchdog::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        assert!(!wd.observe("WSJT-X", true, t0));
+        assert!(!wd.observe("WSJT-X", false, t0 + Duration::from_secs(50)));
+        assert!(!wd.observe("WSJT-X", true, t0 + Duration::from_secs(55)));
+        assert!(!wd.observe("WSJT-X", true, t0 + Duration::from_secs(100)));
+        assert!(wd.observe("WSJT-X", true, t0 + Duration::from_secs(115)));
+    }
+
+    #[test]
+    fn tx_watchdog_tracks_instances_separately() {
+        let mut wd = TxWatchdog::new(Duration::from_secs(60));
+        let t0 = Instant::now();
+        assert!(!wd.observe("A", true, t0));
+        assert!(!wd.observe("B", true, t0 + Duration::from_secs(30)));
+        assert!(wd.observe("A", true, t0 + Duration::from_secs(60)));
+        assert!(!wd.observe("B", true, t0 + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn halt_tx_encodes_as_message_type_8() {
+        let packet = encode_msg(WsjtxMsg::HaltTx(WsjtxHaltTx {
+            id: "WSJT-X".to_string(),
+            auto_tx_only: 0,
+        }));
+        assert_eq!(&packet[0..4], &WSJTX_MAGIC.to_be_bytes());
+        assert_eq!(&packet[4..8], &2u32.to_be_bytes());
+        assert_eq!(&packet[8..12], &8u32.to_be_bytes());
+        assert!(matches!(
+            parse_datagram(&packet, &AcceptedHeader::default()),
+            Ok(WsjtxMsg::HaltTx(WsjtxHaltTx {
+                auto_tx_only: 0,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn replay_round_trips_as_message_type_7() {
+        let replay = || {
+            WsjtxMsg::Replay(WsjtxReplay {
+                id: "WSJT-X".to_string(),
+            })
+        };
+        let packet = encode_msg(replay());
+        assert_eq!(&packet[0..4], &WSJTX_MAGIC.to_be_bytes());
+        assert_eq!(&packet[8..12], &7u32.to_be_bytes());
+        // Nothing after the id: a u32 length then its UTF-8 bytes.
+        assert_eq!(&packet[12..16], &6u32.to_be_bytes());
+        assert_eq!(packet.len(), 16 + 6);
+        assert_eq!(
+            parse_datagram(&packet, &AcceptedHeader::default()).unwrap(),
+            replay()
+        );
+    }
+
+    #[test]
+    fn free_text_round_trips_as_message_type_9() {
+        let free_text = || {
+            WsjtxMsg::FreeText(WsjtxFreeText {
+                id: "WSJT-X".to_string(),
+                text: "CQ M7CLG IO91".to_string(),
+                send: 1,
+            })
+        };
+        let packet = encode_msg(free_text());
+        assert_eq!(&packet[8..12], &9u32.to_be_bytes());
+        assert_eq!(
+            parse_datagram(&packet, &AcceptedHeader::default()).unwrap(),
+            free_text()
+        );
+    }
+
+    #[test]
+    fn switch_configuration_round_trips_as_message_type_14() {
+        let switch = || {
+            WsjtxMsg::SwitchConfiguration(WsjtxSwitchConfiguration {
+                id: "WSJT-X".to_string(),
+                configuration_name: "FT4".to_string(),
+            })
+        };
+        let packet = encode_msg(switch());
+        assert_eq!(&packet[8..12], &14u32.to_be_bytes());
+        // The id, then the name, each a u32 length and UTF-8 bytes.
+        assert_eq!(&packet[22..26], &3u32.to_be_bytes());
+        assert_eq!(&packet[26..], b"FT4");
+        assert_eq!(
+            parse_datagram(&packet, &AcceptedHeader::default()).unwrap(),
+            switch()
+        );
+    }
+
+    #[test]
+    fn location_round_trips_as_message_type_11() {
+        let location = || {
+            WsjtxMsg::Location(WsjtxLocation {
+                id: "WSJT-X".to_string(),
+                location: "IO91wm".to_string(),
+            })
+        };
+        let packet = encode_msg(location());
+        assert_eq!(&packet[8..12], &11u32.to_be_bytes());
+        assert_eq!(&packet[22..26], &6u32.to_be_bytes());
+        assert_eq!(&packet[26..], b"IO91wm");
+        assert_eq!(
+            parse_datagram(&packet, &AcceptedHeader::default()).unwrap(),
+            location()
+        );
+    }
+
+    #[test]
+    fn maidenhead_locators() {
+        for grid in ["IO91", "io91wm", "IO91WM", "RR99xx", "JO01ab12"] {
+            assert!(is_maidenhead(grid), "{grid}");
+        }
+        for grid in [
+            "", "IO9", "IO91w", "SO91", "IO91yz", "IO9A", "IO91wmab", "IO91wm1",
+        ] {
+            assert!(!is_maidenhead(grid), "{grid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn location_must_be_a_locator() {
+        let link = WsjtxLink::default();
+        assert!(matches!(
+            link.send_location("London").await,
+            Err(WsjtxError::InvalidCommand(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn switch_configuration_needs_a_name() {
+        let link = WsjtxLink::default();
+        assert!(matches!(
+            link.send_switch_configuration(" ").await,
+            Err(WsjtxError::InvalidCommand(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn free_text_is_sent_to_heard_instance() {
+        let wsjtx = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let link = WsjtxLink::default();
+        link.attach(Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+        link.heard("WSJT-X", wsjtx.local_addr().unwrap());
+
+        assert_eq!(link.send_free_text("QRT 73", false).await.unwrap(), 1);
+
+        let mut buf = [0u8; SZ_RXBUF];
+        let (amt, _) = tokio::time::timeout(Duration::from_secs(2), wsjtx.recv_from(&mut buf))
+            .await
+            .expect("FreeText not received")
+            .unwrap();
+        assert_eq!(
+            parse_datagram(&buf[..amt], &AcceptedHeader::default()).unwrap(),
+            WsjtxMsg::FreeText(WsjtxFreeText {
+                id: "WSJT-X".to_string(),
+                text: "QRT 73".to_string(),
+                send: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn latest_status_gives_transmitting() {
+        let link = WsjtxLink::default();
+        assert_eq!(link.transmitting(), None);
+        link.received();
+        link.status_seen("WSJT-X", false);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        link.status_seen("WSJT-X - Rig2", true);
+        assert_eq!(link.transmitting(), Some(true));
+        link.closed("WSJT-X - Rig2");
+        assert_eq!(link.transmitting(), Some(false));
+        link.closed("WSJT-X");
+        assert_eq!(link.transmitting(), None);
+    }
+
+    #[test]
+    fn transmitting_goes_stale_when_wsjtx_falls_silent() {
+        let link = WsjtxLink::default();
+        link.received();
+        link.status_seen("WSJT-X", true);
+        let now = Instant::now();
+        assert_eq!(link.transmitting_at(now), Some(true));
+        assert_eq!(link.transmitting_at(now + STATUS_FRESH), None);
+    }
+
+    #[tokio::test]
+    async fn close_forgets_instance_address() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let link = WsjtxLink::default();
+        link.attach(Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+        let src: SocketAddr = "127.0.0.1:2238".parse().unwrap();
+        link.heard("WSJT-X", src);
+        link.heard("WSJT-X - Rig2", src);
+        let mut watchdog = TxWatchdog::new(Duration::from_secs(60));
+        watchdog.observe("WSJT-X", true, Instant::now());
+
+        let close = WsjtxMsg::Close(WsjtxClose {
+            id: "WSJT-X".to_string(),
+        });
+        let logger = QsoLogger {
+            client: Client::new(),
+            wavelog_settings: dummy_settings(),
+            local_adif: None,
+            spool: None,
+            poll: tokio::sync::mpsc::channel(1).0,
+            latency: SharedLatency::default(),
+        };
+        rxhandler(&logger, close, &socket, src, Some(&mut watchdog), &link).await;
+
+        let instances = link.inner.lock().unwrap().instances.clone();
+        assert!(!instances.contains_key("WSJT-X"));
+        assert!(instances.contains_key("WSJT-X - Rig2"));
+        assert!(watchdog.tx_since.is_empty());
+        // Even a Close is a sign of life for /health/wsjtx.
+        assert!(link.last_received().is_some());
+    }
+
+    #[tokio::test]
+    async fn unreachable_wavelog_spools_the_qso() {
+        let path = std::env::temp_dir().join("wlrigctl-wsjtx-spool.jsonl");
+        let _ = std::fs::remove_file(&path);
+        let spool = Arc::new(QsoSpool::new(&path));
+        let mut wavelog_settings = dummy_settings();
+        wavelog_settings.qso_url = "http://127.0.0.1:19999/api/qso".to_string();
+        let logger = QsoLogger {
+            client: Client::new(),
+            wavelog_settings,
+            local_adif: None,
+            spool: Some(spool.clone()),
+            poll: tokio::sync::mpsc::channel(1).0,
+            latency: SharedLatency::default(),
+        };
+
+        let msg = WsjtxMsg::LoggedADIF(WsjtxLoggedAdif {
+            id: "WSJT-X".to_string(),
+            adif_text: "<call:5>G4ABC <mode:3>FT8 <eor>".to_string(),
+        });
+        let result = handle_msg(&logger, msg).await;
+        assert!(matches!(result, Err(WsjtxError::QSOUploadFailed(_))));
+        assert_eq!(
+            spool.pending().await.unwrap(),
+            vec!["<call:5>G4ABC <mode:3>FT8 <eor>"]
+        );
+    }
+
+    #[tokio::test]
+    async fn logged_qso_is_written_to_local_adif() {
+        let path = std::env::temp_dir().join("wlrigctl-wsjtx-local.adi");
+        let _ = std::fs::remove_file(&path);
+        // Nothing listens on the QSO URL, so the upload fails; the local
+        // copy must be written anyway.
+        let mut wavelog_settings = dummy_settings();
+        wavelog_settings.qso_url = "http://127.0.0.1:19999/api/qso".to_string();
+        let logger = QsoLogger {
+            client: Client::new(),
+            wavelog_settings,
+            local_adif: Some(Arc::new(AdifLog::open(&path).unwrap())),
+            spool: None,
+            poll: tokio::sync::mpsc::channel(1).0,
+            latency: SharedLatency::default(),
+        };
+
+        let msg = WsjtxMsg::LoggedADIF(WsjtxLoggedAdif {
+            id: "WSJT-X".to_string(),
+            adif_text: "<adif_ver:5>3.1.0 <EOH>\n<call:5>G4ABC <mode:3>FT8".to_string(),
+        });
+        let result = handle_msg(&logger, msg).await;
+        assert!(matches!(result, Err(WsjtxError::QSOUploadFailed(_))));
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(
+            text.ends_with("<call:5>G4ABC <mode:3>FT8 <EOR>\n"),
+            "{text}"
+        );
+        assert!(!text.contains("3.1.0"), "{text}");
+    }
+
+    fn logged_ft8() -> WsjtxMsg {
+        WsjtxMsg::LoggedADIF(WsjtxLoggedAdif {
+            id: "WSJT-X".to_string(),
+            adif_text: "<call:5>G4ABC <freq:9>10.137500 <mode:3>FT8 <eor>".to_string(),
+        })
+    }
+
+    // A logger whose QSO posts go to a mock Wavelog, and whose requests to
+    // the poll loop arrive on the returned receiver.
+    async fn mock_logger(
+        tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) -> (QsoLogger, crate::wavelog::RefreshReceiver) {
+        let url = crate::mock_wavelog::mock_wavelog(reqwest::StatusCode::OK, tx).await;
+        let mut wavelog_settings = dummy_settings();
+        wavelog_settings.qso_url = url;
+        let (poll, poll_rx) = tokio::sync::mpsc::channel(1);
+        let logger = QsoLogger {
+            client: Client::new(),
+            wavelog_settings,
+            local_adif: None,
+            spool: None,
+            poll,
+            latency: SharedLatency::default(),
+        };
+        (logger, poll_rx)
+    }
+
+    #[tokio::test]
+    async fn logged_qso_updates_live_data() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let (logger, mut poll_rx) = mock_logger(tx).await;
+
+        let before = Instant::now();
+        handle_msg(&logger, logged_ft8()).await.unwrap();
+
+        let qso: serde_json::Value = serde_json::from_str(&rx.recv().await.unwrap()).unwrap();
+        assert_eq!(qso["type"], "adif");
+        match poll_rx.try_recv() {
+            Ok(PollRequest::QsoLogged {
+                frequency,
+                logged_at,
+            }) => {
+                assert_eq!(frequency, "10137500");
+                assert!(logged_at >= before);
+            }
+            _ => panic!("no live update for the logged QSO"),
+        }
+    }
+
+    #[tokio::test]
+    async fn free_text_rejects_overlong_text_and_unknown_peers() {
+        let link = WsjtxLink::default();
+        let long = "X".repeat(MAX_FREE_TEXT_CHARS + 1);
+        assert!(matches!(
+            link.send_free_text(&long, false).await,
+            Err(WsjtxError::InvalidCommand(_))
+        ));
+        assert!(matches!(
+            link.send_free_text("CQ", false).await,
+            Err(WsjtxError::NotConnected(_))
+        ));
+        link.attach(Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()));
+        assert!(matches!(
+            link.send_free_text("CQ", false).await,
+            Err(WsjtxError::NotConnected(_))
+        ));
+    }
+
+    #[test]
+    fn rxbuf_size_defaults_and_clamps() {
+        assert_eq!(rxbuf_size(None), SZ_RXBUF);
+        assert_eq!(rxbuf_size(Some(8192)), 8192);
+        assert_eq!(rxbuf_size(Some(100_000)), MAX_UDP_PAYLOAD);
+        assert_eq!(rxbuf_size(Some(0)), SZ_HDR + 1);
+    }
+
+    #[test]
+    fn datagram_shorter_than_buffer_is_accepted() {
+        assert!(check_datagram_len(SZ_RXBUF - 1, SZ_RXBUF).is_ok());
+    }
+
+    #[test]
+    fn configured_fork_magic_and_schema_are_accepted() {
+        let settings = WsjtxSettings {
+            host: "127.0.0.1".to_string(),
+            port: 2237,
+            err_timeout: 3,
+            forward_to: None,
+            rxbuf_bytes: None,
+            max_tx_secs: None,
+            dual_stack: None,
+            magic: Some(vec!["0xadbccbda".to_string(), "0xCAFEBABE".to_string()]),
+            schema: Some(vec![2, 3]),
+        };
+        let accepted = AcceptedHeader::new(&settings).unwrap();
+
+        let packet = make_packet(0xCAFE_BABE, 3, WsjtxMsg::Clear);
+        assert_eq!(parse_datagram(&packet, &accepted).unwrap(), WsjtxMsg::Clear);
+        let packet = make_packet(WSJTX_MAGIC, 2, WsjtxMsg::Clear);
+        assert_eq!(parse_datagram(&packet, &accepted).unwrap(), WsjtxMsg::Clear);
+
+        // The default still turns the fork away.
+        let packet = make_packet(0xCAFE_BABE, 2, WsjtxMsg::Clear);
+        assert!(matches!(
+            parse_datagram(&packet, &AcceptedHeader::default()),
+            Err(WsjtxError::BadMajick(_))
+        ));
+    }
+
+    #[test]
+    fn configured_magic_must_be_a_u32() {
+        assert_eq!(parse_magic("0xadbccbda"), Ok(WSJTX_MAGIC));
+        assert_eq!(parse_magic("2914831322"), Ok(WSJTX_MAGIC));
+        assert!(parse_magic("0x1adbccbda").is_err());
+        assert!(parse_magic("magic").is_err());
+        assert!(parse_magic("-1").is_err());
+    }
+
+    #[tokio::test]
+    async fn decode_hdr_unsupported_schema() {
+        let buf = make_packet(WSJTX_MAGIC, 3, WsjtxMsg::Clear);
+        let result = decode_hdr(&Client::new(), dummy_settings(), &buf).await;
+        assert!(matches!(result, Err(WsjtxError::UnsupportedSchema(_))));
+    }
 }